@@ -0,0 +1,86 @@
+use serde_json::{json, Value};
+
+use crate::{Cache, Link, Result};
+
+impl Cache {
+    /// Searches like search_limited(), returning the results as an Alfred
+    /// Script Filter JSON document ({"items": [...]}) which a workflow script
+    /// can print as is. Each item's arg is the link's url, and its uid lets
+    /// Alfred learn which links the user picks most. Items show the site's
+    /// favicon when the Cache has one (see Cache::extract_icons).
+    pub fn search_alfred_json(&self, query: &str, limit: u32) -> Result<String> {
+        let mut links = self.search_limited(query, limit)?;
+        self.extract_icons(&mut links)?;
+        let items: Vec<Value> = links.iter().map(alfred_item).collect();
+        Ok(serde_json::to_string(&json!({ "items": items }))?)
+    }
+}
+
+/// Builds the Script Filter item for a link. Links without a title show
+/// their url instead, as do links without a subtitle.
+fn alfred_item(link: &Link) -> Value {
+    let title = Some(link.title.clone())
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| link.display_url());
+    let subtitle = link
+        .subtitle
+        .clone()
+        .filter(|subtitle| !subtitle.is_empty())
+        .unwrap_or_else(|| link.display_url());
+    let mut item = json!({
+        "uid": link.url,
+        "title": title,
+        "subtitle": subtitle,
+        "arg": link.url,
+        "quicklookurl": link.url,
+        "text": { "copy": link.url, "largetype": title },
+    });
+    if let Some(icon_path) = &link.icon_path {
+        item["icon"] = json!({ "path": icon_path });
+    }
+    item
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::create_test_cache;
+
+    #[test]
+    fn test_search_alfred_json() -> Result<()> {
+        let mut cache = create_test_cache();
+        cache.add(
+            Link::new(
+                "https://doc.rust-lang.org/book/".to_string(),
+                "The Rust Programming Language".to_string(),
+            )
+            .with_subtitle("Bookmarks Bar / Rust".to_string()),
+        )?;
+        cache.add(Link::new(
+            "https://www.rust-lang.org/".to_string(),
+            "Rust".to_string(),
+        ))?;
+
+        let json: Value = serde_json::from_str(&cache.search_alfred_json("rust", 10)?)?;
+        let items = json["items"].as_array().expect("items should be an array");
+        assert_eq!(items.len(), 2);
+        let book = items
+            .iter()
+            .find(|item| item["uid"] == "https://doc.rust-lang.org/book/")
+            .expect("The book should be listed");
+        assert_eq!(book["title"], "The Rust Programming Language");
+        assert_eq!(book["subtitle"], "Bookmarks Bar / Rust");
+        assert_eq!(book["arg"], "https://doc.rust-lang.org/book/");
+        let home = items
+            .iter()
+            .find(|item| item["arg"] == "https://www.rust-lang.org/")
+            .expect("The home page should be listed");
+        assert_eq!(home["subtitle"], "rust-lang.org");
+
+        let json: Value = serde_json::from_str(&cache.search_alfred_json("rust", 1)?)?;
+        assert_eq!(json["items"].as_array().map(Vec::len), Some(1));
+        let json: Value = serde_json::from_str(&cache.search_alfred_json("nothing", 10)?)?;
+        assert_eq!(json, json!({ "items": [] }));
+        Ok(())
+    }
+}
@@ -1,13 +1,19 @@
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufReader;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use super::sidebar::SidebarState;
+use super::sidebar::{Bookmark, OutlineNode, SidebarState};
 use crate::error::Result;
-use crate::Link;
+use crate::merge::SUBTITLE_SEPARATOR;
+use crate::{BrowserSource, Link, LinkKind};
+
+/// The source recorded on every Link imported from Arc
+pub const SOURCE: &str = "arc";
 
 pub struct Browser {
     profile_dir: PathBuf,
+    include_archived: bool,
 }
 
 impl Browser {
@@ -16,6 +22,7 @@ impl Browser {
     pub fn new() -> Self {
         Browser {
             profile_dir: Self::default_profile_dir(),
+            include_archived: false,
         }
     }
 
@@ -27,7 +34,16 @@ impl Browser {
         self
     }
 
-    /// Sidebar links builds a Link object for each item in the Arc sidebar
+    /// Includes archived tabs (those Arc flagged as archived or which live
+    /// under an Archive folder) in sidebar_links. They're skipped by default.
+    pub fn with_include_archived(mut self, include_archived: bool) -> Self {
+        self.include_archived = include_archived;
+        self
+    }
+
+    /// Sidebar links builds a Link object for each item in the Arc sidebar.
+    /// A url pinned in several places (e.g. in two spaces) is returned once,
+    /// with the distinct paths it's pinned under joined into its subtitle.
     ///
     pub fn sidebar_links(&self) -> Result<Vec<Link>> {
         // Data values
@@ -35,23 +51,53 @@ impl Browser {
         let bookmarks = state.bookmarks();
 
         let mut links: Vec<Link> = vec![];
+        let mut url_index: HashMap<String, usize> = HashMap::new();
 
         for bookmark in bookmarks {
+            if !self.include_archived && self.is_archived(&mut state, &bookmark)? {
+                continue;
+            }
             let title = bookmark.title().unwrap_or_default();
             let url = bookmark.data.tab.saved_url.unwrap_or_default();
-            let mut link = Link::new(url, title);
+            let guid = Link::deterministic_guid(SOURCE, &url);
+            let mut link = Link::new(url, title)
+                .with_guid(guid)
+                .with_source(SOURCE.to_string())
+                .with_kind(LinkKind::Bookmark);
             if let Some(parent_id) = bookmark.parent_id {
                 let ancestor_titles = state.ancestor_titles(&parent_id)?;
                 if !ancestor_titles.is_empty() {
                     link = link.with_subtitle(ancestor_titles);
                 }
             }
-            links.push(link);
+            match url_index.get(&link.url) {
+                Some(&index) => add_subtitle(&mut links[index], link.subtitle),
+                None => {
+                    url_index.insert(link.url.clone(), links.len());
+                    links.push(link);
+                }
+            }
         }
 
         Ok(links)
     }
 
+    /// Returns the whole sidebar as a nested outline of spaces, folders and
+    /// bookmarks (see SidebarState::outline), e.g. for showing it as a tree.
+    pub fn sidebar_outline(&self) -> Result<Vec<OutlineNode>> {
+        self.sidebar_json()?.outline()
+    }
+
+    fn is_archived(&self, state: &mut SidebarState, bookmark: &Bookmark) -> Result<bool> {
+        if bookmark.is_archived {
+            return Ok(true);
+        }
+        match &bookmark.parent_id {
+            Some(parent_id) => state.in_archive(parent_id),
+            None => Ok(false),
+        }
+    }
+
     fn sidebar_json(&self) -> Result<SidebarState> {
         let file = File::open(self.sidebar_path())?;
         let reader = BufReader::new(file);
@@ -71,15 +117,51 @@ impl Browser {
     /// user's operating system and detected home directory.
     pub fn default_profile_dir() -> PathBuf {
         let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/tmp"));
-        let arc_data_dir = match std::env::consts::OS {
+        Self::profile_dir_in(std::env::consts::OS, &home_dir)
+    }
+
+    /// Returns the Arc profile directory on the given operating system (as
+    /// named by std::env::consts::OS) under the provided home directory.
+    pub fn profile_dir_in(os: &str, home_dir: &Path) -> PathBuf {
+        match os {
             "macos" => home_dir.join("Library/Application Support/Arc"),
             // TODO linux is untested
             "linux" => home_dir.join(".config/arc"),
             // TODO windows is untested
             "windows" => home_dir.join("AppData/Local/Arc"),
             _ => home_dir.join(".config/arc"),
-        };
-        arc_data_dir
+        }
+    }
+}
+
+/// Adds another path a link is pinned under to its subtitle, unless the
+/// subtitle already lists it.
+fn add_subtitle(link: &mut Link, subtitle: Option<String>) {
+    let Some(subtitle) = subtitle else {
+        return;
+    };
+    match &mut link.subtitle {
+        Some(existing) => {
+            if !existing
+                .split(SUBTITLE_SEPARATOR)
+                .any(|path| path == subtitle)
+            {
+                existing.push_str(SUBTITLE_SEPARATOR);
+                existing.push_str(&subtitle);
+            }
+        }
+        None => link.subtitle = Some(subtitle),
+    }
+}
+
+impl BrowserSource for Browser {
+    /// The sidebar's links (see sidebar_links).
+    fn links(&self) -> Result<Vec<Link>> {
+        self.sidebar_links()
+    }
+
+    fn source_name(&self) -> &'static str {
+        SOURCE
     }
 }
 
@@ -123,6 +205,69 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_sidebar_links_are_deduplicated_by_url() -> Result<()> {
+        let links = test_browser().sidebar_links()?;
+        let pinned: Vec<&Link> = links
+            .iter()
+            .filter(|l| l.url == "https://github.com/adlio/linkcache")
+            .collect();
+        // Pinned in both the Work and Personal spaces
+        assert_eq!(pinned.len(), 1);
+        assert_eq!(
+            pinned[0].subtitle,
+            Some("Personal / Entertainment | Work / Projects / linkcache".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_archived_links_are_excluded_by_default() -> Result<()> {
+        let archived_urls = [
+            "https://example.com/archived/sprint-board",
+            "https://example.com/archived/closed-tab",
+        ];
+
+        let links = test_browser().sidebar_links()?;
+        assert!(links
+            .iter()
+            .all(|l| !archived_urls.contains(&l.url.as_str())));
+
+        let links = test_browser().with_include_archived(true).sidebar_links()?;
+        assert_eq!(links.len(), 11);
+        for url in archived_urls {
+            assert!(
+                links.iter().any(|l| l.url == url),
+                "{} should be included",
+                url
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_sidebar_outline() -> Result<()> {
+        let outline = test_browser().sidebar_outline()?;
+        let titles: Vec<&str> = outline.iter().map(|n| n.title.as_str()).collect();
+        assert_eq!(titles, vec!["Work", "Personal"]);
+
+        let child = |node: &OutlineNode, title: &str| -> OutlineNode {
+            node.children
+                .iter()
+                .find(|c| c.title == title)
+                .unwrap_or_else(|| panic!("{} should contain {}", node.title, title))
+                .clone()
+        };
+        let alfred = child(&child(&outline[0], "Areas"), "Alfred");
+        let script_filter = child(&alfred, "Script Filter JSON Format");
+        assert_eq!(
+            script_filter.url,
+            Some("https://www.alfredapp.com/help/workflows/inputs/script-filter/json/".to_string())
+        );
+        assert!(script_filter.children.is_empty());
+        Ok(())
+    }
+
     #[test]
     fn test_storable_sidebar() -> Result<()> {
         let browser = Browser::new().with_profile_dir(PathBuf::from("./test_data"));
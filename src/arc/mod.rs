@@ -1,4 +1,5 @@
 mod browser;
 mod sidebar;
 
-pub use browser::Browser;
+pub use browser::{Browser, SOURCE};
+pub use sidebar::OutlineNode;
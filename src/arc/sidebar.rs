@@ -84,6 +84,8 @@ pub struct Bookmark {
     pub data: SidebarTabData,
     #[serde(rename = "parentID")]
     pub parent_id: Option<String>,
+    #[serde(rename = "isArchived", default)]
+    pub is_archived: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -133,7 +135,79 @@ pub struct Tab {
     pub saved_url: Option<String>,
 }
 
+/// One entry in the nested outline of the sidebar: a space, a folder, or a
+/// bookmark (the only kind of node with a url).
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlineNode {
+    pub id: String,
+    pub title: String,
+    pub url: Option<String>,
+    pub children: Vec<OutlineNode>,
+}
+
 impl SidebarState {
+    /// Returns the whole sidebar as a tree: each space, containing its
+    /// folders and bookmarks nested as they appear in Arc. The untitled
+    /// pinned/unpinned containers of a space are left out, with their
+    /// contents placed directly under the space. Archived items are included.
+    pub fn outline(&mut self) -> Result<Vec<OutlineNode>> {
+        self.build_item_map()?;
+
+        let mut spaces = vec![];
+        for container in &self.sidebar.containers {
+            let SidebarContainer::SpacesAndItems(spaces_and_items) = container else {
+                continue;
+            };
+            for space in &spaces_and_items.spaces {
+                let SpaceType::Space(space) = space else {
+                    continue;
+                };
+                let container_ids = space.container_ids.as_array().cloned().unwrap_or_default();
+                let children = container_ids
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .filter_map(|id| match self.item_map.get(id) {
+                        Some(Node::Folder(folder)) => Some(folder),
+                        _ => None,
+                    })
+                    .flat_map(|folder| &folder.children_ids)
+                    .filter_map(|id| self.outline_node(id))
+                    .collect();
+                spaces.push(OutlineNode {
+                    id: space.id.clone(),
+                    title: space.title.clone().unwrap_or_default(),
+                    url: None,
+                    children,
+                });
+            }
+        }
+        Ok(spaces)
+    }
+
+    /// Returns the outline of the item with the provided id and everything
+    /// under it, or None when the id isn't a folder or bookmark.
+    fn outline_node(&self, id: &str) -> Option<OutlineNode> {
+        match self.item_map.get(id)? {
+            Node::Folder(folder) => Some(OutlineNode {
+                id: folder.id.clone(),
+                title: folder.title.clone().unwrap_or_default(),
+                url: None,
+                children: folder
+                    .children_ids
+                    .iter()
+                    .filter_map(|child_id| self.outline_node(child_id))
+                    .collect(),
+            }),
+            Node::Bookmark(bookmark) => Some(OutlineNode {
+                id: bookmark.id.clone(),
+                title: bookmark.title().unwrap_or_default(),
+                url: bookmark.data.tab.saved_url.clone(),
+                children: vec![],
+            }),
+            Node::Space(_) => None,
+        }
+    }
+
     pub fn ancestor_titles(&mut self, id: &str) -> Result<String> {
         self.build_item_map()?;
 
@@ -170,6 +244,33 @@ impl SidebarState {
         Ok(titles.join(" / "))
     }
 
+    /// Returns true when the item with the provided id lives anywhere under
+    /// a folder titled "Archive".
+    pub fn in_archive(&mut self, id: &str) -> Result<bool> {
+        self.build_item_map()?;
+
+        let mut current_id = id.to_string();
+        while let Some(node) = self.item_map.get(current_id.as_str()) {
+            match node {
+                Node::Folder(folder) => {
+                    let title = folder.title.clone().unwrap_or_default();
+                    if title.trim().eq_ignore_ascii_case("archive") {
+                        return Ok(true);
+                    }
+                    match folder.parent_id() {
+                        Some(pid) => current_id = pid,
+                        None => break,
+                    }
+                }
+                Node::Space(_) => break,
+                Node::Bookmark(bookmark) => {
+                    current_id = bookmark.parent_id.clone().unwrap_or_default();
+                }
+            }
+        }
+        Ok(false)
+    }
+
     pub fn build_item_map(&mut self) -> Result<()> {
         if !self.item_map.is_empty() {
             return Ok(());
@@ -293,6 +394,7 @@ mod tests {
                 },
             },
             parent_id: None,
+            is_archived: false,
         };
         assert_eq!(bookmark.title(), Some("Saved Title".to_string()));
     }
@@ -309,6 +411,7 @@ mod tests {
                 },
             },
             parent_id: None,
+            is_archived: false,
         };
         assert_eq!(bookmark.title(), Some("Human Title".to_string()));
     }
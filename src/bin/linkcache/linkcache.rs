@@ -1,7 +1,8 @@
 use alfrusco::{config, Item, Runnable, URLItem, Workflow};
-use clap::Parser;
-use linkcache::{arc, Cache};
+use clap::{Parser, Subcommand};
+use linkcache::{arc, import, BrowserSource, Cache};
 use log::{info};
+use std::path::PathBuf;
 use std::process::Command;
 use std::time::Duration;
 
@@ -15,28 +16,55 @@ use error::WorkflowError;
 #[command(version = "0.1.0")]
 #[command(about = "Linkcache Utility")]
 #[command(version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
 struct LinkCacheCLI {
+    #[command(subcommand)]
+    command: Option<LinkCacheCommand>,
+
     query: Vec<String>,
 
     #[clap(long, env = "UPDATE_ARC_CACHE", default_value = "false")]
     update_arc_cache: bool,
 }
 
+#[derive(Subcommand, Debug)]
+enum LinkCacheCommand {
+    /// Imports links from a file into the cache
+    Import {
+        /// One of json, html, opml or markdown
+        #[arg(long)]
+        format: import::Format,
+
+        #[arg(long)]
+        input: PathBuf,
+    },
+}
+
 fn main() {
     env_logger::init();
     let command = LinkCacheCLI::parse();
 
+    if let Some(LinkCacheCommand::Import { format, input }) = &command.command {
+        let mut cache = Cache::default().expect("Could not create cache");
+        match import::import_file(&mut cache, *format, input) {
+            Ok(count) => println!("Imported {} links from {}", count, input.display()),
+            Err(err) => {
+                eprintln!(
+                    "Could not import {} as {}: {}",
+                    input.display(),
+                    format,
+                    err
+                );
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     if command.update_arc_cache {
         let mut cache = Cache::default().expect("Could not create cache");
         let arc = arc::Browser::new();
-        let links = arc
-            .sidebar_links()
-            .expect("Could not get Arc sidebar links");
-        for link in links {
-            cache
-                .add(link.clone())
-                .expect("Could not insert link into cache");
-        }
+        arc.cache_links(&mut cache).expect("Could not cache Arc sidebar links");
         return;
     }
 
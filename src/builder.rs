@@ -0,0 +1,380 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::Utc;
+use log::warn;
+use rusqlite::{Connection, ErrorCode, OpenFlags};
+
+use crate::error::{Error, Result};
+use crate::link::DEFAULT_BLOCKED_SCHEMES;
+use crate::replica::{sidecar, SHM_SUFFIX, WAL_SUFFIX};
+use crate::search::RankStrategy;
+use crate::{Cache, Link};
+
+/// Options which tune how a Cache behaves once it has been built. These are
+/// set through the CacheBuilder and carried by the Cache for its lifetime.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Options {
+    /// Multipliers applied to the relevance score of search results, keyed
+    /// by the link's source. Sources without an entry use 1.0.
+    pub source_boosts: HashMap<String, f32>,
+
+    /// When the same url is added from different sources, keep each
+    /// source's subtitle rather than only the latest one.
+    pub merge_subtitles: bool,
+
+    /// Lowercased query terms which are dropped before searching because
+    /// they match too many links to be useful.
+    pub stopwords: HashSet<String>,
+
+    /// The collection every add, search and remove is scoped to. None means
+    /// DEFAULT_COLLECTION.
+    pub collection: Option<String>,
+
+    /// History links older than the duration have their relevance scaled
+    /// by the factor. Bookmarks are never penalized.
+    pub history_age_penalty: Option<(Duration, f32)>,
+
+    /// Lowercased sources, most preferred first, deciding whose timestamp is
+    /// kept when the same url is added from several sources.
+    pub timestamp_precedence: Vec<String>,
+
+    /// Lowercased url schemes of links which are skipped rather than added.
+    /// None means DEFAULT_BLOCKED_SCHEMES.
+    pub blocked_schemes: Option<Vec<String>>,
+
+    /// How long links from each source stay searchable after they're added,
+    /// for links which don't set their own expires_at.
+    pub source_ttls: HashMap<String, Duration>,
+
+    /// Replace the subtitles of history links returned by search() with how
+    /// long ago they were visited.
+    pub relative_time_subtitles: bool,
+
+    /// Optimize the search index after this many links have been written.
+    pub auto_optimize: Option<usize>,
+}
+
+/// The collection links belong to when the Cache wasn't built with one.
+pub(crate) const DEFAULT_COLLECTION: &str = "default";
+
+impl Options {
+    /// Returns the name of the collection this Cache reads and writes.
+    pub fn collection(&self) -> &str {
+        self.collection.as_deref().unwrap_or(DEFAULT_COLLECTION)
+    }
+
+    /// Returns an error when the link's url has a blocked scheme, in which
+    /// case it's skipped rather than added (see Link::validate).
+    pub fn validate(&self, link: &Link) -> Result<()> {
+        match &self.blocked_schemes {
+            Some(schemes) => link.validate(schemes),
+            None => link.validate(DEFAULT_BLOCKED_SCHEMES),
+        }
+    }
+
+    /// Returns true when adding a link must first merge it with the link
+    /// already stored under its url.
+    pub fn merges(&self) -> bool {
+        self.merge_subtitles || !self.timestamp_precedence.is_empty()
+    }
+}
+
+/// What CacheBuilder::build does when the database file turns out to be
+/// corrupt (e.g. after losing power mid-write).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CorruptionPolicy {
+    /// Fail with the error SQLite reported, leaving the file untouched
+    #[default]
+    Fail,
+    /// Move the corrupt file aside and start over with an empty cache
+    Recreate,
+    /// Move the corrupt file aside and start a new cache, copying into it
+    /// every link which can still be read from the corrupt file
+    Recover,
+}
+
+/// CacheBuilder configures and opens a Cache. Cache::new() and
+/// Cache::default() are shortcuts for the most common configurations.
+#[derive(Debug, Default)]
+pub struct CacheBuilder {
+    path: Option<PathBuf>,
+    in_memory: bool,
+    must_exist: bool,
+    on_corruption: CorruptionPolicy,
+    options: Options,
+}
+
+impl CacheBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Overrides the location of the SQLite database file. When unset, the
+    /// cache is stored in ~/.linkcache/linkcache.sqlite.
+    pub fn with_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Keeps the cache in an in-memory SQLite database rather than a file,
+    /// e.g. for tests or tools which only need links for as long as they
+    /// run. The links are lost when the Cache is dropped. Takes precedence
+    /// over with_path.
+    pub fn in_memory(mut self) -> Self {
+        self.in_memory = true;
+        self
+    }
+
+    /// Controls whether build() creates the database when there isn't one
+    /// at the path yet (the default). When false, build() instead fails with
+    /// Error::CacheNotFound, for apps which expect the cache to have been
+    /// created already and would rather fail loudly than start empty.
+    pub fn create_if_missing(mut self, create_if_missing: bool) -> Self {
+        self.must_exist = !create_if_missing;
+        self
+    }
+
+    /// Sets what build() does when the database file is corrupt. By default
+    /// it fails, so the file can be inspected. Otherwise the corrupt file is
+    /// renamed (e.g. to linkcache.sqlite.corrupt-20240315120000) and a new
+    /// one is created in its place, so an app keeps working without the
+    /// user having to delete it. Ignored for in-memory caches.
+    pub fn on_corruption(mut self, policy: CorruptionPolicy) -> Self {
+        self.on_corruption = policy;
+        self
+    }
+
+    /// Sets a multiplier for the relevance score of search results from each
+    /// source (e.g. "arc" => 2.0 ranks Arc links above equally-relevant
+    /// links from other sources). Unspecified sources default to 1.0. Boosting
+    /// firefox::TAB_SOURCE brings tabs which are currently open to the top.
+    /// Boosts which aren't finite (NaN or infinite) are ignored with a
+    /// warning.
+    pub fn with_source_boosts(mut self, mut boosts: HashMap<String, f32>) -> Self {
+        boosts.retain(|source, boost| {
+            if !boost.is_finite() {
+                warn!("Ignoring the non-finite boost {} for {}", boost, source);
+            }
+            boost.is_finite()
+        });
+        self.options.source_boosts = boosts;
+        self
+    }
+
+    /// When enabled, adding a link whose url is already stored from another
+    /// source keeps both subtitles, labelled by source (e.g. "Firefox: Work |
+    /// Chrome: Bookmarks Bar"), up to a length cap. Disabled by default, in
+    /// which case the most recently added subtitle wins.
+    pub fn with_merge_subtitles(mut self, merge_subtitles: bool) -> Self {
+        self.options.merge_subtitles = merge_subtitles;
+        self
+    }
+
+    /// Sets the words (e.g. "the", "home", "login") which are ignored in
+    /// search queries because they appear in so many titles that they'd
+    /// dominate the matches. A query made up only of stopwords returns the
+    /// latest links, as an empty query does. Matching is case-insensitive.
+    pub fn with_stopwords<I, S>(mut self, stopwords: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.options.stopwords = stopwords
+            .into_iter()
+            .map(|word| word.as_ref().to_lowercase())
+            .collect();
+        self
+    }
+
+    /// Scopes the Cache to a named collection (e.g. "personal" or "work"), so
+    /// several independent sets of links can share one database. Links
+    /// added through this Cache are only visible to searches (and removes)
+    /// made through a Cache built with the same collection. When unset, the
+    /// "default" collection is used.
+    pub fn with_collection<S: Into<String>>(mut self, collection: S) -> Self {
+        self.options.collection = Some(collection.into());
+        self
+    }
+
+    /// De-emphasizes stale history: history links whose timestamp is older
+    /// than max_age have their relevance multiplied by penalty (e.g. 0.5
+    /// halves it), so a years-old visit doesn't outrank a recent bookmark
+    /// which matches the query as well. Bookmarks, and links of unknown
+    /// kind, are unaffected. Disabled by default, and left disabled (with a
+    /// warning) when penalty isn't finite.
+    pub fn with_history_age_penalty(mut self, max_age: Duration, penalty: f32) -> Self {
+        if !penalty.is_finite() {
+            warn!("Ignoring the non-finite history age penalty {}", penalty);
+            return self;
+        }
+        self.options.history_age_penalty = Some((max_age, penalty));
+        self
+    }
+
+    /// Sets which source's timestamp is kept when the same url is added from
+    /// several sources, most preferred first (e.g. ["arc", "firefox",
+    /// "chrome"] keeps Arc's timestamp over Firefox's, and Firefox's over
+    /// Chrome's). The link keeps the preferred source along with its
+    /// timestamp. Sources which aren't listed rank below every listed source,
+    /// and between equally-ranked sources the most recently added link wins,
+    /// as it does when no precedence is set.
+    pub fn with_timestamp_precedence<I, S>(mut self, sources: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.options.timestamp_precedence = sources
+            .into_iter()
+            .map(|source| source.as_ref().to_lowercase())
+            .collect();
+        self
+    }
+
+    /// Makes links from the provided sources expire (drop out of searches)
+    /// once the duration has passed since they were added, e.g. "tabs" =>
+    /// one hour for an importer of open tabs, which re-adds the tabs that
+    /// are still open. Links which set their own expires_at keep it.
+    pub fn with_source_ttls(mut self, ttls: HashMap<String, Duration>) -> Self {
+        self.options.source_ttls = ttls;
+        self
+    }
+
+    /// When enabled, history links returned by search() have their subtitle
+    /// replaced with how long ago they were last visited (e.g. "last visited
+    /// 3 days ago"), for views which care more about recency than folders.
+    /// Only the returned links change; what's stored is left as it is.
+    /// Disabled by default.
+    pub fn with_relative_time_subtitle(mut self, enabled: bool) -> Self {
+        self.options.relative_time_subtitles = enabled;
+        self
+    }
+
+    /// Runs Cache::optimize automatically once every_n_writes links have
+    /// been added since it last ran, so a cache which is filled by large
+    /// imports keeps a compact search index. Disabled by default.
+    pub fn with_auto_optimize(mut self, every_n_writes: usize) -> Self {
+        self.options.auto_optimize = Some(every_n_writes);
+        self
+    }
+
+    /// Replaces the url schemes (javascript and data, by default) of links
+    /// which are skipped when they're added or imported. An empty list lets
+    /// every link be cached, e.g. for users who deliberately keep
+    /// bookmarklets.
+    pub fn with_blocked_schemes<I, S>(mut self, schemes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.options.blocked_schemes = Some(
+            schemes
+                .into_iter()
+                .map(|scheme| scheme.as_ref().to_ascii_lowercase())
+                .collect(),
+        );
+        self
+    }
+
+    /// Opens (creating if necessary) the SQLite database and initializes its
+    /// schema. This could fail if the path isn't writeable, the database is
+    /// corrupt (see on_corruption), or the initialization process (creation
+    /// of tables, triggers, etc) fails.
+    pub fn build(self) -> Result<Cache> {
+        if self.in_memory {
+            return open(Connection::open_in_memory()?, self.options);
+        }
+        let path = match self.path {
+            Some(path) => path,
+            None => {
+                let cache_dir = default_data_dir();
+                if !self.must_exist {
+                    std::fs::create_dir_all(&cache_dir)?;
+                }
+                cache_dir.join("linkcache.sqlite")
+            }
+        };
+        let flags = if self.must_exist {
+            if !path.exists() {
+                return Err(Error::CacheNotFound(path));
+            }
+            OpenFlags::SQLITE_OPEN_READ_WRITE
+        } else {
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE
+        };
+        let conn = Connection::open_with_flags(&path, flags)?;
+        match open(conn, self.options.clone()) {
+            Err(err)
+                if is_corrupt_database(&err) && self.on_corruption != CorruptionPolicy::Fail =>
+            {
+                replace_corrupt(&path, &err, self.on_corruption, self.options)
+            }
+            result => result,
+        }
+    }
+}
+
+/// Wraps the connection in a Cache, bringing its schema up to date.
+fn open(conn: Connection, options: Options) -> Result<Cache> {
+    let mut cache = Cache {
+        conn,
+        options,
+        rank_strategy: RankStrategy::Rank,
+        writes_since_optimize: 0,
+    };
+    cache.register_functions()?;
+    cache.apply_migrations()?;
+    cache.reindex_if_unindexed()?;
+    cache.rank_strategy = RankStrategy::probe(&cache.conn);
+    Ok(cache)
+}
+
+/// Moves the corrupt database at the path (along with its write-ahead log)
+/// aside and opens a new cache in its place, recovering what links it can
+/// into it when the policy says to.
+fn replace_corrupt(
+    path: &Path,
+    err: &Error,
+    policy: CorruptionPolicy,
+    options: Options,
+) -> Result<Cache> {
+    let mut aside = path.as_os_str().to_os_string();
+    aside.push(format!(".corrupt-{}", Utc::now().format("%Y%m%d%H%M%S")));
+    let aside = PathBuf::from(aside);
+    warn!(
+        "{} is corrupt ({}); moving it to {}",
+        path.display(),
+        err,
+        aside.display()
+    );
+    fs::rename(path, &aside)?;
+    for suffix in [WAL_SUFFIX, SHM_SUFFIX] {
+        let _ = fs::rename(sidecar(path, suffix), sidecar(&aside, suffix));
+    }
+
+    let mut cache = open(Connection::open(path)?, options)?;
+    if policy == CorruptionPolicy::Recover {
+        let recovered = cache.salvage_links(&aside)?;
+        warn!("Recovered {} links from {}", recovered, aside.display());
+    }
+    Ok(cache)
+}
+
+/// Returns true when the error means the database file is damaged, or isn't
+/// a database at all.
+fn is_corrupt_database(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::Rusqlite(rusqlite::Error::SqliteFailure(e, _))
+            if e.code == ErrorCode::DatabaseCorrupt || e.code == ErrorCode::NotADatabase
+    )
+}
+
+/// Returns the directory where the cache is stored when no path is provided.
+pub fn default_data_dir() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join(".linkcache")
+}
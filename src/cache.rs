@@ -1,10 +1,21 @@
-use rusqlite::{Connection, OpenFlags};
+use chrono::{Days, FixedOffset, NaiveDate, TimeZone, Utc};
+use log::debug;
+use rusqlite::{params, Connection, OptionalExtension, Row, ToSql};
 use std::path::{Path, PathBuf};
 
-use crate::{error::Result, Link};
+use crate::builder::Options;
+use crate::link::normalize_url;
+use crate::merge::merge;
+use crate::search::RankStrategy;
+use crate::{error::Result, CacheBuilder, Error, Link};
 
 pub struct Cache {
     pub(crate) conn: Connection,
+    pub(crate) options: Options,
+    pub(crate) rank_strategy: RankStrategy,
+    /// Links written since the search index was last optimized, counted when
+    /// the Cache was built with_auto_optimize.
+    pub(crate) writes_since_optimize: usize,
 }
 
 impl Cache {
@@ -13,111 +24,436 @@ impl Cache {
     /// writeable, or the initialization process (creation of tables,
     /// triggers, etc) fails.
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let conn = Connection::open_with_flags(
-            path,
-            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
-        )?;
-        let cache = Cache { conn };
-        cache.initialize()?;
-        Ok(cache)
+        CacheBuilder::new().with_path(path.as_ref()).build()
     }
 
     pub fn default() -> Result<Self> {
-        let cache_dir = dirs::home_dir()
-            .unwrap_or_else(|| PathBuf::from("/tmp"))
-            .join(".linkcache");
-        std::fs::create_dir_all(&cache_dir)?;
-        let db_path = cache_dir.join("linkcache.sqlite");
-        Self::new(db_path)
-    }
-
-    /// Adds a new link to the index. The url field is used as the unique
-    /// key. This function removes any existing link with the same url before
-    /// saving a new one. The commit() function must be called after adding
-    /// to persist the changes. Batch updates should call add() many times
-    /// and commit() once.
+        CacheBuilder::new().build()
+    }
+
+    /// Returns the directory holding the Cache's database file, where files
+    /// derived from it (e.g. extracted favicons) are written. In-memory
+    /// caches have none.
+    pub fn data_dir(&self) -> Option<PathBuf> {
+        let path = self.conn.path().filter(|path| !path.is_empty())?;
+        Path::new(path).parent().map(Path::to_path_buf)
+    }
+
+    /// Adds a new link to the index. The url field, once normalized (see
+    /// normalize_url), is used as the unique key. This function removes any
+    /// existing link with the same url before saving a new one. The commit()
+    /// function must be called after adding to persist the changes. Batch
+    /// updates should call add() many times and commit() once. Links whose
+    /// url scheme is blocked (javascript: and data: unless the Cache was
+    /// built with other blocked schemes) are skipped.
     pub fn add(&mut self, link: Link) -> Result<()> {
-        // let json_str = to_string(&link)?;
+        if Self::insert(&self.conn, &self.options, link)? {
+            self.wrote(1)?;
+        }
+        Ok(())
+    }
 
-        self.conn.execute(
+    /// Adds many links (e.g. a whole browser history) in a single
+    /// transaction, which is far faster than calling add() for each. Either
+    /// every link is written or, on the first failure, none are. Returns the
+    /// number of links written, which leaves out those with a blocked url
+    /// scheme.
+    pub fn add_batch(&mut self, links: impl IntoIterator<Item = Link>) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        let mut written = 0;
+        for link in links {
+            if Self::insert(&tx, &self.options, link)? {
+                written += 1;
+            }
+        }
+        tx.commit()?;
+        self.wrote(written)?;
+        Ok(written)
+    }
+
+    /// Counts links written towards the next automatic optimize(), running
+    /// it once the threshold set by with_auto_optimize is reached.
+    fn wrote(&mut self, count: usize) -> Result<()> {
+        let Some(every_n_writes) = self.options.auto_optimize else {
+            return Ok(());
+        };
+        self.writes_since_optimize += count;
+        if self.writes_since_optimize >= every_n_writes {
+            self.optimize()?;
+        }
+        Ok(())
+    }
+
+    /// Merges the search index's segments into one, which speeds up searches
+    /// after large imports. This rewrites the whole index, so it's best run
+    /// occasionally (see CacheBuilder::with_auto_optimize) rather than after
+    /// every write.
+    pub fn optimize(&mut self) -> Result<()> {
+        self.conn
+            .execute("INSERT INTO links_fts (links_fts) VALUES ('optimize')", [])?;
+        self.writes_since_optimize = 0;
+        Ok(())
+    }
+
+    /// Writes a single link using the provided connection, which may be a
+    /// transaction wrapping many inserts. A link with an empty guid is given
+    /// a deterministic one (see Link::deterministic_guid), since an empty
+    /// guid would identify it with every other link which has one. A link
+    /// whose url is a variant of a stored link's url (see normalize_url)
+    /// replaces that link, keeping the url it was first stored under. When
+    /// the options call for it, the link is first merged with any existing
+    /// link stored under its url. Returns false when the link was skipped
+    /// because of its url scheme.
+    pub(crate) fn insert(conn: &Connection, options: &Options, mut link: Link) -> Result<bool> {
+        if let Err(err) = options.validate(&link) {
+            debug!("Skipping {}: {}", link.url, err);
+            return Ok(false);
+        }
+
+        if link
+            .guid
+            .as_deref()
+            .is_some_and(|guid| guid.trim().is_empty())
+        {
+            let source = link.source.as_deref().unwrap_or_default();
+            link.guid = Some(Link::deterministic_guid(source, &link.url));
+        }
+
+        let normalized_url = link.normalized_url();
+        let stored_url: Option<String> = conn
+            .query_row(
+                "SELECT url FROM links WHERE normalized_url = ?1 AND collection = ?2",
+                (&normalized_url, options.collection()),
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(stored_url) = stored_url {
+            link.url = stored_url;
+        }
+
+        let mut link = if options.merges() {
+            match Self::find_by_url(conn, options.collection(), &link.url, None)? {
+                Some(existing) => merge(&existing, link, options),
+                None => link,
+            }
+        } else {
+            link
+        };
+
+        if link.expires_at.is_none() {
+            let ttl = link
+                .source
+                .as_ref()
+                .and_then(|source| options.source_ttls.get(source));
+            link.expires_at = ttl
+                .and_then(|ttl| chrono::Duration::from_std(*ttl).ok())
+                .and_then(|ttl| Utc::now().checked_add_signed(ttl));
+        }
+
+        // A link the browser identifies by guid may have moved to a new url
+        if let Some(guid) = &link.guid {
+            conn.execute(
+                "DELETE FROM links WHERE guid = ?1 AND collection = ?2 AND url != ?3",
+                (guid, options.collection(), &link.url),
+            )?;
+        }
+
+        // Importers don't know when a link was last opened, so re-importing
+        // must not erase a previously recorded last_opened_at. Nor should an
+        // importer which doesn't provide descriptions (or thumbnails, visit
+        // counts, or redirects) erase them.
+        conn.execute(
             "INSERT OR REPLACE INTO links (
                 url, title, subtitle,
                 source, author,
-                timestamp
+                timestamp, description,
+                last_opened_at, collection,
+                kind, guid, expires_at,
+                profile, thumbnail,
+                visit_count, typed_count,
+                canonical_url, normalized_url
             ) VALUES (
                 ?1, ?2, ?3,
                 ?4, ?5,
-                ?6
+                ?6,
+                COALESCE(?7, (SELECT description FROM links WHERE normalized_url = ?18 AND collection = ?9)),
+                COALESCE(?8, (SELECT last_opened_at FROM links WHERE normalized_url = ?18 AND collection = ?9)),
+                ?9,
+                ?10, ?11, ?12,
+                ?13,
+                COALESCE(?14, (SELECT thumbnail FROM links WHERE normalized_url = ?18 AND collection = ?9)),
+                COALESCE(?15, (SELECT visit_count FROM links WHERE normalized_url = ?18 AND collection = ?9)),
+                COALESCE(?16, (SELECT typed_count FROM links WHERE normalized_url = ?18 AND collection = ?9)),
+                -- A link with no known redirect is its own canonical url
+                COALESCE(?17, (SELECT canonical_url FROM links WHERE normalized_url = ?18 AND collection = ?9), ?1),
+                ?18
             )",
-            (
+            params![
                 &link.url,
                 &link.title,
                 &link.subtitle,
                 &link.source,
                 &link.author,
                 &link.timestamp,
-            ),
+                &link.description,
+                &link.last_opened_at,
+                options.collection(),
+                &link.kind,
+                &link.guid,
+                &link.expires_at,
+                &link.profile,
+                link.thumbnail.as_ref().map(|path| path.to_string_lossy()),
+                &link.visit_count,
+                &link.typed_count,
+                &link.canonical_url,
+                &normalized_url,
+            ],
         )?;
-        Ok(())
+        Ok(true)
+    }
+
+    /// Returns the link stored under this URL, or a variant of it (see
+    /// normalize_url), in the provided collection (and from the provided
+    /// source, when there is one), using the provided connection. Removed
+    /// and expired links aren't returned.
+    pub(crate) fn find_by_url(
+        conn: &Connection,
+        collection: &str,
+        url: &str,
+        source: Option<&str>,
+    ) -> Result<Option<Link>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {LINK_COLUMNS}
+             FROM links
+             WHERE links.normalized_url = ?1 AND links.collection = ?2
+             AND (?3 IS NULL OR links.source = ?3)
+             AND links.deleted_at IS NULL AND {UNEXPIRED}",
+        ))?;
+        let mut links_iter =
+            stmt.query_map((&normalize_url(url), collection, source), link_from_row)?;
+        Ok(links_iter.next().transpose()?)
+    }
+
+    /// Records that the link with the provided url was just opened, updating
+    /// its last_opened_at without changing its (import) timestamp. Returns
+    /// false if no link is stored under the url.
+    pub fn record_open(&mut self, url: &str) -> Result<bool> {
+        let updated = self.conn.execute(
+            "UPDATE links SET last_opened_at = ?1
+             WHERE normalized_url = normalize_url(?2) AND collection = ?3",
+            (Utc::now(), url, self.options.collection()),
+        )?;
+        Ok(updated > 0)
     }
 
     /// Removes a Link from the index. The url field is used as the unique key.
+    /// The link is only marked as deleted, so it can be brought back with
+    /// restore() until purge_deleted() is called. Adding the link again
+    /// also restores it.
     pub fn remove(&mut self, link: &Link) -> Result<()> {
-        self.conn
-            .execute("DELETE FROM links WHERE url = ?1", [&link.url])?;
+        self.conn.execute(
+            "UPDATE links SET deleted_at = ?1
+             WHERE normalized_url = normalize_url(?2) AND collection = ?3
+             AND deleted_at IS NULL",
+            (Utc::now(), &link.url, self.options.collection()),
+        )?;
 
         Ok(())
     }
 
-    /// Searches the index for linkx matching the query
-    pub fn search(&self, query: &str) -> Result<Vec<Link>> {
-        if query.is_empty() {
-            return self.get_latest_n(50);
+    /// Removes every link stored under one of the provided urls, as remove()
+    /// does, in a single transaction rather than a statement per link.
+    /// Returns the number of links removed; urls which aren't stored (or are
+    /// already removed) are ignored.
+    pub fn remove_urls(&mut self, urls: &[&str]) -> Result<usize> {
+        let deleted_at = Utc::now();
+        let collection = self.options.collection();
+        let tx = self.conn.transaction()?;
+        let mut removed = 0;
+        for chunk in urls.chunks(MAX_URLS_PER_STATEMENT) {
+            let placeholders = vec!["normalize_url(?)"; chunk.len()].join(", ");
+            let mut params: Vec<&dyn ToSql> = vec![&deleted_at, &collection];
+            params.extend(chunk.iter().map(|url| url as &dyn ToSql));
+            removed += tx.execute(
+                &format!(
+                    "UPDATE links SET deleted_at = ?
+                     WHERE collection = ? AND deleted_at IS NULL
+                     AND normalized_url IN ({placeholders})"
+                ),
+                params.as_slice(),
+            )?;
         }
+        tx.commit()?;
+        Ok(removed)
+    }
 
-        let mut stmt = self.conn.prepare(
-            "SELECT links.* FROM links_fts
-             JOIN links ON links_fts.url = links.url
-             WHERE links_fts MATCH ?1
-             ORDER BY rank",
+    /// Undoes remove() for the link with the provided url. Returns false if
+    /// there's no removed link stored under the url.
+    pub fn restore(&mut self, url: &str) -> Result<bool> {
+        let restored = self.conn.execute(
+            "UPDATE links SET deleted_at = NULL
+             WHERE normalized_url = normalize_url(?1) AND collection = ?2
+             AND deleted_at IS NOT NULL",
+            (url, self.options.collection()),
         )?;
+        Ok(restored > 0)
+    }
 
-        let links_iter = stmt.query_map([query], |row| {
-            Ok(Link {
-                url: row.get(0)?,
-                title: row.get(1)?,
-                subtitle: row.get(2)?,
-                source: row.get(3)?,
-                author: row.get(4)?,
-                timestamp: row.get(5)?,
-                ..Default::default()
-            })
-        })?;
+    /// Permanently deletes every removed link, after which they can't be
+    /// restored. Returns the number of links deleted.
+    pub fn purge_deleted(&mut self) -> Result<usize> {
+        let purged = self.conn.execute(
+            "DELETE FROM links WHERE collection = ?1 AND deleted_at IS NOT NULL",
+            [self.options.collection()],
+        )?;
+        Ok(purged)
+    }
+
+    /// Permanently deletes every link whose expires_at has passed. Expired
+    /// links are already left out of searches, so this only reclaims their
+    /// space. Returns the number of links deleted.
+    pub fn purge_expired(&mut self) -> Result<usize> {
+        let purged = self.conn.execute(
+            &format!("DELETE FROM links WHERE collection = ?1 AND NOT {UNEXPIRED}"),
+            [self.options.collection()],
+        )?;
+        Ok(purged)
+    }
+
+    /// Permanently deletes every link whose timestamp is older than the age,
+    /// e.g. history from years ago, so a cache which is synced regularly
+    /// doesn't grow without bound. Their search index entries are deleted
+    /// with them. Returns the number of links deleted.
+    pub fn prune_older_than(&mut self, age: chrono::Duration) -> Result<usize> {
+        let cutoff = Utc::now()
+            .checked_sub_signed(age)
+            .ok_or_else(|| Error::Parse(format!("Invalid age for pruning: {}", age)))?;
+        let pruned = self.conn.execute(
+            "DELETE FROM links WHERE collection = ?1 AND timestamp < ?2",
+            (self.options.collection(), cutoff),
+        )?;
+        Ok(pruned)
+    }
+
+    /// Fills in the titles of links which don't have one (common for history
+    /// entries) using the provided fetcher, which is called with each such
+    /// link's url and returns the page's title if it could find one. Keeping
+    /// the fetching outside the crate lets the caller decide how (and
+    /// whether) to make network requests. Returns the number of links whose
+    /// title was filled in.
+    pub fn enrich_empty_titles(
+        &mut self,
+        fetcher: impl Fn(&str) -> Option<String>,
+    ) -> Result<usize> {
+        let urls = {
+            let mut stmt = self.conn.prepare(
+                "SELECT url FROM links
+                 WHERE collection = ?1 AND deleted_at IS NULL AND trim(title) = ''",
+            )?;
+            let urls = stmt.query_map([self.options.collection()], |row| row.get(0))?;
+            urls.collect::<std::result::Result<Vec<String>, rusqlite::Error>>()?
+        };
+
+        let mut enriched = 0;
+        for url in urls {
+            let Some(title) = fetcher(&url).filter(|title| !title.trim().is_empty()) else {
+                continue;
+            };
+            enriched += self.conn.execute(
+                "UPDATE links SET title = ?1 WHERE url = ?2 AND collection = ?3",
+                (title.trim(), &url, self.options.collection()),
+            )?;
+        }
+        Ok(enriched)
+    }
+
+    pub fn get_latest_n(&self, n: u32) -> Result<Vec<Link>> {
+        self.latest_links(n, None)
+    }
+
+    /// Returns the n newest links, only from the provided source when there
+    /// is one.
+    pub(crate) fn latest_links(&self, n: u32, source: Option<&str>) -> Result<Vec<Link>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {LINK_COLUMNS}
+             FROM links
+             WHERE collection = ?1 AND deleted_at IS NULL AND {UNEXPIRED}
+             AND (?3 IS NULL OR links.source = ?3)
+             ORDER BY timestamp DESC, url
+             LIMIT ?2",
+        ))?;
+
+        let links_iter = stmt.query_map((self.options.collection(), n, source), link_from_row)?;
 
         links_iter
             .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()
             .map_err(|e| e.into())
     }
 
-    pub fn get_latest_n(&self, n: u32) -> Result<Vec<Link>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT url, title, subtitle, source, author, timestamp 
-             FROM links 
-             ORDER BY timestamp DESC 
-             LIMIT ?",
-        )?;
-
-        let links_iter = stmt.query_map([n], |row| {
-            Ok(Link {
-                url: row.get(0)?,
-                title: row.get(1)?,
-                subtitle: row.get(2)?,
-                source: row.get(3)?,
-                author: row.get(4)?,
-                timestamp: row.get(5)?,
-                ..Default::default()
-            })
-        })?;
+    /// Returns how many links this Cache holds (leaving out removed and
+    /// expired links).
+    pub fn count(&self) -> Result<u64> {
+        self.count_links(None)
+    }
+
+    /// Returns how many links from the provided source this Cache holds.
+    pub fn count_by_source(&self, source: &str) -> Result<u64> {
+        self.count_links(Some(source))
+    }
+
+    /// Counts the links, only from the provided source when there is one.
+    fn count_links(&self, source: Option<&str>) -> Result<u64> {
+        let count: i64 = self.conn.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM links
+                 WHERE collection = ?1 AND deleted_at IS NULL AND {UNEXPIRED}
+                 AND (?2 IS NULL OR links.source = ?2)",
+            ),
+            (self.options.collection(), source),
+            |row| row.get(0),
+        )?;
+        Ok(count as u64)
+    }
+
+    /// Returns the names of every collection which holds at least one link,
+    /// in alphabetical order.
+    pub fn collections(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT DISTINCT collection FROM links
+                 WHERE deleted_at IS NULL AND {UNEXPIRED}
+                 ORDER BY collection",
+        ))?;
+        let names = stmt.query_map([], |row| row.get(0))?;
+        names
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()
+            .map_err(|e| e.into())
+    }
+
+    /// Returns every link whose timestamp falls on the provided calendar day,
+    /// ordered chronologically. The day boundaries are computed in UTC unless
+    /// a timezone offset is provided, in which case "the day" means midnight
+    /// to midnight in that timezone.
+    pub fn links_on_day(&self, day: NaiveDate, tz: Option<FixedOffset>) -> Result<Vec<Link>> {
+        let tz = tz.unwrap_or_else(|| FixedOffset::east_opt(0).expect("UTC is a valid offset"));
+        let start = tz
+            .from_local_datetime(&day.and_hms_opt(0, 0, 0).expect("Midnight is a valid time"))
+            .single()
+            .ok_or_else(|| Error::Parse(format!("Invalid start of day for {}", day)))?
+            .with_timezone(&Utc);
+        let end = start
+            .checked_add_days(Days::new(1))
+            .ok_or_else(|| Error::Parse(format!("Invalid end of day for {}", day)))?;
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {LINK_COLUMNS}
+             FROM links
+             WHERE timestamp >= ?1 AND timestamp < ?2 AND collection = ?3
+             AND deleted_at IS NULL AND {UNEXPIRED}
+             ORDER BY timestamp ASC, url",
+        ))?;
+
+        let links_iter =
+            stmt.query_map((&start, &end, self.options.collection()), link_from_row)?;
 
         links_iter
             .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()
@@ -125,6 +461,52 @@ impl Cache {
     }
 }
 
+/// The most urls bound to a single statement by remove_urls, which keeps it
+/// under the 999 variable limit of SQLite builds before 3.32.
+const MAX_URLS_PER_STATEMENT: usize = 900;
+
+/// The columns of the links table which make up a Link, in the order
+/// link_from_row() expects them. Queries may append extra columns (e.g. the
+/// FTS rank) after these.
+pub(crate) const LINK_COLUMNS: &str = "links.url, links.title, links.subtitle, \
+     links.source, links.author, links.timestamp, links.description, \
+     links.last_opened_at, links.kind, links.guid, links.expires_at, \
+     links.profile, links.thumbnail, links.visit_count, links.typed_count, \
+     links.canonical_url";
+
+/// The number of columns in LINK_COLUMNS, which is also the index of the
+/// first extra column a query appends.
+pub(crate) const LINK_COLUMN_COUNT: usize = 16;
+
+/// A SQL condition which is true for links whose expires_at hasn't passed.
+/// SQLite's clock is formatted the way rusqlite stores timestamps so the two
+/// compare as text.
+pub(crate) const UNEXPIRED: &str = "(links.expires_at IS NULL \
+     OR links.expires_at > strftime('%Y-%m-%d %H:%M:%f+00:00', 'now'))";
+
+/// Maps a row whose leading columns are LINK_COLUMNS into a Link.
+pub(crate) fn link_from_row(row: &Row) -> rusqlite::Result<Link> {
+    Ok(Link {
+        url: row.get(0)?,
+        title: row.get(1)?,
+        subtitle: row.get(2)?,
+        source: row.get(3)?,
+        author: row.get(4)?,
+        timestamp: row.get(5)?,
+        description: row.get(6)?,
+        last_opened_at: row.get(7)?,
+        kind: row.get(8)?,
+        guid: row.get(9)?,
+        expires_at: row.get(10)?,
+        profile: row.get(11)?,
+        thumbnail: row.get::<_, Option<String>>(12)?.map(PathBuf::from),
+        visit_count: row.get(13)?,
+        typed_count: row.get(14)?,
+        canonical_url: row.get(15)?,
+        ..Default::default()
+    })
+}
+
 /// Defines the Default implementaton for Cache.
 impl Default for Cache {
     fn default() -> Self {
@@ -135,18 +517,14 @@ impl Default for Cache {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tempfile::{tempdir, TempDir};
-
-    fn test_cache_instance() -> (Cache, TempDir) {
-        let binding = tempdir().expect("Failed to create temp dir");
-        let temp_dir = binding.path();
-        let cache = Cache::new(temp_dir.join("test.sqlite")).expect("Failed to create test cache");
-        (cache, binding)
-    }
+    use crate::testutils::create_test_cache as test_cache_instance;
+    use crate::CorruptionPolicy;
+    use std::collections::HashMap;
+    use tempfile::tempdir;
 
     #[test]
     fn test_add_and_search_fuzzy() -> Result<()> {
-        let (mut cache, _temp_dir) = test_cache_instance();
+        let mut cache = test_cache_instance();
         cache.add(Link {
             title: "Visual Studio Code".to_string(),
             url: "https://code.visualstudio.com".to_string(),
@@ -162,4 +540,809 @@ mod tests {
         assert_eq!(results[0].title, "Visual Studio Code");
         Ok(())
     }
+
+    #[test]
+    fn test_source_boosts() -> Result<()> {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let boosts = HashMap::from([("arc".to_string(), 2.0)]);
+        let mut cache = CacheBuilder::new()
+            .with_path(temp_dir.path().join("test.sqlite"))
+            .with_source_boosts(boosts)
+            .build()?;
+        cache.add(
+            Link::new(
+                "https://a.example/docs".to_string(),
+                "Rust Docs".to_string(),
+            )
+            .with_source("firefox".to_string()),
+        )?;
+        cache.add(
+            Link::new(
+                "https://b.example/docs".to_string(),
+                "Rust Docs".to_string(),
+            )
+            .with_source("arc".to_string()),
+        )?;
+
+        let results = cache.search("Rust Docs")?;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].source, Some("arc".to_string()));
+        assert!(results[0].score > results[1].score);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_subtitles_across_sources() -> Result<()> {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let mut cache = CacheBuilder::new()
+            .with_path(temp_dir.path().join("test.sqlite"))
+            .with_merge_subtitles(true)
+            .build()?;
+        let url = "https://doc.rust-lang.org/book/".to_string();
+        let title = "The Rust Programming Language".to_string();
+        cache.add(
+            Link::new(url.clone(), title.clone())
+                .with_source("firefox".to_string())
+                .with_subtitle("Work".to_string()),
+        )?;
+        cache.add(
+            Link::new(url.clone(), title.clone())
+                .with_source("chrome".to_string())
+                .with_subtitle("Bookmarks Bar".to_string()),
+        )?;
+        // Re-importing from the same source replaces only its own entry
+        cache.add(
+            Link::new(url.clone(), title.clone())
+                .with_source("firefox".to_string())
+                .with_subtitle("Work / Rust".to_string()),
+        )?;
+
+        let results = cache.search("Rust Programming")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].subtitle,
+            Some("Chrome: Bookmarks Bar | Firefox: Work / Rust".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_timestamp_precedence_across_sources() -> Result<()> {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let mut cache = CacheBuilder::new()
+            .with_path(temp_dir.path().join("test.sqlite"))
+            .with_timestamp_precedence(["arc", "firefox", "chrome"])
+            .build()?;
+        let url = "https://doc.rust-lang.org/book/";
+        let add = |cache: &mut Cache, source: &str, timestamp_seconds: i64| {
+            cache.add(
+                Link::new(url.to_string(), "The Rust Book".to_string())
+                    .with_source(source.to_string())
+                    .with_timestamp_seconds(timestamp_seconds),
+            )
+        };
+        let stored = |cache: &Cache| cache.get_by_url(url).map(|link| link.unwrap());
+
+        add(&mut cache, "chrome", 3000)?;
+        assert_eq!(stored(&cache)?.timestamp.timestamp(), 3000);
+        add(&mut cache, "arc", 1000)?;
+        assert_eq!(stored(&cache)?.timestamp.timestamp(), 1000);
+        add(&mut cache, "chrome", 4000)?;
+        add(&mut cache, "firefox", 2000)?;
+        let link = stored(&cache)?;
+        assert_eq!(link.timestamp.timestamp(), 1000);
+        assert_eq!(link.source, Some("arc".to_string()));
+
+        // Re-adding from the preferred source updates its timestamp
+        add(&mut cache, "arc", 5000)?;
+        assert_eq!(stored(&cache)?.timestamp.timestamp(), 5000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_readding_with_new_title_replaces_index_entry() -> Result<()> {
+        let mut cache = test_cache_instance();
+        cache.add(Link::new(
+            "https://www.rust-lang.org".to_string(),
+            "Rust Programming Language".to_string(),
+        ))?;
+        cache.add(Link::new(
+            "https://www.rust-lang.org".to_string(),
+            "Rust Language Homepage".to_string(),
+        ))?;
+        let results = cache.search("Rust")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].title, "Rust Language Homepage");
+        assert!(cache.search("Programming")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_description_round_trip() -> Result<()> {
+        let mut cache = test_cache_instance();
+        cache.add(
+            Link::new("https://serde.rs".to_string(), "Serde".to_string()).with_description(
+                "A framework for serializing and deserializing Rust data structures".to_string(),
+            ),
+        )?;
+        cache.add(Link::new(
+            "https://example.com/serializing".to_string(),
+            "Serializing Rust Data".to_string(),
+        ))?;
+
+        let results = cache.search("serializing")?;
+        assert_eq!(results.len(), 2);
+        // Title matches outrank description matches
+        assert_eq!(results[0].title, "Serializing Rust Data");
+        assert_eq!(results[1].title, "Serde");
+        assert_eq!(
+            results[1].description,
+            Some("A framework for serializing and deserializing Rust data structures".to_string())
+        );
+
+        // Re-importing without a description keeps the stored one
+        cache.add(Link::new(
+            "https://serde.rs/".to_string(),
+            "Serde".to_string(),
+        ))?;
+        let serde = cache.get_by_url("https://serde.rs")?.unwrap();
+        assert_eq!(
+            serde.description,
+            Some("A framework for serializing and deserializing Rust data structures".to_string())
+        );
+        assert_eq!(cache.search("deserializing")?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_visit_counts_round_trip() -> Result<()> {
+        let mut cache = test_cache_instance();
+        let link = Link {
+            visit_count: Some(40),
+            typed_count: Some(3),
+            ..Link::new(
+                "https://news.ycombinator.com/".to_string(),
+                "Hacker News".to_string(),
+            )
+        };
+        cache.add(link.clone())?;
+        let stored = cache.get_by_url(&link.url)?.unwrap();
+        assert_eq!(stored.visit_count, Some(40));
+        assert_eq!(stored.typed_count, Some(3));
+        let latest = &cache.get_latest_n(1)?[0];
+        assert_eq!(
+            (latest.visit_count, latest.typed_count),
+            (Some(40), Some(3))
+        );
+
+        // Re-importing from a source which doesn't count visits keeps them
+        cache.add(Link::new(link.url.clone(), link.title.clone()))?;
+        let found = &cache.search("hacker news")?[0];
+        assert_eq!((found.visit_count, found.typed_count), (Some(40), Some(3)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_matches_canonical_url() -> Result<()> {
+        let mut cache = test_cache_instance();
+        cache.add(
+            Link::new("https://t.co/a1b2c3".to_string(), "Shared link".to_string())
+                .with_canonical_url("https://github.com/adlio/linkcache".to_string()),
+        )?;
+        cache.add(Link::new(
+            "https://example.com/".to_string(),
+            "Example".to_string(),
+        ))?;
+
+        let results = cache.search("adlio linkcache")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].url, "https://t.co/a1b2c3");
+        assert_eq!(cache.search("a1b2c3")?.len(), 1);
+
+        // Without a redirect, a link is its own canonical url
+        let example = cache.get_by_url("https://example.com/")?.unwrap();
+        assert_eq!(example.canonical_url, Some(example.url));
+
+        // Re-importing from a source which doesn't record redirects keeps it
+        cache.add(Link::new(
+            "https://t.co/a1b2c3".to_string(),
+            "Shared link".to_string(),
+        ))?;
+        assert_eq!(cache.search("adlio linkcache")?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_thumbnail_round_trip() -> Result<()> {
+        let mut cache = test_cache_instance();
+        let thumbnail = PathBuf::from("/tmp/thumbnails/rust-book.png");
+        let link = Link::new(
+            "https://doc.rust-lang.org/book/".to_string(),
+            "The Rust Book".to_string(),
+        );
+        cache.add(link.clone().with_thumbnail(thumbnail.clone()))?;
+        let stored = cache.get_by_url(&link.url)?.unwrap();
+        assert_eq!(stored.thumbnail, Some(thumbnail.clone()));
+        assert_eq!(
+            cache.search("rust book")?[0].thumbnail,
+            Some(thumbnail.clone())
+        );
+
+        // Re-importing from a source without thumbnails keeps it
+        cache.add(link.clone())?;
+        assert_eq!(
+            cache.get_by_url(&link.url)?.unwrap().thumbnail,
+            Some(thumbnail)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_blocked_schemes_are_not_cached() -> Result<()> {
+        let bookmarklet = Link::new(
+            "javascript:(function(){alert(document.title)})()".to_string(),
+            "Show Title Bookmarklet".to_string(),
+        );
+
+        let mut cache = test_cache_instance();
+        cache.add(bookmarklet.clone())?;
+        cache.add(Link::new(
+            "https://example.com/bookmarklets".to_string(),
+            "Bookmarklet Gallery".to_string(),
+        ))?;
+        let results = cache.search("Bookmarklet")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].url, "https://example.com/bookmarklets");
+
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let mut cache = CacheBuilder::new()
+            .with_path(temp_dir.path().join("test.sqlite"))
+            .with_blocked_schemes(Vec::<String>::new())
+            .build()?;
+        cache.add(bookmarklet)?;
+        assert_eq!(cache.search("Bookmarklet")?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_auto_optimize() -> Result<()> {
+        let mut cache = CacheBuilder::new()
+            .in_memory()
+            .with_auto_optimize(5)
+            .build()?;
+        let link = |i: usize| {
+            Link::new(
+                format!("https://example.com/{}", i),
+                format!("Rust Notes {}", i),
+            )
+        };
+        for i in 0..4 {
+            cache.add(link(i))?;
+        }
+        assert_eq!(cache.writes_since_optimize, 4);
+        cache.add(link(4))?;
+        assert_eq!(cache.writes_since_optimize, 0);
+
+        cache.add_batch((5..12).map(link))?;
+        assert_eq!(cache.writes_since_optimize, 0);
+        cache.add(link(12))?;
+        assert_eq!(cache.writes_since_optimize, 1);
+        assert_eq!(cache.search("rust")?.len(), 13);
+
+        // Disabled by default
+        let mut cache = test_cache_instance();
+        cache.add(link(0))?;
+        assert_eq!(cache.writes_since_optimize, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_if_missing() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let path = temp_dir.path().join("missing.sqlite");
+        let result = CacheBuilder::new()
+            .with_path(&path)
+            .create_if_missing(false)
+            .build();
+        assert!(matches!(result, Err(Error::CacheNotFound(missing)) if missing == path));
+        assert!(!path.exists());
+
+        Cache::new(&path)?;
+        CacheBuilder::new()
+            .with_path(&path)
+            .create_if_missing(false)
+            .build()?;
+        Ok(())
+    }
+
+    /// Builds a cache file holding a few links, then corrupts its search
+    /// index, so building a Cache on it fails while the links themselves can
+    /// still be read.
+    fn build_corrupt_cache(path: &Path) -> Result<()> {
+        let mut cache = Cache::new(path)?;
+        for i in 0..3 {
+            cache.add(Link::new(
+                format!("https://example.com/{}", i),
+                format!("Rust Notes {}", i),
+            ))?;
+        }
+        let (root, page_size): (u32, u32) = cache.conn.query_row(
+            "SELECT rootpage, (SELECT page_size FROM pragma_page_size())
+             FROM sqlite_master WHERE name = 'links_fts_content'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        drop(cache);
+
+        let mut bytes = std::fs::read(path)?;
+        let (root, page_size) = (root as usize, page_size as usize);
+        let page = (root - 1) * page_size..root * page_size;
+        bytes[page].fill(0xff);
+        std::fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_database_fails_by_default() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let path = temp_dir.path().join("linkcache.sqlite");
+        build_corrupt_cache(&path)?;
+
+        let result = CacheBuilder::new().with_path(&path).build();
+        assert!(matches!(result, Err(Error::Rusqlite(_))));
+        assert_eq!(std::fs::read_dir(temp_dir.path())?.count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_database_is_recreated() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let path = temp_dir.path().join("linkcache.sqlite");
+        std::fs::write(
+            &path,
+            "not a database, but long enough to have a header ".repeat(4),
+        )?;
+
+        let cache = CacheBuilder::new()
+            .with_path(&path)
+            .on_corruption(CorruptionPolicy::Recreate)
+            .build()?;
+        assert_eq!(cache.count()?, 0);
+        let kept_aside = std::fs::read_dir(temp_dir.path())?
+            .filter_map(|entry| entry.ok())
+            .any(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with("linkcache.sqlite.corrupt-")
+            });
+        assert!(kept_aside);
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_database_is_recovered() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let path = temp_dir.path().join("linkcache.sqlite");
+        build_corrupt_cache(&path)?;
+
+        let cache = CacheBuilder::new()
+            .with_path(&path)
+            .on_corruption(CorruptionPolicy::Recover)
+            .build()?;
+        assert_eq!(cache.count()?, 3);
+        assert_eq!(cache.search("rust notes")?.len(), 3);
+
+        // A file with nothing to recover leaves an empty cache
+        std::fs::write(&path, "garbage ".repeat(64))?;
+        let cache = CacheBuilder::new()
+            .with_path(&path)
+            .on_corruption(CorruptionPolicy::Recover)
+            .build()?;
+        assert_eq!(cache.count()?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_batch() -> Result<()> {
+        let mut cache = test_cache_instance();
+        let links = (0..100).map(|i| {
+            Link::new(
+                format!("https://example.com/history/{}", i),
+                format!("Rust History {}", i),
+            )
+        });
+        let bookmarklet = Link::new(
+            "javascript:alert(1)".to_string(),
+            "Rust Bookmarklet".to_string(),
+        );
+        assert_eq!(cache.add_batch(links.chain([bookmarklet]))?, 100);
+        assert_eq!(cache.search_limited("Rust", None)?.len(), 100);
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_batch_rolls_back_on_failure() -> Result<()> {
+        let mut cache = test_cache_instance();
+        cache.conn.execute_batch(
+            "CREATE TRIGGER reject_bad BEFORE INSERT ON links
+             WHEN NEW.url = 'https://example.com/bad'
+             BEGIN SELECT RAISE(ABORT, 'rejected'); END;",
+        )?;
+        let links = ["good", "bad", "also-good"].map(|name| {
+            Link::new(
+                format!("https://example.com/{}", name),
+                "Rust Notes".to_string(),
+            )
+        });
+        assert!(cache.add_batch(links).is_err());
+        assert!(cache.search("Rust")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_links_on_day() -> Result<()> {
+        let mut cache = test_cache_instance();
+        let at = |s: &str| {
+            chrono::DateTime::parse_from_rfc3339(s)
+                .expect("Invalid test timestamp")
+                .with_timezone(&Utc)
+        };
+        cache.add(Link {
+            title: "Late on the 14th".to_string(),
+            url: "https://example.com/14".to_string(),
+            timestamp: at("2024-03-14T23:30:00Z"),
+            ..Default::default()
+        })?;
+        cache.add(Link {
+            title: "Evening on the 15th".to_string(),
+            url: "https://example.com/15-evening".to_string(),
+            timestamp: at("2024-03-15T18:00:00Z"),
+            ..Default::default()
+        })?;
+        cache.add(Link {
+            title: "Morning on the 15th".to_string(),
+            url: "https://example.com/15-morning".to_string(),
+            timestamp: at("2024-03-15T08:00:00Z"),
+            ..Default::default()
+        })?;
+
+        let day = NaiveDate::from_ymd_opt(2024, 3, 15).unwrap();
+        let links = cache.links_on_day(day, None)?;
+        let titles: Vec<&str> = links.iter().map(|l| l.title.as_str()).collect();
+        assert_eq!(titles, vec!["Morning on the 15th", "Evening on the 15th"]);
+
+        // Two hours east of UTC, 23:30Z on the 14th is already the 15th locally
+        // while 18:00Z on the 15th is still the 15th.
+        let links = cache.links_on_day(day, FixedOffset::east_opt(2 * 3600))?;
+        let titles: Vec<&str> = links.iter().map(|l| l.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            vec![
+                "Late on the 14th",
+                "Morning on the 15th",
+                "Evening on the 15th"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_collections_are_searched_independently() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let path = temp_dir.path().join("cache.sqlite");
+        let mut personal = CacheBuilder::new()
+            .with_path(&path)
+            .with_collection("personal")
+            .build()?;
+        let mut work = CacheBuilder::new()
+            .with_path(&path)
+            .with_collection("work")
+            .build()?;
+
+        personal.add(Link::new(
+            "https://www.rust-lang.org/".to_string(),
+            "Rust Home".to_string(),
+        ))?;
+        personal.add(Link::new(
+            "https://example.com/recipes".to_string(),
+            "Rust-free Recipes".to_string(),
+        ))?;
+        work.add(Link::new(
+            "https://www.rust-lang.org/".to_string(),
+            "Rust at Work".to_string(),
+        ))?;
+
+        assert_eq!(personal.search("Rust")?.len(), 2);
+        let results = work.search("Rust")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].title, "Rust at Work");
+        assert!(work.search("Recipes")?.is_empty());
+        assert_eq!(work.search("")?.len(), 1);
+
+        // Removing from one collection leaves the other's copy alone
+        work.remove(&results[0])?;
+        assert!(work.search("Rust")?.is_empty());
+        assert_eq!(personal.search("Rust Home")?.len(), 1);
+
+        let default = Cache::new(&path)?;
+        assert!(default.search("Rust")?.is_empty());
+        assert_eq!(default.collections()?, vec!["personal".to_string()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_count() -> Result<()> {
+        let mut cache = test_cache_instance();
+        assert_eq!(cache.count()?, 0);
+        for (i, source) in ["firefox", "firefox", "chrome"].iter().enumerate() {
+            cache.add(
+                Link::new(format!("https://example.com/{}", i), format!("Link {}", i))
+                    .with_source(source.to_string()),
+            )?;
+        }
+        assert_eq!(cache.count()?, 3);
+        assert_eq!(cache.count_by_source("firefox")?, 2);
+        assert_eq!(cache.count_by_source("arc")?, 0);
+
+        cache.remove(&Link::new(
+            "https://example.com/0".to_string(),
+            String::new(),
+        ))?;
+        assert_eq!(cache.count()?, 2);
+        assert_eq!(cache.count_by_source("firefox")?, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_and_restore() -> Result<()> {
+        let mut cache = test_cache_instance();
+        let link = Link::new(
+            "https://www.sqlite.org/fts5.html".to_string(),
+            "SQLite FTS5".to_string(),
+        );
+        cache.add(link.clone())?;
+
+        cache.remove(&link)?;
+        assert!(cache.search("FTS5")?.is_empty());
+        assert!(cache.get_latest_n(10)?.is_empty());
+        assert!(cache.get_by_url(&link.url)?.is_none());
+
+        assert!(cache.restore(&link.url)?);
+        assert_eq!(cache.search("FTS5")?.len(), 1);
+        assert!(!cache.restore(&link.url)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_url_variants_are_one_link() -> Result<()> {
+        let mut cache = test_cache_instance();
+        cache.add(Link::new(
+            "https://example.com".to_string(),
+            "Example".to_string(),
+        ))?;
+        cache.record_open("https://example.com")?;
+        for url in [
+            "https://example.com/",
+            "http://example.com",
+            "https://www.example.com/#about",
+            "https://example.com/?utm_source=newsletter",
+        ] {
+            cache.add(Link::new(url.to_string(), "Example Domain".to_string()))?;
+        }
+        cache.add(Link::new(
+            "https://example.com/about".to_string(),
+            "About".to_string(),
+        ))?;
+        assert_eq!(cache.count()?, 2);
+
+        // The link keeps the url it was first stored under
+        let stored = cache.get_by_url("http://www.example.com/")?.unwrap();
+        assert_eq!(stored.url, "https://example.com");
+        assert_eq!(stored.title, "Example Domain");
+        assert!(stored.last_opened_at.is_some());
+
+        // Variants of the url open, remove and restore the stored link
+        assert!(cache.record_open("http://www.example.com/#top")?);
+        cache.remove(&Link::new(
+            "https://example.com/?utm_source=newsletter".to_string(),
+            "Example".to_string(),
+        ))?;
+        assert!(cache.get_by_url("https://example.com")?.is_none());
+        assert!(cache.restore("HTTP://Example.com/")?);
+        assert!(cache.get_by_url("https://example.com")?.is_some());
+        assert_eq!(cache.remove_urls(&["http://example.com/"])?, 1);
+        assert_eq!(cache.count()?, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_urls() -> Result<()> {
+        let mut cache = test_cache_instance();
+        let urls: Vec<String> = (1..=5)
+            .map(|n| format!("https://example.com/page-{}", n))
+            .collect();
+        for url in &urls {
+            cache.add(Link::new(url.clone(), "Example Page".to_string()))?;
+        }
+
+        let removed = cache.remove_urls(&[
+            &urls[0],
+            &urls[2],
+            &urls[4],
+            "https://example.com/never-added",
+        ])?;
+        assert_eq!(removed, 3);
+        let remaining: Vec<String> = cache
+            .search("Example Page")?
+            .into_iter()
+            .map(|link| link.url)
+            .collect();
+        assert_eq!(remaining.len(), 2);
+        assert!(remaining.contains(&urls[1]) && remaining.contains(&urls[3]));
+
+        // Removed links can be restored, and purged from the index
+        assert_eq!(cache.remove_urls(&[&urls[0]])?, 0);
+        assert!(cache.restore(&urls[0])?);
+        assert_eq!(cache.purge_deleted()?, 2);
+        let indexed: i64 = cache
+            .conn
+            .query_row("SELECT COUNT(*) FROM links_fts", [], |row| row.get(0))?;
+        assert_eq!(indexed, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_enrich_empty_titles() -> Result<()> {
+        let mut cache = test_cache_instance();
+        cache.add(Link::new(
+            "https://www.sqlite.org/fts5.html".to_string(),
+            "".to_string(),
+        ))?;
+        cache.add(Link::new(
+            "https://example.com/unreachable".to_string(),
+            " ".to_string(),
+        ))?;
+        cache.add(Link::new(
+            "https://www.rust-lang.org/".to_string(),
+            "Rust".to_string(),
+        ))?;
+
+        let enriched = cache.enrich_empty_titles(|url| match url {
+            "https://www.sqlite.org/fts5.html" => Some("SQLite FTS5 Extension".to_string()),
+            "https://www.rust-lang.org/" => panic!("Titled links shouldn't be fetched"),
+            _ => None,
+        })?;
+        assert_eq!(enriched, 1);
+
+        let results = cache.search("FTS5 Extension")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].url, "https://www.sqlite.org/fts5.html");
+        assert_eq!(
+            cache
+                .get_by_url("https://example.com/unreachable")?
+                .map(|l| l.title),
+            Some(" ".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_expired_links_are_hidden_and_purgeable() -> Result<()> {
+        let mut cache = test_cache_instance();
+        let hour = chrono::Duration::hours(1);
+        cache.add(
+            Link::new(
+                "https://example.com/closed-tab".to_string(),
+                "Closed Tab".to_string(),
+            )
+            .with_expires_at(Utc::now() - hour),
+        )?;
+        cache.add(
+            Link::new(
+                "https://example.com/open-tab".to_string(),
+                "Open Tab".to_string(),
+            )
+            .with_expires_at(Utc::now() + hour),
+        )?;
+        cache.add(Link::new(
+            "https://example.com/bookmark".to_string(),
+            "Bookmarked Tab".to_string(),
+        ))?;
+
+        let results = cache.search("Tab")?;
+        assert_eq!(results.len(), 2);
+        assert!(results
+            .iter()
+            .all(|l| l.url != "https://example.com/closed-tab"));
+        assert_eq!(cache.get_latest_n(10)?.len(), 2);
+
+        assert_eq!(cache.purge_expired()?, 1);
+        assert_eq!(cache.purge_expired()?, 0);
+        let remaining: i64 = cache
+            .conn
+            .query_row("SELECT COUNT(*) FROM links", [], |row| row.get(0))?;
+        assert_eq!(remaining, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_source_ttls() -> Result<()> {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let mut cache = CacheBuilder::new()
+            .with_path(temp_dir.path().join("test.sqlite"))
+            .with_source_ttls(HashMap::from([(
+                "tabs".to_string(),
+                std::time::Duration::ZERO,
+            )]))
+            .build()?;
+        cache.add(
+            Link::new("https://example.com/tab".to_string(), "Tab".to_string())
+                .with_source("tabs".to_string()),
+        )?;
+        cache.add(
+            Link::new(
+                "https://example.com/kept".to_string(),
+                "Kept Tab".to_string(),
+            )
+            .with_source("firefox".to_string()),
+        )?;
+
+        let results = cache.search("Tab")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].url, "https://example.com/kept");
+        assert!(results[0].expires_at.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_prune_older_than() -> Result<()> {
+        let mut cache = test_cache_instance();
+        let now = Utc::now().timestamp();
+        for (name, days_old) in [("ancient", 400), ("old", 40), ("recent", 2)] {
+            cache.add(
+                Link::new(
+                    format!("https://example.com/{}", name),
+                    format!("Rust {}", name),
+                )
+                .with_timestamp_seconds(now - days_old * 24 * 60 * 60),
+            )?;
+        }
+
+        assert_eq!(cache.prune_older_than(chrono::Duration::days(30))?, 2);
+        let results = cache.search("rust")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].url, "https://example.com/recent");
+        let indexed: i64 = cache
+            .conn
+            .query_row("SELECT COUNT(*) FROM links_fts", [], |row| row.get(0))?;
+        assert_eq!(indexed, 1);
+        assert_eq!(cache.prune_older_than(chrono::Duration::days(30))?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_and_purge() -> Result<()> {
+        let mut cache = test_cache_instance();
+        let removed = Link::new(
+            "https://www.sqlite.org/fts5.html".to_string(),
+            "SQLite FTS5".to_string(),
+        );
+        let kept = Link::new(
+            "https://www.sqlite.org/lang_select.html".to_string(),
+            "SQLite SELECT".to_string(),
+        );
+        cache.add(removed.clone())?;
+        cache.add(kept)?;
+
+        cache.remove(&removed)?;
+        assert_eq!(cache.purge_deleted()?, 1);
+        assert!(!cache.restore(&removed.url)?);
+        assert_eq!(cache.search("SQLite")?.len(), 1);
+        let remaining: i64 = cache
+            .conn
+            .query_row("SELECT COUNT(*) FROM links_fts", [], |row| row.get(0))?;
+        assert_eq!(remaining, 1);
+        Ok(())
+    }
 }
@@ -1,27 +1,124 @@
-use chrono::DateTime;
+use chrono::{DateTime, Utc};
 use filetime::FileTime;
 use itertools::Itertools;
 use rusqlite::{params, Connection};
 use serde_json::Value;
+use std::collections::HashMap;
 use std::fs;
 use std::fs::File;
 use std::io::BufReader;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use sublime_fuzzy::best_match;
 
-use crate::error::Result;
-use crate::{Cache, Link};
+use crate::error::{Error, Result};
+use crate::link::host_of;
+use crate::{BrowserSource, Cache, Link, LinkKind};
+
+/// The source recorded on every Link imported from Chrome
+pub const SOURCE: &str = "chrome";
+
+/// How many times less a fuzzy match against a bookmark's host counts for
+/// than one against its title, in search_bookmarks_directly.
+const HOST_MATCH_DIVISOR: isize = 2;
+
+/// Seconds between the WebKit epoch (1601-01-01), which Chrome counts its
+/// timestamps from, and the Unix epoch.
+const WEBKIT_EPOCH_OFFSET_SECS: i64 = 11_644_473_600;
+
+/// Converts a Chrome timestamp (microseconds since the WebKit epoch) into a
+/// DateTime. Zero, which Chrome uses for "never", and values out of range
+/// become the default (Unix epoch) timestamp.
+fn from_webkit_time(micros: i64) -> DateTime<Utc> {
+    if micros <= 0 {
+        return DateTime::default();
+    }
+    DateTime::from_timestamp_micros(micros - WEBKIT_EPOCH_OFFSET_SECS * 1_000_000)
+        .unwrap_or_default()
+}
+
+/// The qualifiers Chrome sets on a visit's transition when the page was
+/// reached by a redirect (server or client side) from the previous visit.
+const REDIRECT_QUALIFIERS: i64 = 0xC000_0000;
+
+/// The source recorded on every Link imported from Chrome's Reading List
+pub const READING_LIST_SOURCE: &str = "chrome:readinglist";
+
+/// Browsers built on Chromium which share Chrome's profile format (the
+/// Bookmarks file, History database, etc) but keep their profiles in their
+/// own directories.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ChromiumFlavor {
+    #[default]
+    Chrome,
+    Brave,
+    Edge,
+}
+
+impl ChromiumFlavor {
+    /// The source recorded on every Link imported from this flavor.
+    pub fn source(&self) -> &'static str {
+        match self {
+            ChromiumFlavor::Chrome => SOURCE,
+            ChromiumFlavor::Brave => "brave",
+            ChromiumFlavor::Edge => "edge",
+        }
+    }
+
+    /// The source recorded on every Link imported from this flavor's
+    /// Reading List.
+    pub fn reading_list_source(&self) -> &'static str {
+        match self {
+            ChromiumFlavor::Chrome => READING_LIST_SOURCE,
+            ChromiumFlavor::Brave => "brave:readinglist",
+            ChromiumFlavor::Edge => "edge:readinglist",
+        }
+    }
+
+    /// Returns this flavor's Default Profile directory on the given operating
+    /// system (as named by std::env::consts::OS) under the provided home
+    /// directory.
+    pub fn profile_dir_in(&self, os: &str, home_dir: &Path) -> PathBuf {
+        let relative = match (self, os) {
+            (ChromiumFlavor::Chrome, "macos") => {
+                "Library/Application Support/Google/Chrome/Default"
+            }
+            (ChromiumFlavor::Chrome, "windows") => "AppData/Local/Google/Chrome/User Data/Default",
+            (ChromiumFlavor::Chrome, _) => ".config/google-chrome/Default",
+            (ChromiumFlavor::Brave, "macos") => {
+                "Library/Application Support/BraveSoftware/Brave-Browser/Default"
+            }
+            (ChromiumFlavor::Brave, "windows") => {
+                "AppData/Local/BraveSoftware/Brave-Browser/User Data/Default"
+            }
+            (ChromiumFlavor::Brave, _) => ".config/BraveSoftware/Brave-Browser/Default",
+            (ChromiumFlavor::Edge, "macos") => "Library/Application Support/Microsoft Edge/Default",
+            (ChromiumFlavor::Edge, "windows") => "AppData/Local/Microsoft/Edge/User Data/Default",
+            (ChromiumFlavor::Edge, _) => ".config/microsoft-edge/Default",
+        };
+        home_dir.join(relative)
+    }
+}
 
 pub struct Browser {
     profile_dir: PathBuf,
+    flavor: ChromiumFlavor,
+    skip_unvisited: bool,
 }
 
 impl Browser {
     /// Default constructor for a Browser. Uses the default Chrome profile
     /// from the current user's home directory as the profile directory.
     pub fn new() -> Result<Self> {
+        Self::with_flavor(ChromiumFlavor::Chrome)
+    }
+
+    /// Constructs a Browser reading from the default profile of the provided
+    /// Chromium flavor (e.g. Brave).
+    pub fn with_flavor(flavor: ChromiumFlavor) -> Result<Self> {
         Ok(Browser {
-            profile_dir: Self::default_profile_dir()?,
+            profile_dir: Self::default_profile_dir_for(flavor)?,
+            flavor,
+            skip_unvisited: true,
         })
     }
 
@@ -32,13 +129,24 @@ impl Browser {
         self
     }
 
+    /// Controls whether history entries which were never actually loaded
+    /// (visit_count = 0, e.g. typed but abandoned URLs or prefetches) are
+    /// skipped by cache_history and history_links. Defaults to true.
+    pub fn with_skip_unvisited(mut self, skip_unvisited: bool) -> Self {
+        self.skip_unvisited = skip_unvisited;
+        self
+    }
+
     /// Adds every bookmark from this browser to the provided Cache.
     ///
     pub fn cache_bookmarks(&self, cache: &mut Cache) -> Result<()> {
-        let links = self.bookmark_links()?;
-        for link in links {
-            cache.add(link)?;
-        }
+        cache.add_batch(self.bookmark_links()?)?;
+        Ok(())
+    }
+
+    /// Adds every entry in this browser's Reading List to the provided Cache.
+    pub fn cache_reading_list(&self, cache: &mut Cache) -> Result<()> {
+        cache.add_batch(self.reading_list_links()?)?;
         Ok(())
     }
 
@@ -46,16 +154,96 @@ impl Browser {
     /// Cache.
     pub fn cache_history(&self, cache: &mut Cache) -> Result<()> {
         self.create_history_replica()?;
-        let links = self.history_links()?;
-        for link in links {
-            cache.add(link)?;
+        cache.add_batch(self.history_links()?)?;
+        Ok(())
+    }
+
+    /// Stores the favicons Chrome has cached for the pages in its history in
+    /// the provided Cache (see Cache::favicon_for), keeping the largest icon
+    /// for each host. Returns the number of hosts given icons, which is 0
+    /// when the profile has no Favicons database.
+    pub fn cache_favicons(&self, cache: &mut Cache) -> Result<usize> {
+        if !self.favicons_path().exists() {
+            return Ok(0);
+        }
+        fs::copy(self.favicons_path(), self.favicons_replica_path())?;
+        let conn = Connection::open(self.favicons_replica_path())?;
+        let mut stmt = conn.prepare(
+            r#"
+                SELECT icon_mapping.page_url, favicon_bitmaps.image_data
+                FROM icon_mapping
+                JOIN favicon_bitmaps ON favicon_bitmaps.icon_id = icon_mapping.icon_id
+                WHERE favicon_bitmaps.image_data IS NOT NULL
+                ORDER BY favicon_bitmaps.width ASC
+            "#,
+        )?;
+        let icons: Vec<(String, Vec<u8>)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            // Remove erroneous rows
+            .filter_map(|icon| icon.ok())
+            .collect();
+        // Larger icons come last, so they replace the smaller ones
+        cache.store_favicons(icons)
+    }
+
+    /// Adds every bookmark and history entry from this browser to the
+    /// provided Cache, storing a url which is both bookmarked and in the
+    /// history once, as the bookmark (see combined_links).
+    pub fn cache_all(&self, cache: &mut Cache) -> Result<()> {
+        cache.add_batch(self.combined_links()?)?;
+        Ok(())
+    }
+
+    /// Adds every bookmark and history entry from every profile of this
+    /// browser (see profiles_in) to the provided Cache, as cache_all does
+    /// for a single profile. Each link records the profile it came from.
+    pub fn cache_all_profiles(&self, cache: &mut Cache) -> Result<()> {
+        let user_data_dir = self.profile_dir.parent().unwrap_or(&self.profile_dir);
+        for profile_dir in Self::profiles_in(user_data_dir)? {
+            let browser = Browser {
+                profile_dir,
+                flavor: self.flavor,
+                skip_unvisited: self.skip_unvisited,
+            };
+            browser.cache_all(cache)?;
         }
         Ok(())
     }
 
+    /// Returns the bookmarks and the history of this browser together, with
+    /// history entries for bookmarked urls folded into the bookmark: it keeps
+    /// the bookmark's title and folder subtitle, and gains the history's
+    /// visit and typed counts.
+    pub fn combined_links(&self) -> Result<Vec<Link>> {
+        let mut links = self.bookmark_links()?;
+        let bookmark_index: HashMap<String, usize> = links
+            .iter()
+            .enumerate()
+            .map(|(index, link)| (link.url.clone(), index))
+            .collect();
+
+        self.create_history_replica()?;
+        for link in self.history_links()? {
+            match bookmark_index.get(&link.url) {
+                Some(&index) => {
+                    let bookmark = &mut links[index];
+                    let visits = bookmark.visit_count.unwrap_or(0) + link.visit_count.unwrap_or(0);
+                    bookmark.visit_count = Some(visits);
+                    let typed = bookmark.typed_count.unwrap_or(0) + link.typed_count.unwrap_or(0);
+                    bookmark.typed_count = Some(typed);
+                }
+                None => links.push(link),
+            }
+        }
+        Ok(links)
+    }
+
     /// TODO Possibly Remove? This function provides an alternative mechanism
     /// to scanning the file and adding all bookmarks to the index and instead
-    /// just searches them directly using the sublime_fuzzy algorithm.
+    /// just searches them directly using the sublime_fuzzy algorithm. The
+    /// query is matched against the title and folder, and against the host,
+    /// so a misspelled site name (e.g. "githb") still finds the site's
+    /// bookmarks. Host matches score half as much as title matches.
     ///
     pub fn search_bookmarks_directly(&self, query: &str) -> Result<Vec<Link>> {
         fn get_fuzzy_score(query: &str, title: &str) -> Option<isize> {
@@ -71,7 +259,7 @@ impl Browser {
             .bookmark_links()?
             .into_iter()
             .filter_map(|link| {
-                match get_fuzzy_score(
+                let title_score = get_fuzzy_score(
                     query,
                     format!(
                         "{} {}",
@@ -79,7 +267,11 @@ impl Browser {
                         link.subtitle.clone().unwrap_or_default()
                     )
                     .as_str(),
-                ) {
+                );
+                let host_score = host_of(&link.url)
+                    .and_then(|host| get_fuzzy_score(query, &host))
+                    .map(|score| score / HOST_MATCH_DIVISOR);
+                match title_score.max(host_score) {
                     Some(score) if score > 0 => Some((score, link)),
                     _ => None,
                 }
@@ -100,8 +292,7 @@ impl Browser {
             Ok(conn) => {
                 let mut stmt = conn.prepare(
                     r#"
-                    SELECT url, title,
-                    CAST((last_visit_time / 1000000) - 11644473600 AS INTEGER) || 'Z' AS last_visit_time_epoch
+                    SELECT url, title, last_visit_time
                     FROM urls
                     WHERE title LIKE ?1 OR url LIKE ?1
                     ORDER BY
@@ -115,10 +306,11 @@ impl Browser {
                 let links = stmt
                     .query_map(params![format!("%{}%", query)], |row| {
                         Ok(Link {
-                            // id: row.get(0)?,
-                            url: row.get(1)?,
-                            title: row.get(2)?,
-                            timestamp: row.get(3)?,
+                            url: row.get(0)?,
+                            title: row.get(1)?,
+                            source: Some(self.flavor.source().to_string()),
+                            kind: Some(LinkKind::History),
+                            timestamp: from_webkit_time(row.get(2)?),
                             ..Default::default()
                         })
                     })?
@@ -131,7 +323,9 @@ impl Browser {
 
     /// Parses the Bookmarks file (a JSON blob) in the browser profile
     /// directory and processes it recursively, returning each non-folder
-    /// bookmark entry as a Link.
+    /// bookmark entry as a Link. Each Link carries Chrome's own guid for the
+    /// bookmark (or a deterministic one, when the node has none), which stays
+    /// the same across runs.
     ///
     pub fn bookmark_links(&self) -> Result<Vec<Link>> {
         let mut links = vec![];
@@ -140,27 +334,28 @@ impl Browser {
         let reader = BufReader::new(file);
         let json: Value = serde_json::from_reader(reader)?;
 
-        fn traverse(node: &Value, links: &mut Vec<Link>, subtitle: &str) {
+        fn traverse(node: &Value, links: &mut Vec<Link>, subtitle: &str, source: &str) {
             if let Some(my_title) = node.get("name").and_then(Value::as_str) {
                 if let Some(url) = node.get("url").and_then(Value::as_str) {
-                    let date_added = match node.get("date_added").and_then(Value::as_str) {
-                        Some(date_str) => {
-                            let mut date = date_str.parse().unwrap_or_default();
-                            if date > 0 {
-                                // Convert from Chrome timestamp to Unix epoch
-                                date = (date / 1000000) - 11644473600
-                            }
-                            date
-                        }
-                        None => 0,
-                    };
+                    let date_added = node
+                        .get("date_added")
+                        .and_then(Value::as_str)
+                        .and_then(|date| date.parse().ok())
+                        .unwrap_or_default();
 
                     links.push(Link {
                         title: my_title.to_string(),
                         url: url.to_string(),
                         subtitle: Some(subtitle.to_string()),
-                        timestamp: DateTime::from_timestamp(date_added, 0)
-                            .expect("Failed to convert timestamp"),
+                        source: Some(source.to_string()),
+                        kind: Some(LinkKind::Bookmark),
+                        guid: node
+                            .get("guid")
+                            .and_then(Value::as_str)
+                            .filter(|guid| !guid.is_empty())
+                            .map(String::from)
+                            .or_else(|| Some(Link::deterministic_guid(source, url))),
+                        timestamp: from_webkit_time(date_added),
                         ..Default::default()
                     });
                 }
@@ -171,6 +366,7 @@ impl Browser {
                             child,
                             links,
                             format!("{}/{}", &subtitle, &my_title).as_str(),
+                            source,
                         );
                     }
                 }
@@ -180,17 +376,70 @@ impl Browser {
         if let Some(roots) = json.get("roots").and_then(Value::as_object) {
             for (key, value) in roots {
                 if key == "bookmark_bar" || key == "other" || key == "synced" {
-                    traverse(value, &mut links, "");
+                    traverse(value, &mut links, "", self.flavor.source());
                 }
             }
         }
 
+        let profile = self.profile_name();
+        for link in &mut links {
+            link.profile = profile.clone();
+        }
+        Ok(links)
+    }
+
+    /// Parses the Reading List out of the Bookmarks file, where Chrome keeps
+    /// it under its own "reading_list" root, separate from the bookmarks.
+    /// Each entry is returned as a Link with the flavor's reading list source
+    /// (READING_LIST_SOURCE for Chrome), dated by its creationTime
+    /// (microseconds since the Unix epoch). A profile without a Reading List
+    /// has no entries.
+    ///
+    pub fn reading_list_links(&self) -> Result<Vec<Link>> {
+        let file = File::open(self.bookmarks_path())?;
+        let reader = BufReader::new(file);
+        let json: Value = serde_json::from_reader(reader)?;
+
+        let entries = json
+            .pointer("/roots/reading_list/children")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        let profile = self.profile_name();
+        let links = entries
+            .iter()
+            .filter_map(|entry| {
+                let url = entry.get("url").and_then(Value::as_str)?;
+                let title = entry
+                    .get("title")
+                    .or_else(|| entry.get("name"))
+                    .and_then(Value::as_str)
+                    .unwrap_or(url);
+                // Chrome writes large integers as strings
+                let created_micros = match entry.get("creationTime") {
+                    Some(Value::String(time)) => time.parse().unwrap_or_default(),
+                    Some(time) => time.as_i64().unwrap_or_default(),
+                    None => 0,
+                };
+                Some(Link {
+                    url: url.to_string(),
+                    title: title.to_string(),
+                    subtitle: Some("Reading List".to_string()),
+                    source: Some(self.flavor.reading_list_source().to_string()),
+                    kind: Some(LinkKind::Bookmark),
+                    profile: profile.clone(),
+                    timestamp: DateTime::from_timestamp_micros(created_micros).unwrap_or_default(),
+                    ..Default::default()
+                })
+            })
+            .collect();
         Ok(links)
     }
 
     /// Scans the copy of the browser history file (this function assumes it
     /// already exists) and returns a Link struct for each entry in the
-    /// database.
+    /// database. A url which redirected (e.g. a short link) gets the page it
+    /// last redirected to as its canonical_url.
     ///
     pub fn history_links(&self) -> Result<Vec<Link>> {
         let path = self.history_replica_path();
@@ -199,21 +448,40 @@ impl Browser {
             Ok(conn) => {
                 let mut stmt = conn.prepare(
                     r#"
-                        SELECT id, url, title,
-                        CAST((last_visit_time / 1000000) - 11644473600 AS INTEGER) AS last_visit_time_epoch
+                        SELECT id, url, title, last_visit_time, visit_count, typed_count,
+                        (
+                            SELECT destination.url
+                            FROM visits AS visit
+                            JOIN visits AS redirect ON redirect.from_visit = visit.id
+                            JOIN urls AS destination ON destination.id = redirect.url
+                            WHERE visit.url = urls.id
+                            AND redirect.transition & ?2 != 0
+                            ORDER BY redirect.visit_time DESC
+                            LIMIT 1
+                        )
                         FROM urls
                         WHERE typed_count > 0
                         AND last_visit_time > 0
+                        AND (?1 = 0 OR visit_count > 0)
                         ORDER BY last_visit_time ASC
                     "#,
                 )?;
+                let profile = self.profile_name();
                 let links: Vec<Link> = stmt
                     // Map the query to a result per row
-                    .query_map(params![], |row| {
+                    .query_map(params![self.skip_unvisited, REDIRECT_QUALIFIERS], |row| {
+                        let url: String = row.get(1)?;
                         Ok(Link {
-                            url: row.get(1)?,
+                            guid: Some(Link::deterministic_guid(self.flavor.source(), &url)),
+                            url,
                             title: row.get(2)?,
-                            timestamp: row.get(3)?,
+                            source: Some(self.flavor.source().to_string()),
+                            kind: Some(LinkKind::History),
+                            timestamp: from_webkit_time(row.get(3)?),
+                            visit_count: row.get(4)?,
+                            typed_count: row.get(5)?,
+                            canonical_url: row.get(6)?,
+                            profile: profile.clone(),
                             ..Default::default()
                         })
                     })?
@@ -242,6 +510,14 @@ impl Browser {
         Ok(())
     }
 
+    /// The name of the profile's directory (e.g. "Default" or "Profile 1"),
+    /// recorded on every Link imported from it.
+    fn profile_name(&self) -> Option<String> {
+        self.profile_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+    }
+
     fn bookmarks_path(&self) -> PathBuf {
         self.profile_dir.join("Bookmarks")
     }
@@ -254,16 +530,665 @@ impl Browser {
         self.history_path().with_file_name("History.linkcache")
     }
 
+    fn favicons_path(&self) -> PathBuf {
+        self.profile_dir.join("Favicons")
+    }
+
+    fn favicons_replica_path(&self) -> PathBuf {
+        self.favicons_path().with_file_name("Favicons.linkcache")
+    }
+
     /// Returns the directory of the Default Chrome Profile based on the user's
     /// operating system and detected home directory.
     pub fn default_profile_dir() -> Result<PathBuf> {
+        Self::default_profile_dir_for(ChromiumFlavor::Chrome)
+    }
+
+    /// Returns the directory of the Default Profile of the provided Chromium
+    /// flavor based on the user's operating system and home directory.
+    pub fn default_profile_dir_for(flavor: ChromiumFlavor) -> Result<PathBuf> {
         let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/tmp"));
-        let chrome_data_dir = match std::env::consts::OS {
-            "macos" => home_dir.join("Library/Application Support/Google/Chrome/Default"),
-            "linux" => home_dir.join(".config/google-chrome/Default"),
-            "windows" => home_dir.join("AppData/Local/Google/Chrome/User Data/Default"),
-            _ => home_dir.join(".config/google-chrome/Default"),
+        Ok(flavor.profile_dir_in(std::env::consts::OS, &home_dir))
+    }
+
+    /// Returns the directory of every Chrome profile (e.g. "Default" and
+    /// "Profile 1") of the current user. See profiles_in.
+    pub fn all_profiles() -> Result<Vec<PathBuf>> {
+        Self::all_profiles_for(ChromiumFlavor::Chrome)
+    }
+
+    /// Returns the directory of every profile of the provided Chromium flavor
+    /// of the current user. See profiles_in.
+    pub fn all_profiles_for(flavor: ChromiumFlavor) -> Result<Vec<PathBuf>> {
+        let default_profile_dir = Self::default_profile_dir_for(flavor)?;
+        let user_data_dir = default_profile_dir.parent().unwrap_or(&default_profile_dir);
+        Self::profiles_in(user_data_dir)
+    }
+
+    /// Returns the directory of every profile listed in the "Local State"
+    /// file of the provided user data directory (the parent of the profile
+    /// directories), sorted by name. Profiles whose directory no longer
+    /// exists are left out.
+    pub fn profiles_in(user_data_dir: &Path) -> Result<Vec<PathBuf>> {
+        let file = File::open(user_data_dir.join("Local State"))?;
+        let json: Value = serde_json::from_reader(BufReader::new(file))?;
+        let profiles = json
+            .pointer("/profile/info_cache")
+            .and_then(Value::as_object)
+            .ok_or_else(|| Error::Parse("Local State doesn't list any profiles".to_string()))?;
+        Ok(profiles
+            .keys()
+            .sorted()
+            .map(|name| user_data_dir.join(name))
+            .filter(|dir| dir.is_dir())
+            .collect())
+    }
+
+    /// Returns the Default Chrome Profile directory on the given operating
+    /// system (as named by std::env::consts::OS) under the provided home
+    /// directory.
+    pub fn profile_dir_in(os: &str, home_dir: &Path) -> PathBuf {
+        ChromiumFlavor::Chrome.profile_dir_in(os, home_dir)
+    }
+}
+
+impl BrowserSource for Browser {
+    /// The bookmarks and history together (see combined_links).
+    fn links(&self) -> Result<Vec<Link>> {
+        self.combined_links()
+    }
+
+    fn source_name(&self) -> &'static str {
+        self.flavor.source()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a minimal Chrome History database (the urls table, and an
+    /// empty visits table) into the provided profile directory.
+    fn write_history_fixture(profile_dir: &std::path::Path) -> Result<()> {
+        let conn = Connection::open(profile_dir.join("History"))?;
+        conn.execute_batch(
+            "CREATE TABLE urls (
+                id INTEGER PRIMARY KEY,
+                url LONGVARCHAR,
+                title LONGVARCHAR,
+                visit_count INTEGER DEFAULT 0 NOT NULL,
+                typed_count INTEGER DEFAULT 0 NOT NULL,
+                last_visit_time INTEGER NOT NULL
+            );
+            CREATE TABLE visits (
+                id INTEGER PRIMARY KEY,
+                url INTEGER NOT NULL,
+                visit_time INTEGER NOT NULL,
+                from_visit INTEGER,
+                transition INTEGER DEFAULT 0 NOT NULL
+            );
+            INSERT INTO urls (url, title, visit_count, typed_count, last_visit_time) VALUES
+                ('https://www.rust-lang.org/', 'Rust', 3, 1, 13350000000000000),
+                ('https://example.com/typed-only', 'Never Loaded', 0, 1, 13350000100000000);",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_bookmark_links_preserve_guid() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let write_bookmarks = |url: &str| {
+            let bookmarks = serde_json::json!({
+                "roots": {
+                    "bookmark_bar": {
+                        "name": "Bookmarks Bar",
+                        "type": "folder",
+                        "children": [
+                            {
+                                "name": "Rust",
+                                "type": "url",
+                                "url": url,
+                                "guid": "0b9c1d2e-3f40-4a5b-8c6d-7e8f90a1b2c3",
+                                "id": "5",
+                                "date_added": "13350000000000000"
+                            },
+                            {
+                                "name": "No Guid",
+                                "type": "url",
+                                "url": "https://example.com/",
+                                "date_added": "13350000000000000"
+                            }
+                        ]
+                    }
+                }
+            });
+            std::fs::write(temp_dir.path().join("Bookmarks"), bookmarks.to_string())
+        };
+        write_bookmarks("https://www.rust-lang.org/")?;
+        let browser = Browser::new()?.with_profile_dir(temp_dir.path().to_path_buf());
+
+        let links = browser.bookmark_links()?;
+        assert_eq!(
+            links[0].guid,
+            Some("0b9c1d2e-3f40-4a5b-8c6d-7e8f90a1b2c3".to_string())
+        );
+        // Bookmarks without one get a guid derived from their url
+        assert_eq!(
+            links[1].guid,
+            Some(Link::deterministic_guid("chrome", "https://example.com/"))
+        );
+
+        // The same bookmark under a different url replaces its old row
+        let mut cache = crate::testutils::create_test_cache();
+        browser.cache_bookmarks(&mut cache)?;
+        write_bookmarks("https://www.rust-lang.org/learn")?;
+        browser.cache_bookmarks(&mut cache)?;
+        let results = cache.search("Rust")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].url, "https://www.rust-lang.org/learn");
+        assert_eq!(cache.search("")?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bookmarks_with_empty_guids_are_all_cached() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let bookmark = |name: &str, url: &str| {
+            serde_json::json!({
+                "name": name,
+                "type": "url",
+                "url": url,
+                "guid": "",
+                "date_added": "13350000000000000"
+            })
         };
-        Ok(chrome_data_dir)
+        let bookmarks = serde_json::json!({
+            "roots": {
+                "bookmark_bar": {
+                    "name": "Bookmarks Bar",
+                    "type": "folder",
+                    "children": [
+                        bookmark("Rust Programming Language", "https://www.rust-lang.org/"),
+                        bookmark("SQLite Documentation", "https://www.sqlite.org/docs.html")
+                    ]
+                }
+            }
+        });
+        std::fs::write(temp_dir.path().join("Bookmarks"), bookmarks.to_string())?;
+        let browser = Browser::new()?.with_profile_dir(temp_dir.path().to_path_buf());
+
+        let mut cache = crate::testutils::create_test_cache();
+        browser.cache_bookmarks(&mut cache)?;
+        assert_eq!(cache.count()?, 2);
+        let rust = cache.search("Programming")?;
+        assert_eq!(rust.len(), 1);
+        assert_eq!(rust[0].url, "https://www.rust-lang.org/");
+        let sqlite = cache.search("Documentation")?;
+        assert_eq!(sqlite.len(), 1);
+        assert_eq!(sqlite[0].url, "https://www.sqlite.org/docs.html");
+
+        // Links added directly with an empty guid don't replace each other
+        cache.add(Link {
+            guid: Some(String::new()),
+            ..Link::new("https://crates.io/".to_string(), "Crates".to_string())
+        })?;
+        cache.add(Link {
+            guid: Some(String::new()),
+            ..Link::new("https://docs.rs/".to_string(), "Docs".to_string())
+        })?;
+        assert_eq!(cache.count()?, 4);
+        assert_ne!(
+            cache.get_by_url("https://docs.rs/")?.unwrap().guid,
+            Some(String::new())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_combined_links_fold_history_into_bookmarks() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        write_history_fixture(temp_dir.path())?;
+        let bookmarks = serde_json::json!({
+            "roots": {
+                "bookmark_bar": {
+                    "name": "Bookmarks Bar",
+                    "type": "folder",
+                    "children": [
+                        {
+                            "name": "Rust Programming Language",
+                            "type": "url",
+                            "url": "https://www.rust-lang.org/",
+                            "date_added": "13350000000000000"
+                        }
+                    ]
+                }
+            }
+        });
+        std::fs::write(temp_dir.path().join("Bookmarks"), bookmarks.to_string())?;
+        let browser = Browser::new()?.with_profile_dir(temp_dir.path().to_path_buf());
+
+        let links = browser.combined_links()?;
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].title, "Rust Programming Language");
+        assert_eq!(links[0].subtitle, Some("/Bookmarks Bar".to_string()));
+        assert_eq!(links[0].kind, Some(LinkKind::Bookmark));
+        assert_eq!(links[0].visit_count, Some(3));
+        assert_eq!(links[0].typed_count, Some(1));
+
+        let mut cache = crate::testutils::create_test_cache();
+        browser.cache_all(&mut cache)?;
+        let results = cache.search("rust")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].title, "Rust Programming Language");
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_bookmarks_directly_matches_misspelled_hosts() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let bookmarks = serde_json::json!({
+            "roots": {
+                "bookmark_bar": {
+                    "name": "Bookmarks Bar",
+                    "type": "folder",
+                    "children": [
+                        {
+                            "name": "adlio/linkcache",
+                            "type": "url",
+                            "url": "https://github.com/adlio/linkcache",
+                            "date_added": "13350000000000000"
+                        },
+                        {
+                            "name": "GitHub Mirror",
+                            "type": "url",
+                            "url": "https://git.example.com/",
+                            "date_added": "13350000000000000"
+                        }
+                    ]
+                }
+            }
+        });
+        std::fs::write(temp_dir.path().join("Bookmarks"), bookmarks.to_string())?;
+        let browser = Browser::new()?.with_profile_dir(temp_dir.path().to_path_buf());
+
+        // Matching the title counts for more than matching the host
+        let links = browser.search_bookmarks_directly("github")?;
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].title, "GitHub Mirror");
+
+        // The index doesn't match the typo
+        let mut cache = crate::testutils::create_test_cache();
+        browser.cache_bookmarks(&mut cache)?;
+        assert!(cache.search("githb")?.is_empty());
+
+        // adlio/linkcache is only found through its host
+        let links = browser.search_bookmarks_directly("githb")?;
+        assert_eq!(links.len(), 2);
+        assert!(links
+            .iter()
+            .any(|l| l.url == "https://github.com/adlio/linkcache"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_reading_list_links() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let bookmarks = serde_json::json!({
+            "roots": {
+                "bookmark_bar": {
+                    "name": "Bookmarks Bar",
+                    "type": "folder",
+                    "children": [
+                        {
+                            "name": "Rust",
+                            "type": "url",
+                            "url": "https://www.rust-lang.org/",
+                            "date_added": "13350000000000000"
+                        }
+                    ]
+                },
+                "reading_list": {
+                    "name": "Reading List",
+                    "children": [
+                        {
+                            "title": "Designing Data-Intensive Applications",
+                            "url": "https://dataintensive.net/",
+                            "creationTime": "1700000000000000"
+                        },
+                        {
+                            "title": "SQLite FTS5",
+                            "url": "https://www.sqlite.org/fts5.html",
+                            "creationTime": 1710000000000000_i64
+                        }
+                    ]
+                }
+            }
+        });
+        std::fs::write(temp_dir.path().join("Bookmarks"), bookmarks.to_string())?;
+        let browser = Browser::new()?.with_profile_dir(temp_dir.path().to_path_buf());
+
+        let links = browser.reading_list_links()?;
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].title, "Designing Data-Intensive Applications");
+        assert_eq!(links[0].timestamp.timestamp(), 1_700_000_000);
+        assert_eq!(links[1].timestamp.timestamp(), 1_710_000_000);
+        assert!(links
+            .iter()
+            .all(|l| l.source == Some(READING_LIST_SOURCE.to_string())));
+
+        // Reading List entries aren't bookmarks
+        assert_eq!(browser.bookmark_links()?.len(), 1);
+
+        let mut cache = crate::testutils::create_test_cache();
+        browser.cache_reading_list(&mut cache)?;
+        let results = cache.search("FTS5")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].source, Some(READING_LIST_SOURCE.to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_brave_links_record_their_source() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        write_history_fixture(temp_dir.path())?;
+        let bookmarks = serde_json::json!({
+            "roots": {
+                "bookmark_bar": {
+                    "name": "Bookmarks Bar",
+                    "children": [
+                        { "name": "Docs", "type": "url", "url": "https://docs.rs/" }
+                    ]
+                },
+                "reading_list": {
+                    "children": [
+                        { "title": "SQLite FTS5", "url": "https://www.sqlite.org/fts5.html" }
+                    ]
+                }
+            }
+        });
+        std::fs::write(temp_dir.path().join("Bookmarks"), bookmarks.to_string())?;
+        let browser = Browser::with_flavor(ChromiumFlavor::Brave)?
+            .with_profile_dir(temp_dir.path().to_path_buf());
+
+        let links = browser.combined_links()?;
+        assert_eq!(links.len(), 2);
+        assert!(links.iter().all(|l| l.source == Some("brave".to_string())));
+        assert_eq!(
+            browser.reading_list_links()?[0].source,
+            Some("brave:readinglist".to_string())
+        );
+        assert_eq!(browser.source_name(), "brave");
+        Ok(())
+    }
+
+    #[test]
+    fn test_brave_profile_dirs() {
+        let home = Path::new("/home/user");
+        assert_eq!(
+            ChromiumFlavor::Brave.profile_dir_in("macos", home),
+            home.join("Library/Application Support/BraveSoftware/Brave-Browser/Default")
+        );
+        assert_eq!(
+            ChromiumFlavor::Brave.profile_dir_in("linux", home),
+            home.join(".config/BraveSoftware/Brave-Browser/Default")
+        );
+        assert_eq!(
+            ChromiumFlavor::Brave.profile_dir_in("windows", home),
+            home.join("AppData/Local/BraveSoftware/Brave-Browser/User Data/Default")
+        );
+        assert_eq!(
+            Browser::profile_dir_in("linux", home),
+            home.join(".config/google-chrome/Default")
+        );
+    }
+
+    #[test]
+    fn test_edge_bookmark_links() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        // Edge calls its bookmarks favorites, but stores them like Chrome
+        let bookmarks = serde_json::json!({
+            "checksum": "0e1bdb0f5c9d4b8e2f0d4a9c3e7b6a51",
+            "roots": {
+                "bookmark_bar": {
+                    "name": "Favorites bar",
+                    "type": "folder",
+                    "children": [
+                        {
+                            "name": "Microsoft Learn",
+                            "type": "url",
+                            "url": "https://learn.microsoft.com/",
+                            "guid": "6a1f3c2e-8b4d-4e6f-9a0b-1c2d3e4f5a6b",
+                            "date_added": "13350000000000000"
+                        }
+                    ]
+                },
+                "other": {
+                    "name": "Other favorites",
+                    "type": "folder",
+                    "children": [
+                        {
+                            "name": "Rust",
+                            "type": "url",
+                            "url": "https://www.rust-lang.org/",
+                            "date_added": "13350000000000000"
+                        }
+                    ]
+                },
+                "synced": { "name": "Mobile favorites", "type": "folder", "children": [] }
+            },
+            "version": 1
+        });
+        std::fs::write(temp_dir.path().join("Bookmarks"), bookmarks.to_string())?;
+        let browser = Browser::with_flavor(ChromiumFlavor::Edge)?
+            .with_profile_dir(temp_dir.path().to_path_buf());
+
+        let links = browser.bookmark_links()?;
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].title, "Microsoft Learn");
+        assert_eq!(links[0].subtitle, Some("/Favorites bar".to_string()));
+        assert_eq!(links[1].subtitle, Some("/Other favorites".to_string()));
+        assert!(links.iter().all(|l| l.source == Some("edge".to_string())));
+        assert_eq!(browser.source_name(), "edge");
+        Ok(())
+    }
+
+    #[test]
+    fn test_edge_profile_dirs() {
+        let home = Path::new("/home/user");
+        assert_eq!(
+            ChromiumFlavor::Edge.profile_dir_in("macos", home),
+            home.join("Library/Application Support/Microsoft Edge/Default")
+        );
+        assert_eq!(
+            ChromiumFlavor::Edge.profile_dir_in("linux", home),
+            home.join(".config/microsoft-edge/Default")
+        );
+        assert_eq!(
+            ChromiumFlavor::Edge.profile_dir_in("windows", home),
+            home.join("AppData/Local/Microsoft/Edge/User Data/Default")
+        );
+    }
+
+    #[test]
+    fn test_links_record_their_profile() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let profile_dir = temp_dir.path().join("Profile 2");
+        std::fs::create_dir(&profile_dir)?;
+        write_history_fixture(&profile_dir)?;
+        let bookmarks = serde_json::json!({
+            "roots": {
+                "bookmark_bar": {
+                    "name": "Bookmarks Bar",
+                    "children": [
+                        { "name": "Docs", "type": "url", "url": "https://docs.rs/" }
+                    ]
+                }
+            }
+        });
+        std::fs::write(profile_dir.join("Bookmarks"), bookmarks.to_string())?;
+        let browser = Browser::new()?.with_profile_dir(profile_dir);
+
+        let links = browser.combined_links()?;
+        assert_eq!(links.len(), 2);
+        assert!(links
+            .iter()
+            .all(|l| l.profile == Some("Profile 2".to_string())));
+
+        let mut cache = crate::testutils::create_test_cache();
+        browser.cache_all(&mut cache)?;
+        let stored = cache.get_by_url("https://docs.rs/")?.unwrap();
+        assert_eq!(stored.profile, Some("Profile 2".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_all_profiles() -> Result<()> {
+        let user_data_dir = tempfile::tempdir()?;
+        let local_state = serde_json::json!({
+            "profile": {
+                "info_cache": {
+                    "Default": { "name": "Personal" },
+                    "Profile 1": { "name": "Work" },
+                    "Profile 3": { "name": "Deleted" }
+                }
+            }
+        });
+        std::fs::write(
+            user_data_dir.path().join("Local State"),
+            local_state.to_string(),
+        )?;
+        for (profile, url) in [
+            ("Default", "https://docs.rs/"),
+            ("Profile 1", "https://jira.example.com/"),
+        ] {
+            let profile_dir = user_data_dir.path().join(profile);
+            std::fs::create_dir(&profile_dir)?;
+            write_history_fixture(&profile_dir)?;
+            let bookmarks = serde_json::json!({
+                "roots": {
+                    "bookmark_bar": {
+                        "name": "Bookmarks Bar",
+                        "children": [{ "name": profile, "type": "url", "url": url }]
+                    }
+                }
+            });
+            std::fs::write(profile_dir.join("Bookmarks"), bookmarks.to_string())?;
+        }
+
+        assert_eq!(
+            Browser::profiles_in(user_data_dir.path())?,
+            vec![
+                user_data_dir.path().join("Default"),
+                user_data_dir.path().join("Profile 1")
+            ]
+        );
+
+        let browser = Browser::new()?.with_profile_dir(user_data_dir.path().join("Default"));
+        let mut cache = crate::testutils::create_test_cache();
+        browser.cache_all_profiles(&mut cache)?;
+        let work = cache.get_by_url("https://jira.example.com/")?.unwrap();
+        assert_eq!(work.profile, Some("Profile 1".to_string()));
+        assert_eq!(work.source, Some(SOURCE.to_string()));
+        let personal = cache.get_by_url("https://docs.rs/")?.unwrap();
+        assert_eq!(personal.profile, Some("Default".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_favicons() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let browser = Browser::new()?.with_profile_dir(temp_dir.path().to_path_buf());
+        let mut cache = crate::testutils::create_test_cache();
+        assert_eq!(browser.cache_favicons(&mut cache)?, 0);
+
+        let conn = Connection::open(temp_dir.path().join("Favicons"))?;
+        conn.execute_batch(
+            "CREATE TABLE favicons (id INTEGER PRIMARY KEY, url LONGVARCHAR NOT NULL);
+            CREATE TABLE favicon_bitmaps (
+                id INTEGER PRIMARY KEY,
+                icon_id INTEGER NOT NULL,
+                image_data BLOB,
+                width INTEGER DEFAULT 0,
+                height INTEGER DEFAULT 0
+            );
+            CREATE TABLE icon_mapping (
+                id INTEGER PRIMARY KEY,
+                page_url LONGVARCHAR NOT NULL,
+                icon_id INTEGER
+            );
+            INSERT INTO favicons (id, url) VALUES (1, 'https://www.rust-lang.org/favicon.ico');
+            INSERT INTO favicon_bitmaps (icon_id, image_data, width, height) VALUES
+                (1, X'89504E4701', 32, 32),
+                (1, X'89504E4702', 16, 16);
+            INSERT INTO icon_mapping (page_url, icon_id) VALUES
+                ('https://www.rust-lang.org/learn', 1),
+                ('https://www.rust-lang.org/tools', 1);",
+        )?;
+
+        assert_eq!(browser.cache_favicons(&mut cache)?, 1);
+        assert_eq!(
+            cache.favicon_for("https://www.rust-lang.org/")?,
+            Some(vec![0x89, b'P', b'N', b'G', 0x01])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_webkit_time() {
+        assert_eq!(
+            from_webkit_time(13_350_000_000_123_456).to_rfc3339(),
+            "2024-01-17T21:20:00.123456+00:00"
+        );
+        assert_eq!(
+            from_webkit_time(WEBKIT_EPOCH_OFFSET_SECS * 1_000_000).timestamp(),
+            0
+        );
+        assert_eq!(from_webkit_time(0), DateTime::<Utc>::default());
+    }
+
+    #[test]
+    fn test_history_links_skip_unvisited() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        write_history_fixture(temp_dir.path())?;
+        let browser = Browser::new()?.with_profile_dir(temp_dir.path().to_path_buf());
+        browser.create_history_replica()?;
+
+        let links = browser.history_links()?;
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].url, "https://www.rust-lang.org/");
+        assert_eq!(links[0].timestamp.to_rfc3339(), "2024-01-17T21:20:00+00:00");
+
+        let links = browser.with_skip_unvisited(false).history_links()?;
+        assert_eq!(links.len(), 2);
+        assert!(links
+            .iter()
+            .any(|l| l.url == "https://example.com/typed-only"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_history_links_record_redirects() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        write_history_fixture(temp_dir.path())?;
+        // A typed short link which the server redirected to rust-lang.org
+        Connection::open(temp_dir.path().join("History"))?.execute_batch(
+            "INSERT INTO urls (id, url, title, visit_count, typed_count, last_visit_time)
+                VALUES (10, 'https://rust.ly/home', '', 1, 1, 13350000200000000);
+             INSERT INTO visits (id, url, visit_time, from_visit, transition) VALUES
+                (1, 10, 13350000200000000, 0, 268435457),
+                (2, 1, 13350000200000001, 1, 2684354560);",
+        )?;
+        let browser = Browser::new()?.with_profile_dir(temp_dir.path().to_path_buf());
+        let mut cache = crate::testutils::create_test_cache();
+        browser.cache_history(&mut cache)?;
+
+        let short = cache.get_by_url("https://rust.ly/home")?.unwrap();
+        assert_eq!(
+            short.canonical_url,
+            Some("https://www.rust-lang.org/".to_string())
+        );
+        let destination = cache.get_by_url("https://www.rust-lang.org/")?.unwrap();
+        assert_eq!(destination.canonical_url, Some(destination.url.clone()));
+        Ok(())
     }
 }
@@ -1,12 +1,189 @@
+use std::path::Path;
+
+use log::warn;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection, OpenFlags};
+
+use crate::link::normalize_url;
+use crate::search::{title_position, title_words};
 use crate::Cache;
 use crate::Result;
 
+/// The schema migrations, applied in order. The number of migrations which
+/// have been applied to a database is tracked in its user_version pragma, so
+/// each one runs exactly once. Never edit a released migration; add a new
+/// one instead.
+const MIGRATIONS: &[&str] = &[
+    include_str!("migrations/001_CreateLinks.sql"),
+    include_str!("migrations/002_AddDescription.sql"),
+    include_str!("migrations/003_AddLastOpenedAt.sql"),
+    include_str!("migrations/004_AddCollection.sql"),
+    include_str!("migrations/005_AddKind.sql"),
+    include_str!("migrations/006_AddGuid.sql"),
+    include_str!("migrations/007_AddDeletedAt.sql"),
+    include_str!("migrations/008_FoldDiacritics.sql"),
+    include_str!("migrations/009_AddExpiresAt.sql"),
+    include_str!("migrations/010_AddProfile.sql"),
+    include_str!("migrations/011_AddLinkMeta.sql"),
+    include_str!("migrations/012_AddThumbnail.sql"),
+    include_str!("migrations/013_AddFavicons.sql"),
+    include_str!("migrations/014_AddVisitCounts.sql"),
+    include_str!("migrations/015_AddCanonicalUrl.sql"),
+    include_str!("migrations/016_AddNormalizedUrl.sql"),
+];
+
+/// The version of the schema once every migration has been applied.
+pub(crate) const SCHEMA_VERSION: usize = MIGRATIONS.len();
+
 impl Cache {
-    /// Initializes the index, its schema, and custom tokenization
-    pub(crate) fn initialize(&self) -> Result<()> {
-        self.conn.execute_batch(
-            "
-            CREATE TABLE IF NOT EXISTS links (
+    /// Registers the SQL functions the migrations and searches use, e.g.
+    /// normalize_url(). They must be registered before the migrations are
+    /// applied.
+    pub(crate) fn register_functions(&self) -> Result<()> {
+        self.conn.create_scalar_function(
+            "normalize_url",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| Ok(normalize_url(&ctx.get::<String>(0)?)),
+        )?;
+        // Scores where a query's words appear in a title (see title_position),
+        // parsing the words from the query once per statement
+        self.conn.create_scalar_function(
+            "title_position",
+            2,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| {
+                let words = ctx.get_or_create_aux(1, |query| -> rusqlite::Result<_> {
+                    Ok(title_words(query.as_str()?))
+                })?;
+                let title = ctx.get::<Option<String>>(0)?.unwrap_or_default();
+                Ok(title_position(&title, &words))
+            },
+        )?;
+        // Rounds a REAL to the f32 precision of Link::score, so searches can
+        // compare ranks with the scores callers were handed
+        self.conn.create_scalar_function(
+            "to_f32",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| Ok(ctx.get::<f64>(0)? as f32 as f64),
+        )?;
+        Ok(())
+    }
+
+    /// Initializes the index, its schema, and custom tokenization by applying
+    /// any migrations which haven't yet been applied to the database.
+    pub(crate) fn apply_migrations(&self) -> Result<()> {
+        let applied: u32 = self
+            .conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(applied as usize) {
+            let tx = self.conn.unchecked_transaction()?;
+            tx.execute_batch(migration)?;
+            tx.pragma_update(None, "user_version", (index + 1) as u32)?;
+            tx.commit()?;
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the search index from the links table when the index is
+    /// empty but links aren't, as happens with databases created before the
+    /// index existed (or whose index was lost). Returns true if it reindexed.
+    pub(crate) fn reindex_if_unindexed(&self) -> Result<bool> {
+        let (links, indexed): (i64, i64) = self.conn.query_row(
+            "SELECT (SELECT COUNT(*) FROM links), (SELECT COUNT(*) FROM links_fts)",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        if links == 0 || indexed > 0 {
+            return Ok(false);
+        }
+        self.reindex()?;
+        Ok(true)
+    }
+
+    /// Replaces the contents of the search index with every link in the
+    /// links table. The columns must be kept in step with the latest
+    /// migration which creates links_fts.
+    pub fn reindex(&self) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute_batch(
+            "DELETE FROM links_fts;
+             INSERT INTO links_fts
+             (url, title, subtitle, source, author, description, collection, canonical_url)
+             SELECT url, title, subtitle, source, author, description, collection,
+                 NULLIF(canonical_url, url)
+             FROM links;",
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Copies every link which can still be read from the links table of
+    /// the corrupt database at the path into this cache, stopping at the
+    /// first row which can't be read. Returns the number of links copied.
+    /// Only links are recovered; their metadata is lost.
+    pub(crate) fn salvage_links(&mut self, corrupt: &Path) -> Result<usize> {
+        let columns: Vec<String> = self
+            .conn
+            .prepare("SELECT name FROM pragma_table_info('links')")?
+            .query_map([], |row| row.get(0))?
+            .collect::<std::result::Result<_, rusqlite::Error>>()?;
+        let columns = columns.join(", ");
+
+        let mut rows: Vec<Vec<Value>> = vec![];
+        let read = Connection::open_with_flags(corrupt, OpenFlags::SQLITE_OPEN_READ_ONLY).and_then(
+            |source| {
+                let mut stmt = source.prepare(&format!("SELECT {columns} FROM links"))?;
+                let count = stmt.column_count();
+                let mut results = stmt.query([])?;
+                while let Some(row) = results.next()? {
+                    rows.push(
+                        (0..count)
+                            .map(|index| row.get(index))
+                            .collect::<std::result::Result<_, rusqlite::Error>>()?,
+                    );
+                }
+                Ok(())
+            },
+        );
+        if let Err(err) = read {
+            warn!("Stopped recovering links after {}: {}", rows.len(), err);
+        }
+
+        let tx = self.conn.transaction()?;
+        let mut recovered = 0;
+        {
+            let placeholders = vec!["?"; columns.split(", ").count()].join(", ");
+            let mut insert = tx.prepare(&format!(
+                "INSERT OR IGNORE INTO links ({columns}) VALUES ({placeholders})"
+            ))?;
+            for row in rows {
+                recovered += insert.execute(params_from_iter(row))?;
+            }
+        }
+        tx.commit()?;
+        Ok(recovered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_legacy_database_is_indexed_on_build() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let path = temp_dir.path().join("linkcache.sqlite");
+
+        // The schema from before the search index existed
+        let conn = Connection::open(&path)?;
+        conn.execute_batch(
+            "CREATE TABLE links (
                 url TEXT PRIMARY KEY,
                 title TEXT NOT NULL,
                 subtitle TEXT,
@@ -14,39 +191,34 @@ impl Cache {
                 author TEXT,
                 timestamp TEXT NOT NULL
             );
+            INSERT INTO links (url, title, timestamp)
+            VALUES ('https://www.sqlite.org/fts5.html', 'SQLite FTS5', '2024-03-15 08:00:00+00:00');",
+        )?;
+        drop(conn);
 
+        let cache = Cache::new(&path)?;
+        let results = cache.search("FTS5")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].url, "https://www.sqlite.org/fts5.html");
+        Ok(())
+    }
 
-            CREATE VIRTUAL TABLE IF NOT EXISTS links_fts USING fts5 (
-                url, title, subtitle, source, author,
-                tokenize='trigram'
-            );
-
-
-            CREATE TRIGGER IF NOT EXISTS links_upsert AFTER INSERT ON links
-            BEGIN
-                DELETE FROM links_fts WHERE url = new.url AND title = new.title;
-                INSERT INTO links_fts
-                (url, title, subtitle, source, author)
-                VALUES
-                (new.url, new.title, new.subtitle, new.source, new.author);
-            END;
-
-
-            CREATE TRIGGER IF NOT EXISTS links_update AFTER UPDATE ON links
-            BEGIN
-                INSERT OR REPLACE INTO links_fts
-                (url, title, subtitle, source, author)
-                VALUES
-                (new.url, new.title, new.subtitle, new.source, new.author);
-            END;
-
+    #[test]
+    fn test_empty_index_is_rebuilt_on_build() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let path = temp_dir.path().join("linkcache.sqlite");
+        let mut cache = Cache::new(&path)?;
+        cache.add(crate::Link::new(
+            "https://www.sqlite.org/fts5.html".to_string(),
+            "SQLite FTS5".to_string(),
+        ))?;
+        cache.conn.execute("DELETE FROM links_fts", [])?;
+        assert!(cache.search("FTS5")?.is_empty());
+        drop(cache);
 
-            CREATE TRIGGER IF NOT EXISTS links_delete BEFORE DELETE ON links
-            BEGIN
-                DELETE FROM links_fts WHERE url = old.url;
-            END;
-            ",
-        )?;
+        let cache = Cache::new(&path)?;
+        assert_eq!(cache.search("FTS5")?.len(), 1);
+        assert!(!cache.reindex_if_unindexed()?);
         Ok(())
     }
 }
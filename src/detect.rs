@@ -0,0 +1,149 @@
+use std::path::{Path, PathBuf};
+
+use crate::chrome::ChromiumFlavor;
+use crate::firefox::FirefoxFlavor;
+use crate::{arc, firefox, safari};
+
+/// A browser linkcache knows how to import from, and whether its default
+/// profile exists on this machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetectedBrowser {
+    /// The browser's name, which is also the source recorded on the links
+    /// imported from it (e.g. "chrome" or "librewolf").
+    pub name: String,
+
+    /// Where the browser's default profile is (or would be) stored. For
+    /// Firefox flavors whose default profile wasn't found, this is the
+    /// directory the profiles are expected to live in.
+    pub profile_dir: PathBuf,
+
+    /// True when the profile directory exists, so the browser can be
+    /// imported from.
+    pub available: bool,
+}
+
+/// Probes the default profile location of every supported browser in the
+/// current user's home directory. Every supported browser is returned, with
+/// `available` set for those which appear to be installed, so a front-end
+/// can list them all as import sources.
+pub fn detected_browsers() -> Vec<DetectedBrowser> {
+    let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/tmp"));
+    detected_browsers_in(std::env::consts::OS, &home_dir)
+}
+
+/// Probes the default profile location of every supported browser on the
+/// given operating system (as named by std::env::consts::OS) under the
+/// provided home directory.
+pub fn detected_browsers_in(os: &str, home_dir: &Path) -> Vec<DetectedBrowser> {
+    let mut browsers = vec![detected(
+        arc::SOURCE,
+        arc::Browser::profile_dir_in(os, home_dir),
+    )];
+    let chromium_flavors = [
+        ChromiumFlavor::Chrome,
+        ChromiumFlavor::Brave,
+        ChromiumFlavor::Edge,
+    ];
+    for flavor in chromium_flavors {
+        browsers.push(detected(
+            flavor.source(),
+            flavor.profile_dir_in(os, home_dir),
+        ));
+    }
+    if os == "macos" {
+        browsers.push(detected(
+            safari::SOURCE,
+            safari::Browser::profile_dir_in(home_dir),
+        ));
+    }
+
+    let flavors = [
+        FirefoxFlavor::Firefox,
+        FirefoxFlavor::LibreWolf,
+        FirefoxFlavor::Waterfox,
+        FirefoxFlavor::Zen,
+    ];
+    for flavor in flavors {
+        let Some(parent_dir) = flavor.profiles_dir(os, home_dir) else {
+            continue;
+        };
+        let browser = match firefox::Browser::find_profile_dir(parent_dir.clone()) {
+            Ok(profile_dir) => detected(flavor.source(), profile_dir),
+            Err(_) => DetectedBrowser {
+                name: flavor.source().to_string(),
+                profile_dir: parent_dir,
+                available: false,
+            },
+        };
+        browsers.push(browser);
+    }
+    browsers
+}
+
+fn detected(name: &str, profile_dir: PathBuf) -> DetectedBrowser {
+    DetectedBrowser {
+        name: name.to_string(),
+        available: profile_dir.is_dir(),
+        profile_dir,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn find<'a>(browsers: &'a [DetectedBrowser], name: &str) -> &'a DetectedBrowser {
+        browsers
+            .iter()
+            .find(|b| b.name == name)
+            .unwrap_or_else(|| panic!("{} should be listed", name))
+    }
+
+    #[test]
+    fn test_detected_browsers_in_fake_home() {
+        let home = TempDir::new().expect("Failed to create temp dir");
+        let chrome_dir = home.path().join(".config/google-chrome/Default");
+        let firefox_dir = home
+            .path()
+            .join(".mozilla/firefox/abcd1234.default-release");
+        std::fs::create_dir_all(&chrome_dir).unwrap();
+        std::fs::create_dir_all(&firefox_dir).unwrap();
+
+        let browsers = detected_browsers_in("linux", home.path());
+        assert_eq!(browsers.len(), 8);
+
+        let chrome = find(&browsers, "chrome");
+        assert!(chrome.available);
+        assert_eq!(chrome.profile_dir, chrome_dir);
+
+        let firefox = find(&browsers, "firefox");
+        assert!(firefox.available);
+        assert_eq!(firefox.profile_dir, firefox_dir);
+
+        assert!(!find(&browsers, "arc").available);
+        let brave = find(&browsers, "brave");
+        assert!(!brave.available);
+        assert_eq!(
+            brave.profile_dir,
+            home.path()
+                .join(".config/BraveSoftware/Brave-Browser/Default")
+        );
+        let zen = find(&browsers, "zen");
+        assert!(!zen.available);
+        assert_eq!(zen.profile_dir, home.path().join(".zen"));
+    }
+
+    #[test]
+    fn test_safari_is_only_detected_on_macos() {
+        let home = TempDir::new().expect("Failed to create temp dir");
+        std::fs::create_dir_all(home.path().join("Library/Safari")).unwrap();
+
+        let safari = find(&detected_browsers_in("macos", home.path()), "safari").clone();
+        assert!(safari.available);
+        assert_eq!(safari.profile_dir, home.path().join("Library/Safari"));
+        assert!(detected_browsers_in("linux", home.path())
+            .iter()
+            .all(|b| b.name != "safari"));
+    }
+}
@@ -0,0 +1,204 @@
+use std::io::{BufRead, BufReader, Read, Write};
+
+use rusqlite::types::Value;
+
+use crate::ddl::SCHEMA_VERSION;
+use crate::{Cache, Error, Result};
+
+/// The statement a dump starts with, followed by its schema version.
+const VERSION_PREFIX: &str = "PRAGMA user_version = ";
+
+/// The start of every other statement in a dump.
+const INSERT_PREFIX: &str = "INSERT OR REPLACE INTO links ";
+
+impl Cache {
+    /// Writes every link in the database (across all collections) as SQL
+    /// text, e.g. for keeping a curated set of links under version control.
+    /// The dump starts with the schema version, followed by an INSERT
+    /// statement per link, one per line. The search index isn't written,
+    /// since it's rebuilt from the links as they're imported (see
+    /// import_sql).
+    pub fn export_sql<W: Write>(&self, mut w: W) -> Result<()> {
+        let columns: Vec<String> = self
+            .conn
+            .prepare("SELECT name FROM pragma_table_info('links')")?
+            .query_map([], |row| row.get(0))?
+            .collect::<std::result::Result<_, rusqlite::Error>>()?;
+        let columns = columns.join(", ");
+
+        writeln!(w, "-- linkcache links")?;
+        writeln!(w, "{}{};", VERSION_PREFIX, SCHEMA_VERSION)?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {columns} FROM links ORDER BY collection, url"
+        ))?;
+        let count = stmt.column_count();
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let values = (0..count)
+                .map(|index| row.get(index).map(|value| sql_literal(&value)))
+                .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+            writeln!(
+                w,
+                "{}({}) VALUES ({});",
+                INSERT_PREFIX,
+                columns,
+                values.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Adds the links of a dump written by export_sql in a single
+    /// transaction, replacing any stored under the same url. Dumps of an
+    /// older schema can be imported, but not those of a newer one. Only the
+    /// statements export_sql writes are run; anything else is an
+    /// Error::Parse. Returns the number of links imported.
+    pub fn import_sql<R: Read>(&mut self, r: R) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        let mut imported = 0;
+        for (index, line) in BufReader::new(r).lines().enumerate() {
+            let line = line?;
+            let statement = line.trim();
+            if statement.is_empty() || statement.starts_with("--") {
+                continue;
+            }
+            if let Some(version) = statement.strip_prefix(VERSION_PREFIX) {
+                let version: usize = version
+                    .trim_end_matches(';')
+                    .parse()
+                    .map_err(|_| Error::Parse(format!("Invalid schema version: {}", line)))?;
+                if version > SCHEMA_VERSION {
+                    return Err(Error::Parse(format!(
+                        "The dump's schema version ({}) is newer than this cache's ({})",
+                        version, SCHEMA_VERSION
+                    )));
+                }
+            } else if statement.starts_with(INSERT_PREFIX) && statement.ends_with(';') {
+                imported += tx.execute(statement, [])?;
+            } else {
+                return Err(Error::Parse(format!(
+                    "Unexpected statement on line {} of the dump",
+                    index + 1
+                )));
+            }
+        }
+        // Dumps from before urls were normalized don't have them
+        tx.execute(
+            "UPDATE links SET normalized_url = normalize_url(url) WHERE normalized_url IS NULL",
+            [],
+        )?;
+        tx.commit()?;
+        Ok(imported)
+    }
+}
+
+/// Formats the value as a SQL literal. Line breaks in text are written as
+/// char() calls so every statement stays on one line.
+fn sql_literal(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Integer(integer) => integer.to_string(),
+        Value::Real(real) => format!("{:?}", real),
+        Value::Text(text) => format!(
+            "'{}'",
+            text.replace('\'', "''")
+                .replace('\r', "' || char(13) || '")
+                .replace('\n', "' || char(10) || '")
+        ),
+        Value::Blob(bytes) => {
+            let hex: String = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+            format!("X'{}'", hex)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::create_test_cache;
+    use crate::{CacheBuilder, Link, LinkKind};
+
+    fn dump(cache: &Cache) -> Result<String> {
+        let mut buffer = vec![];
+        cache.export_sql(&mut buffer)?;
+        Ok(String::from_utf8(buffer).expect("Dumps should be UTF-8"))
+    }
+
+    #[test]
+    fn test_export_and_import_sql() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("linkcache.sqlite");
+        let mut cache = Cache::new(&path)?;
+        cache.add(
+            Link::new(
+                "https://www.rust-lang.org/".to_string(),
+                "Rust's Home".to_string(),
+            )
+            .with_description("A language empowering everyone\nto build software".to_string())
+            .with_kind(LinkKind::Bookmark),
+        )?;
+        cache.add(Link::new(
+            "https://crates.io/".to_string(),
+            "crates.io".to_string(),
+        ))?;
+        cache.record_open("https://crates.io/")?;
+        CacheBuilder::new()
+            .with_path(&path)
+            .with_collection("work")
+            .build()?
+            .add(Link::new(
+                "https://jira.example.com/".to_string(),
+                "Jira".to_string(),
+            ))?;
+
+        let exported = dump(&cache)?;
+        assert!(exported.contains(&format!("{}{};", VERSION_PREFIX, SCHEMA_VERSION)));
+        assert_eq!(
+            exported
+                .lines()
+                .filter(|line| line.starts_with(INSERT_PREFIX))
+                .count(),
+            3
+        );
+
+        let mut restored = create_test_cache();
+        assert_eq!(restored.import_sql(exported.as_bytes())?, 3);
+        assert_eq!(dump(&restored)?, exported);
+        let rust = restored.search("empowering")?;
+        assert_eq!(rust.len(), 1);
+        assert_eq!(rust[0].title, "Rust's Home");
+        assert_eq!(
+            rust[0].description.as_deref(),
+            Some("A language empowering everyone\nto build software")
+        );
+        assert!(restored
+            .get_by_url("https://crates.io/")?
+            .unwrap()
+            .last_opened_at
+            .is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_sql_rejects_other_statements() -> Result<()> {
+        let mut cache = create_test_cache();
+        let newer = format!("{}{};", VERSION_PREFIX, SCHEMA_VERSION + 1);
+        assert!(matches!(
+            cache.import_sql(newer.as_bytes()),
+            Err(Error::Parse(_))
+        ));
+        assert!(matches!(
+            cache.import_sql("DROP TABLE links;".as_bytes()),
+            Err(Error::Parse(_))
+        ));
+        assert!(cache
+            .import_sql(
+                "INSERT OR REPLACE INTO links (url, title, timestamp) \
+                 VALUES ('https://x.org/', 'X', '2024-01-01'); DROP TABLE links;"
+                    .as_bytes()
+            )
+            .is_err());
+        assert_eq!(cache.count()?, 0);
+        Ok(())
+    }
+}
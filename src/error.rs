@@ -1,4 +1,5 @@
 use std::fmt;
+use std::path::PathBuf;
 
 #[derive(Debug)]
 pub enum Error {
@@ -6,6 +7,11 @@ pub enum Error {
     Parse(String),
     Serde(serde_json::Error),
     Rusqlite(rusqlite::Error),
+    /// An operation was abandoned because it ran longer than allowed
+    Timeout,
+    /// The cache database doesn't exist, and the CacheBuilder was told not
+    /// to create it
+    CacheNotFound(PathBuf),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -17,6 +23,10 @@ impl fmt::Display for Error {
             Error::Parse(ref desc) => write!(f, "Parse Error: {}", desc),
             Error::Serde(ref err) => write!(f, "Serde Error: {}", err),
             Error::Rusqlite(ref err) => write!(f, "Rusqlite Error: {}", err),
+            Error::Timeout => write!(f, "Timeout Error: the operation took too long"),
+            Error::CacheNotFound(ref path) => {
+                write!(f, "Cache Not Found: {} doesn't exist", path.display())
+            }
         }
     }
 }
@@ -46,6 +56,8 @@ impl std::error::Error for Error {
             Error::Parse(_) => None,
             Error::Serde(ref err) => Some(err),
             Error::Rusqlite(ref err) => Some(err),
+            Error::Timeout => None,
+            Error::CacheNotFound(_) => None,
         }
     }
 }
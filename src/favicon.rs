@@ -0,0 +1,249 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::link::host_of;
+use crate::{Cache, Link, Result};
+
+/// The directory within the Cache's data_dir which extracted favicons are
+/// written to.
+const FAVICONS_DIR: &str = "favicons";
+
+impl Cache {
+    /// Stores the bytes of the favicon (and their mime type, e.g.
+    /// "image/png") for the host of the provided url, replacing any icon
+    /// already stored for it. Icons are kept per host rather than per link,
+    /// and are shared by every collection. Returns false if the url has no
+    /// host to store the icon under.
+    pub fn store_favicon(&mut self, url: &str, bytes: &[u8], mime: &str) -> Result<bool> {
+        Self::insert_favicon(&self.conn, url, bytes, mime)
+    }
+
+    /// Returns the bytes of the favicon stored for the host of the provided
+    /// url, or None when there isn't one.
+    pub fn favicon_for(&self, url: &str) -> Result<Option<Vec<u8>>> {
+        let Some(host) = host_of(url) else {
+            return Ok(None);
+        };
+        let bytes = self
+            .conn
+            .query_row(
+                "SELECT bytes FROM favicons WHERE host = ?1",
+                [host],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(bytes)
+    }
+
+    /// Writes the favicon stored for each link's host into the favicons
+    /// directory of the Cache's data_dir, and points the link's icon_path at
+    /// the file, so launchers like Alfred can show it. A file is only
+    /// rewritten when the stored icon is newer than it. Links whose host has
+    /// no icon are left as they are, as are all the links of an in-memory
+    /// Cache. Returns the number of links given an icon_path.
+    pub fn extract_icons(&self, links: &mut [Link]) -> Result<usize> {
+        let Some(icons_dir) = self.data_dir().map(|dir| dir.join(FAVICONS_DIR)) else {
+            return Ok(0);
+        };
+        let mut extracted_hosts: HashMap<String, Option<PathBuf>> = HashMap::new();
+        let mut extracted = 0;
+        for link in links.iter_mut() {
+            let Some(host) = host_of(&link.url) else {
+                continue;
+            };
+            let path = match extracted_hosts.get(&host) {
+                Some(path) => path.clone(),
+                None => {
+                    let path = self.extract_icon(&host, &icons_dir)?;
+                    extracted_hosts.insert(host, path.clone());
+                    path
+                }
+            };
+            if let Some(path) = path {
+                link.icon_path = Some(path);
+                extracted += 1;
+            }
+        }
+        Ok(extracted)
+    }
+
+    /// Writes the favicon stored for the host into icons_dir, unless the file
+    /// is already up to date. Returns its path, or None when the host has no
+    /// icon.
+    fn extract_icon(&self, host: &str, icons_dir: &Path) -> Result<Option<PathBuf>> {
+        let icon = self
+            .conn
+            .query_row(
+                "SELECT bytes, mime, fetched_at FROM favicons WHERE host = ?1",
+                [host],
+                |row| {
+                    Ok((
+                        row.get::<_, Vec<u8>>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, DateTime<Utc>>(2)?,
+                    ))
+                },
+            )
+            .optional()?;
+        let Some((bytes, mime, fetched_at)) = icon else {
+            return Ok(None);
+        };
+        let path = icons_dir.join(format!("{}.{}", file_stem(host), extension_for(&mime)));
+        let up_to_date = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| DateTime::<Utc>::from(modified) >= fetched_at);
+        if !up_to_date {
+            fs::create_dir_all(icons_dir)?;
+            fs::write(&path, bytes)?;
+        }
+        Ok(Some(path))
+    }
+
+    /// Stores the favicons read from a browser, given as the url of a page
+    /// which uses the icon along with the icon's bytes, in a single
+    /// transaction. When several icons are given for the same host, the
+    /// last one is kept. Returns the number of hosts which were given icons.
+    pub(crate) fn store_favicons(
+        &mut self,
+        icons: impl IntoIterator<Item = (String, Vec<u8>)>,
+    ) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        let mut hosts = HashSet::new();
+        for (url, bytes) in icons {
+            if Self::insert_favicon(&tx, &url, &bytes, sniff_mime(&bytes))? {
+                hosts.extend(host_of(&url));
+            }
+        }
+        tx.commit()?;
+        Ok(hosts.len())
+    }
+
+    /// Writes a single favicon using the provided connection, which may be a
+    /// transaction wrapping many inserts.
+    fn insert_favicon(conn: &Connection, url: &str, bytes: &[u8], mime: &str) -> Result<bool> {
+        let Some(host) = host_of(url) else {
+            return Ok(false);
+        };
+        conn.execute(
+            "INSERT OR REPLACE INTO favicons (host, bytes, mime, fetched_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            (host, bytes, mime, Utc::now()),
+        )?;
+        Ok(true)
+    }
+}
+
+/// Guesses the mime type of a browser's favicon from its first bytes, since
+/// neither Chrome nor Firefox record it. Unrecognized icons are assumed to
+/// be PNGs, which is what both browsers store most of them as.
+fn sniff_mime(bytes: &[u8]) -> &'static str {
+    if bytes.starts_with(&[0x00, 0x00, 0x01, 0x00]) {
+        "image/x-icon"
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        "image/jpeg"
+    } else if bytes.starts_with(b"GIF8") {
+        "image/gif"
+    } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        "image/webp"
+    } else if bytes.trim_ascii_start().starts_with(b"<") {
+        "image/svg+xml"
+    } else {
+        "image/png"
+    }
+}
+
+/// Returns the file extension for an icon of the mime type.
+fn extension_for(mime: &str) -> &'static str {
+    match mime {
+        "image/x-icon" | "image/vnd.microsoft.icon" => "ico",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+        _ => "png",
+    }
+}
+
+/// Turns a host into a file name, replacing the characters some platforms
+/// don't allow (e.g. the colons of an IPv6 address).
+fn file_stem(host: &str) -> String {
+    host.replace(
+        |c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '-'),
+        "_",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::create_test_cache;
+
+    #[test]
+    fn test_favicon_round_trip() -> Result<()> {
+        let mut cache = create_test_cache();
+        let png = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0x00];
+        assert!(cache.store_favicon("https://www.rust-lang.org/learn", &png, "image/png")?);
+        assert!(!cache.store_favicon("about:blank", &png, "image/png")?);
+
+        // Every page on the host shares its icon
+        assert_eq!(
+            cache.favicon_for("https://WWW.rust-lang.org/tools")?,
+            Some(png.to_vec())
+        );
+        assert_eq!(cache.favicon_for("https://crates.io/")?, None);
+
+        cache.store_favicon("https://www.rust-lang.org/", b"<svg/>", "image/svg+xml")?;
+        assert_eq!(
+            cache.favicon_for("https://www.rust-lang.org/")?,
+            Some(b"<svg/>".to_vec())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_icons() -> Result<()> {
+        let data_dir = tempfile::tempdir()?;
+        let mut cache = Cache::new(data_dir.path().join("linkcache.sqlite"))?;
+        cache.store_favicon("https://www.rust-lang.org/", b"<svg/>", "image/svg+xml")?;
+
+        let mut links = vec![
+            Link::new(
+                "https://www.rust-lang.org/learn".to_string(),
+                "Learn".to_string(),
+            ),
+            Link::new(
+                "https://www.rust-lang.org/tools".to_string(),
+                "Tools".to_string(),
+            ),
+            Link::new("https://crates.io/".to_string(), "crates.io".to_string()),
+        ];
+        assert_eq!(cache.extract_icons(&mut links)?, 2);
+        let icon_path = data_dir
+            .path()
+            .join("favicons")
+            .join("www.rust-lang.org.svg");
+        assert_eq!(links[0].icon_path, Some(icon_path.clone()));
+        assert_eq!(links[1].icon_path, Some(icon_path.clone()));
+        assert_eq!(links[2].icon_path, None);
+        assert_eq!(fs::read(&icon_path)?, b"<svg/>");
+
+        // In-memory caches have nowhere to write icons
+        let mut memory_cache = create_test_cache();
+        memory_cache.store_favicon("https://crates.io/", b"<svg/>", "image/svg+xml")?;
+        assert_eq!(memory_cache.data_dir(), None);
+        assert_eq!(memory_cache.extract_icons(&mut links[2..])?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sniff_mime() {
+        assert_eq!(sniff_mime(&[0x89, b'P', b'N', b'G']), "image/png");
+        assert_eq!(sniff_mime(&[0x00, 0x00, 0x01, 0x00, 0x01]), "image/x-icon");
+        assert_eq!(sniff_mime(b"  <svg xmlns=\"\"/>"), "image/svg+xml");
+        assert_eq!(sniff_mime(b"RIFF\0\0\0\0WEBPVP8 "), "image/webp");
+    }
+}
@@ -1,35 +1,232 @@
 use chrono::DateTime;
+use log::debug;
+use rusqlite::{Connection, OptionalExtension};
 use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
 use std::fs::File;
 use std::io::BufReader;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::cache::Cache;
 use crate::error::Result;
-use crate::link::Link;
+use crate::import::ImportStats;
+use crate::link::{Link, LinkKind};
+use crate::replica::replicate;
+use crate::BrowserSource;
+
+/// The source recorded on every Link imported from Firefox
+pub const SOURCE: &str = "firefox";
+
+/// The source for links of tabs which are open in Firefox. linkcache doesn't
+/// read open tabs itself; an integrator which adds them under this source
+/// can rank them above other matches with CacheBuilder::with_source_boosts
+/// (the user is clearly interested in them), and let them drop out once
+/// they've been closed with CacheBuilder::with_source_ttls.
+pub const TAB_SOURCE: &str = "firefox:tab";
+
+/// Browsers built on Firefox which share its profile format (places.sqlite,
+/// bookmark backups, etc) but keep their profiles in their own directories.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FirefoxFlavor {
+    #[default]
+    Firefox,
+    LibreWolf,
+    Waterfox,
+    Zen,
+}
+
+impl FirefoxFlavor {
+    /// The source recorded on every Link imported from this flavor.
+    pub fn source(&self) -> &'static str {
+        match self {
+            FirefoxFlavor::Firefox => SOURCE,
+            FirefoxFlavor::LibreWolf => "librewolf",
+            FirefoxFlavor::Waterfox => "waterfox",
+            FirefoxFlavor::Zen => "zen",
+        }
+    }
+
+    /// Returns the directory containing this flavor's profiles on the given
+    /// operating system (as named by std::env::consts::OS), relative to the
+    /// provided home directory. Returns None for unsupported systems.
+    pub fn profiles_dir(&self, os: &str, home_dir: &Path) -> Option<PathBuf> {
+        let relative = match (self, os) {
+            (FirefoxFlavor::Firefox, "macos") => "Library/Application Support/Firefox/Profiles",
+            (FirefoxFlavor::Firefox, "linux") => ".mozilla/firefox",
+            (FirefoxFlavor::Firefox, "windows") => "AppData/Roaming/Mozilla/Firefox/Profiles",
+            (FirefoxFlavor::LibreWolf, "macos") => "Library/Application Support/librewolf/Profiles",
+            (FirefoxFlavor::LibreWolf, "linux") => ".librewolf",
+            (FirefoxFlavor::LibreWolf, "windows") => "AppData/Roaming/librewolf/Profiles",
+            (FirefoxFlavor::Waterfox, "macos") => "Library/Application Support/Waterfox/Profiles",
+            (FirefoxFlavor::Waterfox, "linux") => ".waterfox",
+            (FirefoxFlavor::Waterfox, "windows") => "AppData/Roaming/Waterfox/Profiles",
+            (FirefoxFlavor::Zen, "macos") => "Library/Application Support/zen/Profiles",
+            (FirefoxFlavor::Zen, "linux") => ".zen",
+            (FirefoxFlavor::Zen, "windows") => "AppData/Roaming/zen/Profiles",
+            _ => return None,
+        };
+        Some(home_dir.join(relative))
+    }
+}
 
 pub struct Browser {
     profile_dir: PathBuf,
+    flavor: FirefoxFlavor,
+    bookmark_folders: Vec<String>,
+    skip_unvisited: bool,
+}
+
+/// A bookmark folder in places.sqlite
+struct PlacesFolder {
+    parent: i64,
+    title: String,
+    guid: String,
+}
+
+impl PlacesFolder {
+    /// Returns the name Firefox displays for the folder: its title, except
+    /// for the root folders, whose stored titles are e.g. "toolbar".
+    fn display_name(&self) -> &str {
+        match self.guid.as_str() {
+            "menu________" => "Bookmarks Menu",
+            "toolbar_____" => "Bookmarks Toolbar",
+            "unfiled_____" => "Other Bookmarks",
+            "mobile______" => "Mobile Bookmarks",
+            _ => &self.title,
+        }
+    }
+
+    /// Returns true when the name is this folder's title or the name Firefox
+    /// displays for it. Case-insensitive.
+    fn is_named(&self, name: &str) -> bool {
+        self.title.eq_ignore_ascii_case(name) || self.display_name().eq_ignore_ascii_case(name)
+    }
+}
+
+/// Returns the path of folders down to the folder with the provided id
+/// (including it), e.g. "Bookmarks Toolbar / Dev", leaving out the untitled
+/// root which holds every other folder.
+fn folder_path(folders: &HashMap<i64, PlacesFolder>, folder_id: i64) -> String {
+    let mut names = vec![];
+    let mut current = folders.get(&folder_id);
+    // Bounded by the number of folders in case of a corrupt cycle
+    for _ in 0..folders.len() {
+        let Some(folder) = current else {
+            break;
+        };
+        if !folder.display_name().is_empty() {
+            names.push(folder.display_name());
+        }
+        current = folders.get(&folder.parent);
+    }
+    names.reverse();
+    names.join(" / ")
 }
 
 impl Browser {
     pub fn new() -> Result<Self> {
+        Self::with_flavor(FirefoxFlavor::Firefox)
+    }
+
+    /// Constructs a Browser reading from the default profile of the provided
+    /// Firefox flavor (e.g. LibreWolf or Zen).
+    pub fn with_flavor(flavor: FirefoxFlavor) -> Result<Self> {
+        let parent_dir = Self::default_profile_parent_dir_for(flavor)?;
         Ok(Browser {
-            profile_dir: Self::default_profile_dir()?,
+            profile_dir: Self::find_profile_dir(parent_dir)?,
+            flavor,
+            bookmark_folders: vec![],
+            skip_unvisited: true,
         })
     }
 
+    /// Constructs a Browser reading from the provided profile directory
+    /// without probing for the default Firefox profile, which may not exist
+    /// on machines where Firefox isn't installed.
+    pub fn from_profile_dir(dir: PathBuf) -> Self {
+        Browser {
+            profile_dir: dir,
+            flavor: FirefoxFlavor::Firefox,
+            bookmark_folders: vec![],
+            skip_unvisited: true,
+        }
+    }
+
     pub fn with_profile_dir(mut self, dir: PathBuf) -> Self {
         self.profile_dir = dir;
         self
     }
 
-    pub fn cache_bookmarks(&self, cache: &mut Cache) -> Result<()> {
-        let links = self.bookmark_links()?;
-        for link in links {
-            cache.add(link)?;
+    /// Restricts all_bookmarks (and so cache_bookmarks) to bookmarks inside
+    /// one of the named folders, at any depth, e.g. &["Toolbar"] to skip
+    /// "Mobile Bookmarks". Names are matched case-insensitively, and the root
+    /// folders match both their displayed and stored names ("Bookmarks
+    /// Toolbar" or "toolbar"). By default every folder is imported.
+    pub fn with_bookmark_folders(mut self, folders: &[&str]) -> Self {
+        self.bookmark_folders = folders.iter().map(|f| f.to_string()).collect();
+        self
+    }
+
+    /// Controls whether history entries which were never actually visited
+    /// (visit_count = 0, e.g. typed but abandoned urls) are skipped by
+    /// cache_history and history_links. Defaults to true.
+    pub fn with_skip_unvisited(mut self, skip_unvisited: bool) -> Self {
+        self.skip_unvisited = skip_unvisited;
+        self
+    }
+
+    /// Adds every bookmark from this profile to the provided Cache. They're
+    /// read from places.sqlite when the profile has one, and otherwise from
+    /// the most recent bookmark backup. Rows of places.sqlite which can't be
+    /// read are skipped, and counted in the returned stats.
+    pub fn cache_bookmarks(&self, cache: &mut Cache) -> Result<ImportStats> {
+        let (links, skipped) = self.bookmarks()?;
+        let imported = cache.add_batch(links)?;
+        Ok(ImportStats { imported, skipped })
+    }
+
+    /// Adds the bookmarks of every profile listed beside this one in
+    /// profiles.ini (see profile_dirs_in) to the provided Cache, as
+    /// cache_bookmarks does for a single profile, returning the combined
+    /// stats. Each link records the profile it came from.
+    pub fn cache_all_profiles(&self, cache: &mut Cache) -> Result<ImportStats> {
+        let parent_dir = self.profile_dir.parent().unwrap_or(&self.profile_dir);
+        let mut stats = ImportStats::default();
+        for profile_dir in Self::profile_dirs_in(parent_dir)? {
+            let browser = Browser {
+                profile_dir,
+                flavor: self.flavor,
+                bookmark_folders: self.bookmark_folders.clone(),
+                skip_unvisited: self.skip_unvisited,
+            };
+            let profile_stats = browser.cache_bookmarks(cache)?;
+            stats.imported += profile_stats.imported;
+            stats.skipped += profile_stats.skipped;
+        }
+        Ok(stats)
+    }
+
+    /// Adds every visited page in this profile's history to the provided
+    /// Cache. Rows of places.sqlite which can't be read are skipped, and
+    /// counted in the returned stats.
+    pub fn cache_history(&self, cache: &mut Cache) -> Result<ImportStats> {
+        self.create_places_replica()?;
+        let (links, skipped) = self.places_history()?;
+        let imported = cache.add_batch(links)?;
+        Ok(ImportStats { imported, skipped })
+    }
+
+    /// Returns every bookmark in this profile, from places.sqlite when the
+    /// profile has one and otherwise from the most recent bookmark backup,
+    /// along with the number of places.sqlite rows which were skipped.
+    fn bookmarks(&self) -> Result<(Vec<Link>, usize)> {
+        if self.places_path().exists() {
+            self.create_places_replica()?;
+            self.places_bookmarks()
+        } else {
+            Ok((self.bookmark_links()?, 0))
         }
-        Ok(())
     }
 
     pub fn bookmark_links(&self) -> Result<Vec<Link>> {
@@ -38,7 +235,7 @@ impl Browser {
         let reader = BufReader::new(file);
         let json: Value = serde_json::from_reader(reader)?;
 
-        fn traverse(node: &Value, links: &mut Vec<Link>) {
+        fn traverse(node: &Value, links: &mut Vec<Link>, source: &str) {
             if let Some(obj) = node.as_object() {
                 // Firefox bookmarks have different JSON structure than Chrome
                 if obj.contains_key("type") && obj["type"] == "bookmark" {
@@ -53,6 +250,8 @@ impl Browser {
                             title: title.to_string(),
                             url: uri.to_string(),
                             subtitle: None, // Firefox doesn't have folder paths like Chrome
+                            source: Some(source.to_string()),
+                            kind: Some(LinkKind::Bookmark),
                             timestamp: DateTime::from_timestamp(date_added, 0)
                                 .expect("Failed to convert timestamp"),
                             ..Default::default()
@@ -63,7 +262,7 @@ impl Browser {
                 // Recursively process children
                 if let Some(children) = obj.get("children").and_then(Value::as_array) {
                     for child in children {
-                        traverse(child, links);
+                        traverse(child, links, source);
                     }
                 }
             }
@@ -71,13 +270,292 @@ impl Browser {
 
         if let Some(children) = json.get("children").and_then(Value::as_array) {
             for child in children {
-                traverse(child, &mut links);
+                traverse(child, &mut links, self.flavor.source());
             }
         }
 
+        let profile = self.profile_name();
+        for link in &mut links {
+            link.profile = profile.clone();
+        }
         Ok(links)
     }
 
+    /// Scans the copy of places.sqlite (this function assumes it already
+    /// exists) and returns a Link for each bookmark. Folders, separators and
+    /// the entries Firefox uses to record tags are skipped. Untitled
+    /// bookmarks fall back to the page's title, then to the url. A bookmark's
+    /// description (which older Firefox versions let users enter) becomes
+    /// the Link's description, and the path of folders it's in (e.g.
+    /// "Bookmarks Toolbar / Dev") its subtitle. Bookmarks outside the
+    /// folders set by with_bookmark_folders, and rows which can't be read,
+    /// are skipped.
+    pub fn all_bookmarks(&self) -> Result<Vec<Link>> {
+        Ok(self.places_bookmarks()?.0)
+    }
+
+    /// Returns the bookmarks all_bookmarks() does, along with the number of
+    /// rows which were skipped because they couldn't be read.
+    fn places_bookmarks(&self) -> Result<(Vec<Link>, usize)> {
+        let conn = Connection::open(self.places_replica_path())?;
+        let mut stmt = conn.prepare(&format!(
+            r#"
+                SELECT moz_places.url,
+                COALESCE(NULLIF(moz_bookmarks.title, ''), NULLIF(moz_places.title, ''), moz_places.url),
+                moz_bookmarks.dateAdded,
+                moz_bookmarks.parent,
+                {description}
+                FROM moz_bookmarks
+                JOIN moz_places ON moz_bookmarks.fk = moz_places.id
+                WHERE moz_bookmarks.type = 1
+                AND moz_bookmarks.parent NOT IN (
+                    SELECT tag.id FROM moz_bookmarks AS tag
+                    JOIN moz_bookmarks AS tags ON tag.parent = tags.id
+                    WHERE tags.guid = 'tags________'
+                )
+                ORDER BY moz_bookmarks.dateAdded ASC
+            "#,
+            description = Self::bookmark_description_expr(&conn)?,
+        ))?;
+        let source = self.flavor.source();
+        let profile = self.profile_name();
+        let mut skipped = 0;
+        let bookmarks: Vec<(Link, i64)> = stmt
+            .query_map([], |row| {
+                // Firefox stores times in microseconds since the epoch
+                let date_added: i64 = row.get(2)?;
+                let link = Link {
+                    url: row.get(0)?,
+                    title: row.get(1)?,
+                    source: Some(source.to_string()),
+                    kind: Some(LinkKind::Bookmark),
+                    profile: profile.clone(),
+                    timestamp: DateTime::from_timestamp_micros(date_added).unwrap_or_default(),
+                    description: row.get(4)?,
+                    ..Default::default()
+                };
+                Ok((link, row.get::<_, i64>(3)?))
+            })?
+            .filter_map(|bookmark| {
+                bookmark
+                    .inspect_err(|err| {
+                        debug!("Skipping unreadable bookmark row: {}", err);
+                        skipped += 1;
+                    })
+                    .ok()
+            })
+            .collect();
+        if skipped > 0 {
+            debug!("Skipped {} unreadable bookmark rows", skipped);
+        }
+
+        let folders = Self::places_folders(&conn)?;
+        let links = bookmarks
+            .into_iter()
+            .filter(|(_, parent)| {
+                self.bookmark_folders.is_empty() || self.in_bookmark_folders(&folders, *parent)
+            })
+            .map(|(mut link, parent)| {
+                let path = folder_path(&folders, parent);
+                link.subtitle = Some(path).filter(|path| !path.is_empty());
+                link
+            })
+            .collect();
+        Ok((links, skipped))
+    }
+
+    /// Scans the copy of places.sqlite (this function assumes it already
+    /// exists) and returns a Link for each page in the history, most recent
+    /// visit first, carrying its visit_count and whether it was typed into the
+    /// address bar. Pages with no visit date (e.g. only bookmarked or
+    /// prefetched), those Firefox hides from its own history, rows which
+    /// can't be read and, unless with_skip_unvisited(false) was set, pages
+    /// with no visits are skipped.
+    pub fn history_links(&self) -> Result<Vec<Link>> {
+        Ok(self.places_history()?.0)
+    }
+
+    /// Returns the links history_links() does, along with the number of
+    /// rows which were skipped because they couldn't be read.
+    fn places_history(&self) -> Result<(Vec<Link>, usize)> {
+        let conn = Connection::open(self.places_replica_path())?;
+        let mut stmt = conn.prepare(
+            r#"
+                SELECT url, COALESCE(NULLIF(title, ''), url), last_visit_date, visit_count, typed
+                FROM moz_places
+                WHERE last_visit_date IS NOT NULL
+                AND (?1 = 0 OR visit_count > 0)
+                AND hidden = 0
+                ORDER BY last_visit_date DESC, url ASC
+            "#,
+        )?;
+        let source = self.flavor.source();
+        let profile = self.profile_name();
+        let mut skipped = 0;
+        let links = stmt
+            .query_map([self.skip_unvisited], |row| {
+                // Firefox stores times in microseconds since the epoch
+                let last_visit_date: i64 = row.get(2)?;
+                Ok(Link {
+                    url: row.get(0)?,
+                    title: row.get(1)?,
+                    source: Some(source.to_string()),
+                    kind: Some(LinkKind::History),
+                    profile: profile.clone(),
+                    timestamp: DateTime::from_timestamp_micros(last_visit_date).unwrap_or_default(),
+                    visit_count: row.get(3)?,
+                    typed_count: row.get(4)?,
+                    ..Default::default()
+                })
+            })?
+            .filter_map(|link| {
+                link.inspect_err(|err| {
+                    debug!("Skipping unreadable history row: {}", err);
+                    skipped += 1;
+                })
+                .ok()
+            })
+            .collect();
+        if skipped > 0 {
+            debug!("Skipped {} unreadable history rows", skipped);
+        }
+        Ok((links, skipped))
+    }
+
+    /// Returns a SQL expression for the description of the bookmark in the
+    /// current moz_bookmarks row, kept in its "bookmarkProperties/description"
+    /// annotation. Newer versions of Firefox dropped the tables annotations
+    /// were kept in, and their bookmarks have no description.
+    fn bookmark_description_expr(conn: &Connection) -> Result<&'static str> {
+        let annotation_tables: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master
+             WHERE type = 'table' AND name IN ('moz_items_annos', 'moz_anno_attributes')",
+            [],
+            |row| row.get(0),
+        )?;
+        if annotation_tables < 2 {
+            return Ok("NULL");
+        }
+        Ok("(
+            SELECT NULLIF(moz_items_annos.content, '')
+            FROM moz_items_annos
+            JOIN moz_anno_attributes ON moz_anno_attributes.id = moz_items_annos.anno_attribute_id
+            WHERE moz_items_annos.item_id = moz_bookmarks.id
+            AND moz_anno_attributes.name = 'bookmarkProperties/description'
+        )")
+    }
+
+    /// Returns every bookmark folder in places.sqlite, keyed by id.
+    fn places_folders(conn: &Connection) -> Result<HashMap<i64, PlacesFolder>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, parent, COALESCE(title, ''), COALESCE(guid, '')
+             FROM moz_bookmarks
+             WHERE type = 2",
+        )?;
+        let folders = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    PlacesFolder {
+                        parent: row.get(1)?,
+                        title: row.get(2)?,
+                        guid: row.get(3)?,
+                    },
+                ))
+            })?
+            .collect::<std::result::Result<HashMap<_, _>, rusqlite::Error>>()?;
+        Ok(folders)
+    }
+
+    /// Returns true when the folder, or any of its ancestors, is one of the
+    /// folders set by with_bookmark_folders.
+    fn in_bookmark_folders(&self, folders: &HashMap<i64, PlacesFolder>, folder_id: i64) -> bool {
+        let mut current = folders.get(&folder_id);
+        // Bounded by the number of folders in case of a corrupt cycle
+        for _ in 0..folders.len() {
+            let Some(folder) = current else {
+                break;
+            };
+            if self
+                .bookmark_folders
+                .iter()
+                .any(|name| folder.is_named(name))
+            {
+                return true;
+            }
+            current = folders.get(&folder.parent);
+        }
+        false
+    }
+
+    /// Copies places.sqlite to a replica which can be read while Firefox
+    /// holds its lock on the original. While Firefox is running, its latest
+    /// writes are in the write-ahead log (places.sqlite-wal), so the log and
+    /// its index (-shm) are copied alongside it when they exist. The copy is
+    /// skipped when the replica is already newer than places.sqlite and its
+    /// log, since the files may be large and are often unchanged between
+    /// refreshes. Returns true if it copied.
+    pub fn create_places_replica(&self) -> Result<bool> {
+        replicate(&self.places_path(), &self.places_replica_path())
+    }
+
+    /// Stores the favicons Firefox has cached for the pages in its history
+    /// in the provided Cache (see Cache::favicon_for), keeping the largest
+    /// icon for each host. Returns the number of hosts given icons, which is
+    /// 0 when the profile has no favicons.sqlite.
+    pub fn cache_favicons(&self, cache: &mut Cache) -> Result<usize> {
+        if !self.favicons_path().exists() {
+            return Ok(0);
+        }
+        replicate(&self.favicons_path(), &self.favicons_replica_path())?;
+        let conn = Connection::open(self.favicons_replica_path())?;
+        let mut stmt = conn.prepare(
+            r#"
+                SELECT moz_pages_w_icons.page_url, moz_icons.data
+                FROM moz_icons_to_pages
+                JOIN moz_pages_w_icons ON moz_pages_w_icons.id = moz_icons_to_pages.page_id
+                JOIN moz_icons ON moz_icons.id = moz_icons_to_pages.icon_id
+                WHERE moz_icons.data IS NOT NULL
+                ORDER BY moz_icons.width ASC
+            "#,
+        )?;
+        let icons: Vec<(String, Vec<u8>)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            // Remove erroneous rows
+            .filter_map(|icon| icon.ok())
+            .collect();
+        // Larger icons come last, so they replace the smaller ones
+        cache.store_favicons(icons)
+    }
+
+    /// Returns the bytes of the largest favicon Firefox has cached for the
+    /// page at the provided url, or None when it has none (or the profile has
+    /// no favicons.sqlite). Like places.sqlite, the database is read from a
+    /// copy, since Firefox keeps it locked while it's running.
+    pub fn favicon_for(&self, url: &str) -> Result<Option<Vec<u8>>> {
+        if !self.favicons_path().exists() {
+            return Ok(None);
+        }
+        replicate(&self.favicons_path(), &self.favicons_replica_path())?;
+        let conn = Connection::open(self.favicons_replica_path())?;
+        let bytes = conn
+            .query_row(
+                r#"
+                    SELECT moz_icons.data
+                    FROM moz_icons_to_pages
+                    JOIN moz_pages_w_icons ON moz_pages_w_icons.id = moz_icons_to_pages.page_id
+                    JOIN moz_icons ON moz_icons.id = moz_icons_to_pages.icon_id
+                    WHERE moz_pages_w_icons.page_url = ?1 AND moz_icons.data IS NOT NULL
+                    ORDER BY moz_icons.width DESC
+                    LIMIT 1
+                "#,
+                [url],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(bytes)
+    }
+
     fn bookmarks_path(&self) -> PathBuf {
         // Firefox stores bookmarks in places.sqlite, but also maintains a JSON backup
         self.profile_dir
@@ -138,6 +616,23 @@ impl Browser {
         self.profile_dir.join("places.sqlite")
     }
 
+    /// The name of the profile's directory (e.g. "abcd1234.default-release"),
+    /// recorded on every Link imported from it.
+    fn profile_name(&self) -> Option<String> {
+        self.profile_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+    }
+
+    fn favicons_path(&self) -> PathBuf {
+        self.profile_dir.join("favicons.sqlite")
+    }
+
+    fn favicons_replica_path(&self) -> PathBuf {
+        self.favicons_path()
+            .with_file_name("favicons.linkcache.sqlite")
+    }
+
     fn places_replica_path(&self) -> PathBuf {
         self.places_path().with_file_name("places.linkcache.sqlite")
     }
@@ -147,10 +642,104 @@ impl Browser {
     ///
     pub fn default_profile_dir() -> Result<PathBuf> {
         let parent_dir = Self::default_profile_parent_dir()?;
-        let profile_dir = Self::find_default_release_dir(parent_dir)?;
+        let profile_dir = Self::find_profile_dir(parent_dir)?;
         Ok(profile_dir)
     }
 
+    /// Given the top-level Firefox Profiles parent directory, this function
+    /// finds the profile Firefox itself would open. The install-specific
+    /// default (from installs.ini, or the Install sections of profiles.ini,
+    /// which Firefox has used since dedicated profiles were introduced) wins
+    /// over the Profile section flagged Default=1, which older versions use.
+    /// When profiles.ini names no existing profile, falls back to the
+    /// .default-release directory.
+    ///
+    pub fn find_profile_dir(parent_dir: PathBuf) -> Result<PathBuf> {
+        match Self::profile_dir_from_ini(&parent_dir) {
+            Some(profile_dir) => Ok(profile_dir),
+            None => Self::find_default_release_dir(parent_dir),
+        }
+    }
+
+    /// Returns the directory of every Firefox profile of the current user.
+    /// See profile_dirs_in.
+    pub fn all_profiles() -> Result<Vec<PathBuf>> {
+        Self::all_profiles_for(FirefoxFlavor::Firefox)
+    }
+
+    /// Returns the directory of every profile of the provided Firefox flavor
+    /// of the current user. See profile_dirs_in.
+    pub fn all_profiles_for(flavor: FirefoxFlavor) -> Result<Vec<PathBuf>> {
+        let parent_dir = Self::default_profile_parent_dir_for(flavor)?;
+        Self::profile_dirs_in(&parent_dir)
+    }
+
+    /// Returns the directory of every Profile section of the profiles.ini
+    /// for the provided Profiles parent directory, in the order they're
+    /// listed. Relative paths (IsRelative=1, the default) are resolved
+    /// against the directory holding profiles.ini. Profiles whose directory
+    /// no longer exists are left out.
+    ///
+    pub fn profile_dirs_in(parent_dir: &Path) -> Result<Vec<PathBuf>> {
+        let ini_dir = profiles_ini_dir(parent_dir).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Could not find profiles.ini for {:?}", parent_dir),
+            )
+        })?;
+        let profile_dirs = read_ini(&ini_dir.join("profiles.ini"))
+            .iter()
+            .filter(|(name, _)| name.starts_with("Profile"))
+            .filter_map(|(_, keys)| {
+                keys.get("Path")
+                    .map(|path| resolve_profile_path(ini_dir, path, is_relative(keys)))
+            })
+            .filter(|profile_dir| profile_dir.is_dir())
+            .collect();
+        Ok(profile_dirs)
+    }
+
+    /// Returns the active profile named by profiles.ini (and installs.ini),
+    /// if it exists. An install's Default is resolved using the IsRelative
+    /// flag of the Profile section with the same Path, since the Install
+    /// sections don't have one of their own.
+    fn profile_dir_from_ini(parent_dir: &Path) -> Option<PathBuf> {
+        let ini_dir = profiles_ini_dir(parent_dir)?;
+        let installs = read_ini(&ini_dir.join("installs.ini"));
+        let profiles = read_ini(&ini_dir.join("profiles.ini"));
+        let resolve = |path: &str, relative: bool| resolve_profile_path(ini_dir, path, relative);
+        let install_relative = |path: &str| {
+            profiles
+                .iter()
+                .filter(|(name, _)| name.starts_with("Profile"))
+                .find(|(_, keys)| keys.get("Path").is_some_and(|p| p == path))
+                .map(|(_, keys)| is_relative(keys))
+                .unwrap_or_else(|| !Path::new(path).is_absolute())
+        };
+
+        let install_defaults = installs
+            .iter()
+            .chain(
+                profiles
+                    .iter()
+                    .filter(|(name, _)| name.starts_with("Install")),
+            )
+            .filter_map(|(_, keys)| keys.get("Default"))
+            .map(|path| resolve(path, install_relative(path)));
+        let flagged_defaults = profiles
+            .iter()
+            .filter(|(name, keys)| {
+                name.starts_with("Profile") && keys.get("Default").is_some_and(|d| d == "1")
+            })
+            .filter_map(|(_, keys)| {
+                keys.get("Path")
+                    .map(|path| resolve(path, is_relative(keys)))
+            });
+        install_defaults
+            .chain(flagged_defaults)
+            .find(|profile_dir| profile_dir.is_dir())
+    }
+
     /// Given the top-level Firefox Profiles parent directory, this function finds the
     /// subdirectory which ends with .default-release, which is the convention Firefox
     /// uses to indicate the default/first-created profile.
@@ -182,6 +771,13 @@ impl Browser {
     /// which will be the current user's default Firefox profile.
     ///
     pub fn default_profile_parent_dir() -> Result<PathBuf> {
+        Self::default_profile_parent_dir_for(FirefoxFlavor::Firefox)
+    }
+
+    /// Returns the OS-aware parent directory for the profiles of the provided
+    /// Firefox flavor.
+    ///
+    pub fn default_profile_parent_dir_for(flavor: FirefoxFlavor) -> Result<PathBuf> {
         let home_dir = dirs::home_dir().ok_or_else(|| {
             std::io::Error::new(
                 std::io::ErrorKind::NotFound,
@@ -190,25 +786,207 @@ impl Browser {
         })?;
 
         let os = std::env::consts::OS;
-        let profile_parent_dir = match os {
-            "macos" => home_dir.join("Library/Application Support/Firefox/Profiles"),
-            "linux" => home_dir.join(".mozilla/firefox"),
-            "windows" => home_dir.join("AppData/Roaming/Mozilla/Firefox/Profiles"),
-            unsupported => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Unsupported,
-                    format!("Unsupported operating system: {}", unsupported),
-                )
-                .into());
-            }
-        };
-        Ok(profile_parent_dir)
+        flavor.profiles_dir(os, &home_dir).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("Unsupported operating system: {}", os),
+            )
+            .into()
+        })
     }
 }
 
+impl BrowserSource for Browser {
+    /// The profile's bookmarks (see cache_bookmarks).
+    fn links(&self) -> Result<Vec<Link>> {
+        Ok(self.bookmarks()?.0)
+    }
+
+    fn source_name(&self) -> &'static str {
+        self.flavor.source()
+    }
+}
+
+/// Returns the directory holding profiles.ini for the provided Profiles
+/// parent directory. The ini files sit beside the profiles on Linux, and
+/// above the Profiles directory on macOS and Windows.
+fn profiles_ini_dir(parent_dir: &Path) -> Option<&Path> {
+    [Some(parent_dir), parent_dir.parent()]
+        .into_iter()
+        .flatten()
+        .find(|dir| dir.join("profiles.ini").is_file())
+}
+
+/// Returns whether the Path of a Profile section of profiles.ini is relative,
+/// which it is unless the section says IsRelative=0.
+fn is_relative(keys: &HashMap<String, String>) -> bool {
+    keys.get("IsRelative").is_none_or(|r| r == "1")
+}
+
+/// Resolves a profile's Path from profiles.ini, which is relative to the
+/// directory holding profiles.ini unless the profile says otherwise.
+fn resolve_profile_path(ini_dir: &Path, path: &str, relative: bool) -> PathBuf {
+    if relative {
+        ini_dir.join(path)
+    } else {
+        PathBuf::from(path)
+    }
+}
+
+/// Reads the sections of an ini file (e.g. profiles.ini) in the order they
+/// appear, each with its keys and values. A missing or unreadable file has
+/// no sections.
+fn read_ini(path: &Path) -> Vec<(String, HashMap<String, String>)> {
+    let mut sections: Vec<(String, HashMap<String, String>)> = vec![];
+    let contents = fs::read_to_string(path).unwrap_or_default();
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push((name.to_string(), HashMap::new()));
+        } else if let (Some((key, value)), Some((_, keys))) =
+            (line.split_once('='), sections.last_mut())
+        {
+            keys.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    sections
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::replica::{sidecar, WAL_SUFFIX};
+    use crate::testutils::build_firefox_fixture;
+    use filetime::FileTime;
+
+    #[test]
+    fn test_find_profile_dir_prefers_install_default() -> Result<()> {
+        let parent_dir = PathBuf::from("test_data/FirefoxProfilesIni/modern/Profiles");
+        let dir = Browser::find_profile_dir(parent_dir)?;
+        // Not Profile1, which is still flagged Default=1 from before the
+        // install-specific default was set
+        assert_eq!(
+            dir,
+            PathBuf::from("test_data/FirefoxProfilesIni/modern/Profiles/q1w2e3r4.default-release")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_profile_dir_legacy_default_flag() -> Result<()> {
+        let parent_dir = PathBuf::from("test_data/FirefoxProfilesIni/legacy/Profiles");
+        let dir = Browser::find_profile_dir(parent_dir)?;
+        assert_eq!(
+            dir,
+            PathBuf::from("test_data/FirefoxProfilesIni/legacy/Profiles/x9y8z7w6.default")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_profile_dir_respects_is_relative() -> Result<()> {
+        let firefox_dir = tempfile::tempdir()?;
+        let elsewhere = tempfile::tempdir()?;
+        std::fs::create_dir(firefox_dir.path().join("a1b2c3d4.default-release"))?;
+        let write_ini = |default: &str, is_relative: u8| {
+            std::fs::write(
+                firefox_dir.path().join("profiles.ini"),
+                format!(
+                    "[Profile0]\nName=default-release\nIsRelative={is_relative}\nPath={default}\n\n\
+                     [Install308046B0AF4A39CB]\nDefault={default}\nLocked=1\n"
+                ),
+            )
+        };
+
+        write_ini("a1b2c3d4.default-release", 1)?;
+        assert_eq!(
+            Browser::find_profile_dir(firefox_dir.path().to_path_buf())?,
+            firefox_dir.path().join("a1b2c3d4.default-release")
+        );
+
+        // A profile outside the Firefox directory is used as it is
+        write_ini(&elsewhere.path().display().to_string(), 0)?;
+        assert_eq!(
+            Browser::find_profile_dir(firefox_dir.path().to_path_buf())?,
+            elsewhere.path()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_profile_dirs_in() -> Result<()> {
+        let parent_dir = PathBuf::from("test_data/FirefoxProfilesIni/legacy/Profiles");
+        assert_eq!(
+            Browser::profile_dirs_in(&parent_dir)?,
+            vec![
+                parent_dir.join("x9y8z7w6.default"),
+                parent_dir.join("work.profile")
+            ]
+        );
+
+        // Absolute paths are used as they are, and missing profiles skipped
+        let firefox_dir = tempfile::tempdir()?;
+        let elsewhere = tempfile::tempdir()?;
+        std::fs::create_dir(firefox_dir.path().join("a1b2c3d4.default-release"))?;
+        std::fs::write(
+            firefox_dir.path().join("profiles.ini"),
+            format!(
+                "[Profile0]\nName=default-release\nIsRelative=1\nPath=a1b2c3d4.default-release\n\n\
+                 [Profile1]\nName=external\nIsRelative=0\nPath={}\n\n\
+                 [Profile2]\nName=removed\nPath=e5f6g7h8.removed\n\n\
+                 [General]\nVersion=2\n",
+                elsewhere.path().display()
+            ),
+        )?;
+        assert_eq!(
+            Browser::profile_dirs_in(firefox_dir.path())?,
+            vec![
+                firefox_dir.path().join("a1b2c3d4.default-release"),
+                elsewhere.path().to_path_buf()
+            ]
+        );
+        assert!(Browser::profile_dirs_in(elsewhere.path()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_all_profiles() -> Result<()> {
+        let firefox_dir = tempfile::tempdir()?;
+        for profile in ["a1b2c3d4.default-release", "e5f6g7h8.work"] {
+            let profile_dir = firefox_dir.path().join(profile);
+            std::fs::create_dir(&profile_dir)?;
+            build_firefox_fixture(&profile_dir.join("places.sqlite"))?;
+        }
+        std::fs::write(
+            firefox_dir.path().join("profiles.ini"),
+            "[Profile0]\nPath=a1b2c3d4.default-release\n\n[Profile1]\nPath=e5f6g7h8.work\n",
+        )?;
+        let browser =
+            Browser::from_profile_dir(firefox_dir.path().join("a1b2c3d4.default-release"));
+
+        let mut cache = crate::testutils::create_test_cache();
+        let stats = browser.cache_all_profiles(&mut cache)?;
+        let single = browser.cache_bookmarks(&mut crate::testutils::create_test_cache())?;
+        assert_eq!(stats.imported, 2 * single.imported);
+        // Both profiles share the fixture's urls, so the last one wins
+        let link = cache
+            .get_by_url("https://doc.rust-lang.org/book/")?
+            .unwrap();
+        assert_eq!(link.profile, Some("e5f6g7h8.work".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_profile_dir_without_ini() -> Result<()> {
+        let dir = Browser::find_profile_dir(PathBuf::from("test_data/FirefoxProfileDir"))?;
+        assert_eq!(
+            "5abcyz0s.default-release",
+            dir.file_name().expect("Directory should have a name"),
+        );
+        Ok(())
+    }
 
     #[test]
     fn test_find_default_release_dir() {
@@ -221,6 +999,390 @@ mod tests {
         assert!(dir.exists());
     }
 
+    #[test]
+    fn test_all_bookmarks_from_places() -> Result<()> {
+        let profile_dir = tempfile::tempdir()?;
+        build_firefox_fixture(&profile_dir.path().join("places.sqlite"))?;
+        let browser = Browser::from_profile_dir(profile_dir.path().to_path_buf());
+        browser.create_places_replica()?;
+
+        let links = browser.all_bookmarks()?;
+        let titles: Vec<&str> = links.iter().map(|l| l.title.as_str()).collect();
+        // The tag entry for the Rust Book isn't a second bookmark
+        assert_eq!(
+            titles,
+            vec![
+                "The Rust Book",
+                "SQLite FTS5 Extension",
+                "https://example.com/untitled",
+                "Recipes"
+            ]
+        );
+        assert_eq!(links[0].url, "https://doc.rust-lang.org/book/");
+        assert_eq!(links[0].timestamp.timestamp(), 1_700_000_100);
+        let subtitles: Vec<Option<&str>> = links.iter().map(|l| l.subtitle.as_deref()).collect();
+        assert_eq!(
+            subtitles,
+            vec![
+                Some("Bookmarks Toolbar / Dev"),
+                Some("Bookmarks Menu"),
+                Some("Other Bookmarks"),
+                Some("Mobile Bookmarks")
+            ]
+        );
+        assert!(links.iter().all(|l| l.source == Some(SOURCE.to_string())));
+        let profile = profile_dir.path().file_name().unwrap().to_string_lossy();
+        assert!(links
+            .iter()
+            .all(|l| l.profile.as_deref() == Some(&*profile)));
+
+        let mut cache = crate::testutils::create_test_cache();
+        browser.cache_bookmarks(&mut cache)?;
+        assert_eq!(cache.search("Rust Book")?.len(), 1);
+        // The folder path is searchable
+        let results = cache.search("Toolbar")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].url, "https://doc.rust-lang.org/book/");
+        Ok(())
+    }
+
+    #[test]
+    fn test_bookmark_descriptions_are_searchable() -> Result<()> {
+        let profile_dir = tempfile::tempdir()?;
+        build_firefox_fixture(&profile_dir.path().join("places.sqlite"))?;
+        let browser = Browser::from_profile_dir(profile_dir.path().to_path_buf());
+
+        let mut cache = crate::testutils::create_test_cache();
+        browser.cache_bookmarks(&mut cache)?;
+        let results = cache.search("virtual table")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].title, "SQLite FTS5 Extension");
+        assert_eq!(
+            results[0].description,
+            Some("Virtual table module for full-text search".to_string())
+        );
+        let rust_book = cache
+            .get_by_url("https://doc.rust-lang.org/book/")?
+            .unwrap();
+        assert_eq!(rust_book.description, None);
+
+        // Newer versions of Firefox have no annotation tables
+        let profile_dir = tempfile::tempdir()?;
+        let places_path = profile_dir.path().join("places.sqlite");
+        build_firefox_fixture(&places_path)?;
+        Connection::open(&places_path)?.execute_batch("DROP TABLE moz_items_annos;")?;
+        let browser = Browser::from_profile_dir(profile_dir.path().to_path_buf());
+        browser.create_places_replica()?;
+        assert!(browser
+            .all_bookmarks()?
+            .iter()
+            .all(|link| link.description.is_none()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_history_links_from_places() -> Result<()> {
+        let profile_dir = tempfile::tempdir()?;
+        build_firefox_fixture(&profile_dir.path().join("places.sqlite"))?;
+        let browser = Browser::from_profile_dir(profile_dir.path().to_path_buf());
+        browser.create_places_replica()?;
+
+        let links = browser.history_links()?;
+        let urls: Vec<&str> = links.iter().map(|l| l.url.as_str()).collect();
+        // Newest first. The prefetched page was never visited
+        assert_eq!(
+            urls,
+            vec![
+                "https://news.ycombinator.com/",
+                "https://doc.rust-lang.org/book/",
+                "https://www.sqlite.org/fts5.html",
+                "https://example.com/untitled",
+                "https://m.example.com/recipes"
+            ]
+        );
+        let hn = &links[0];
+        assert_eq!(hn.title, "Hacker News");
+        assert_eq!(hn.kind, Some(LinkKind::History));
+        assert_eq!(hn.timestamp.timestamp(), 1_710_576_000);
+        assert_eq!(hn.visit_count, Some(40));
+        assert_eq!(hn.typed_count, Some(1));
+        assert_eq!(links[4].typed_count, Some(0));
+        assert_eq!(links[3].title, "https://example.com/untitled");
+
+        let mut cache = crate::testutils::create_test_cache();
+        assert_eq!(
+            browser.cache_history(&mut cache)?,
+            ImportStats {
+                imported: 5,
+                skipped: 0
+            }
+        );
+        assert_eq!(cache.search("Hacker News")?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unvisited_and_unreadable_history_rows() -> Result<()> {
+        let profile_dir = tempfile::tempdir()?;
+        let places_path = profile_dir.path().join("places.sqlite");
+        build_firefox_fixture(&places_path)?;
+        Connection::open(&places_path)?.execute_batch(
+            "INSERT INTO moz_places (id, url, title, visit_count, typed, last_visit_date, guid) VALUES
+                (20, 'https://example.com/typed-only', 'Typed Only', 0, 1, 1710000000000000, 'place-typed'),
+                (21, 'https://example.com/broken', 'Broken', 1, 0, 'not a timestamp', 'place-broken');",
+        )?;
+        let browser = Browser::from_profile_dir(profile_dir.path().to_path_buf());
+
+        let mut cache = crate::testutils::create_test_cache();
+        assert_eq!(
+            browser.cache_history(&mut cache)?,
+            ImportStats {
+                imported: 5,
+                skipped: 1
+            }
+        );
+        assert!(cache.search("Typed Only")?.is_empty());
+
+        let links = browser.with_skip_unvisited(false).history_links()?;
+        assert_eq!(links.len(), 6);
+        assert!(links
+            .iter()
+            .any(|l| l.url == "https://example.com/typed-only"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_favicons() -> Result<()> {
+        let profile_dir = tempfile::tempdir()?;
+        let browser = Browser::from_profile_dir(profile_dir.path().to_path_buf());
+        let mut cache = crate::testutils::create_test_cache();
+        assert_eq!(browser.cache_favicons(&mut cache)?, 0);
+
+        let conn = Connection::open(profile_dir.path().join("favicons.sqlite"))?;
+        conn.execute_batch(
+            "CREATE TABLE moz_icons (
+                id INTEGER PRIMARY KEY,
+                icon_url TEXT NOT NULL,
+                width INTEGER NOT NULL DEFAULT 0,
+                data BLOB
+            );
+            CREATE TABLE moz_pages_w_icons (id INTEGER PRIMARY KEY, page_url TEXT NOT NULL);
+            CREATE TABLE moz_icons_to_pages (
+                page_id INTEGER NOT NULL,
+                icon_id INTEGER NOT NULL,
+                PRIMARY KEY (page_id, icon_id)
+            );
+            INSERT INTO moz_icons (id, icon_url, width, data) VALUES
+                (1, 'https://news.ycombinator.com/favicon.ico', 32, X'0000010001'),
+                (2, 'https://news.ycombinator.com/y18.svg', 16, '<svg/>'),
+                (3, 'https://example.com/missing.png', 16, NULL);
+            INSERT INTO moz_pages_w_icons (id, page_url) VALUES
+                (1, 'https://news.ycombinator.com/'),
+                (2, 'https://example.com/');
+            INSERT INTO moz_icons_to_pages (page_id, icon_id) VALUES (1, 1), (1, 2), (2, 3);",
+        )?;
+
+        assert_eq!(browser.cache_favicons(&mut cache)?, 1);
+        assert_eq!(
+            cache.favicon_for("https://news.ycombinator.com/item?id=1")?,
+            Some(vec![0x00, 0x00, 0x01, 0x00, 0x01])
+        );
+        assert_eq!(cache.favicon_for("https://example.com/")?, None);
+
+        // The largest icon of the page itself is read straight from Firefox
+        assert_eq!(
+            browser.favicon_for("https://news.ycombinator.com/")?,
+            Some(vec![0x00, 0x00, 0x01, 0x00, 0x01])
+        );
+        assert_eq!(browser.favicon_for("https://example.com/")?, None);
+        assert_eq!(browser.favicon_for("https://crates.io/")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unreadable_bookmark_rows_are_skipped() -> Result<()> {
+        let profile_dir = tempfile::tempdir()?;
+        let places_path = profile_dir.path().join("places.sqlite");
+        build_firefox_fixture(&places_path)?;
+        Connection::open(&places_path)?.execute(
+            "INSERT INTO moz_bookmarks (id, type, fk, parent, position, title, dateAdded, lastModified, guid)
+             VALUES (15, 1, 2, 2, 5, 'Broken', 'not a timestamp', 0, 'broken______')",
+            [],
+        )?;
+        let browser = Browser::from_profile_dir(profile_dir.path().to_path_buf());
+
+        let mut cache = crate::testutils::create_test_cache();
+        let stats = browser.cache_bookmarks(&mut cache)?;
+        assert_eq!(
+            stats,
+            ImportStats {
+                imported: 4,
+                skipped: 1
+            }
+        );
+        assert!(cache.search("Broken")?.is_empty());
+        assert_eq!(cache.search("Rust Book")?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_places_replica_is_only_copied_when_stale() -> Result<()> {
+        let profile_dir = tempfile::tempdir()?;
+        let places = profile_dir.path().join("places.sqlite");
+        build_firefox_fixture(&places)?;
+        let browser = Browser::from_profile_dir(profile_dir.path().to_path_buf());
+
+        assert!(browser.create_places_replica()?);
+        assert!(!browser.create_places_replica()?);
+
+        // Firefox writing to places.sqlite after the copy makes it stale
+        let copied = FileTime::from_unix_time(FileTime::now().unix_seconds() - 120, 0);
+        filetime::set_file_mtime(browser.places_replica_path(), copied)?;
+        assert!(browser.create_places_replica()?);
+        assert!(!browser.create_places_replica()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_browser_sources() -> Result<()> {
+        let profile_dir = tempfile::tempdir()?;
+        build_firefox_fixture(&profile_dir.path().join("places.sqlite"))?;
+        let sources: Vec<Box<dyn BrowserSource>> = vec![
+            Box::new(Browser::from_profile_dir(profile_dir.path().to_path_buf())),
+            Box::new(crate::arc::Browser::new().with_profile_dir(PathBuf::from("./test_data"))),
+        ];
+
+        let mut cache = crate::testutils::create_test_cache();
+        for source in &sources {
+            let added = source.cache_links(&mut cache)?;
+            assert_eq!(added as u64, cache.count_by_source(source.source_name())?);
+        }
+        assert_eq!(cache.count_by_source(SOURCE)?, 4);
+        assert_eq!(cache.count_by_source(crate::arc::SOURCE)?, 9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_places_replica_includes_the_write_ahead_log() -> Result<()> {
+        let profile_dir = tempfile::tempdir()?;
+        let places = profile_dir.path().join("places.sqlite");
+        build_firefox_fixture(&places)?;
+
+        // Like a running Firefox, hold places.sqlite open with a bookmark
+        // which is still only in its write-ahead log
+        let firefox = Connection::open(&places)?;
+        firefox.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        firefox.pragma_update(None, "wal_autocheckpoint", 0)?;
+        firefox.execute_batch(
+            "INSERT INTO moz_places (id, url, title, visit_count, guid) VALUES
+                (7, 'https://example.com/wal', 'Written Recently', 1, 'place-wal');
+            INSERT INTO moz_bookmarks (id, type, fk, parent, position, title, dateAdded, lastModified, guid) VALUES
+                (15, 1, 7, 7, 1, 'Written Recently', 1700000600000000, 1700000600000000, 'bookmark-wal');",
+        )?;
+        assert!(sidecar(&places, WAL_SUFFIX).exists());
+
+        let browser = Browser::from_profile_dir(profile_dir.path().to_path_buf());
+        assert!(browser.create_places_replica()?);
+        let links = browser.all_bookmarks()?;
+        assert!(links.iter().any(|l| l.title == "Written Recently"));
+        drop(firefox);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bookmark_folders_filter() -> Result<()> {
+        let profile_dir = tempfile::tempdir()?;
+        build_firefox_fixture(&profile_dir.path().join("places.sqlite"))?;
+        let browser = Browser::from_profile_dir(profile_dir.path().to_path_buf());
+        browser.create_places_replica()?;
+
+        // Includes bookmarks in subfolders, e.g. Bookmarks Toolbar / Dev
+        let links = browser
+            .with_bookmark_folders(&["Toolbar"])
+            .all_bookmarks()?;
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].title, "The Rust Book");
+
+        let browser = Browser::from_profile_dir(profile_dir.path().to_path_buf())
+            .with_bookmark_folders(&["Bookmarks Menu", "other bookmarks"]);
+        let titles: Vec<String> = browser
+            .all_bookmarks()?
+            .into_iter()
+            .map(|l| l.title)
+            .collect();
+        assert_eq!(
+            titles,
+            vec!["SQLite FTS5 Extension", "https://example.com/untitled"]
+        );
+
+        let browser = Browser::from_profile_dir(profile_dir.path().to_path_buf())
+            .with_bookmark_folders(&["Dev"]);
+        assert_eq!(browser.all_bookmarks()?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_flavor_profiles_dirs() {
+        let home = Path::new("/home/me");
+        let cases = [
+            (
+                FirefoxFlavor::Firefox,
+                "macos",
+                "Library/Application Support/Firefox/Profiles",
+            ),
+            (FirefoxFlavor::Firefox, "linux", ".mozilla/firefox"),
+            (
+                FirefoxFlavor::Firefox,
+                "windows",
+                "AppData/Roaming/Mozilla/Firefox/Profiles",
+            ),
+            (
+                FirefoxFlavor::LibreWolf,
+                "macos",
+                "Library/Application Support/librewolf/Profiles",
+            ),
+            (FirefoxFlavor::LibreWolf, "linux", ".librewolf"),
+            (
+                FirefoxFlavor::LibreWolf,
+                "windows",
+                "AppData/Roaming/librewolf/Profiles",
+            ),
+            (
+                FirefoxFlavor::Waterfox,
+                "macos",
+                "Library/Application Support/Waterfox/Profiles",
+            ),
+            (FirefoxFlavor::Waterfox, "linux", ".waterfox"),
+            (
+                FirefoxFlavor::Waterfox,
+                "windows",
+                "AppData/Roaming/Waterfox/Profiles",
+            ),
+            (
+                FirefoxFlavor::Zen,
+                "macos",
+                "Library/Application Support/zen/Profiles",
+            ),
+            (FirefoxFlavor::Zen, "linux", ".zen"),
+            (
+                FirefoxFlavor::Zen,
+                "windows",
+                "AppData/Roaming/zen/Profiles",
+            ),
+        ];
+        for (flavor, os, expected) in cases {
+            assert_eq!(
+                flavor.profiles_dir(os, home),
+                Some(home.join(expected)),
+                "{:?} on {}",
+                flavor,
+                os
+            );
+        }
+        assert_eq!(FirefoxFlavor::Zen.profiles_dir("freebsd", home), None);
+        assert_eq!(FirefoxFlavor::default(), FirefoxFlavor::Firefox);
+    }
+
     #[test]
     #[ignore = "CI environments don't have a Firefox home directory"]
     fn test_default_profile_dir() {
@@ -0,0 +1,261 @@
+use regex::Regex;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+use crate::{Cache, Link, LinkKind};
+
+/// The file formats which can be imported into a Cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// A JSON array of serialized Link objects
+    Json,
+    /// A Netscape-style bookmarks.html file, as exported by most browsers
+    Html,
+    /// An OPML outline, where each outline element with a URL is a link
+    Opml,
+    /// A Markdown document, where each [title](url) is a link
+    Markdown,
+}
+
+impl Format {
+    /// Returns the source recorded on links imported in this format.
+    pub fn source(&self) -> String {
+        format!("import:{}", self)
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Format::Json => write!(f, "json"),
+            Format::Html => write!(f, "html"),
+            Format::Opml => write!(f, "opml"),
+            Format::Markdown => write!(f, "markdown"),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(Format::Json),
+            "html" => Ok(Format::Html),
+            "opml" => Ok(Format::Opml),
+            "markdown" | "md" => Ok(Format::Markdown),
+            other => Err(Error::Parse(format!(
+                "Unsupported import format: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Counts of what happened to the records read by a browser import.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ImportStats {
+    /// Links which were added to the Cache
+    pub imported: usize,
+    /// Rows which couldn't be read (e.g. a column holding an unexpected
+    /// type) and so were left out
+    pub skipped: usize,
+}
+
+/// Parses the file at the provided path and adds every link it contains to
+/// the cache in a single transaction. Either every link is imported or, on
+/// the first failure, none are. Links with a blocked url scheme (e.g.
+/// javascript: bookmarklets) are left out. Returns the number of links
+/// imported.
+pub fn import_file<P: AsRef<Path>>(cache: &mut Cache, format: Format, path: P) -> Result<usize> {
+    let contents = std::fs::read_to_string(path)?;
+    let links = parse(format, &contents)?;
+    cache.add_batch(links)
+}
+
+/// Parses the contents of a file in the provided format into Links, each
+/// tagged with the format's source. Links are bookmarks unless the (JSON)
+/// file says otherwise, and are given a deterministic guid unless it gives
+/// them one.
+pub fn parse(format: Format, contents: &str) -> Result<Vec<Link>> {
+    let links = match format {
+        Format::Json => serde_json::from_str::<Vec<Link>>(contents)?,
+        Format::Html => parse_html(contents),
+        Format::Opml => parse_opml(contents),
+        Format::Markdown => parse_markdown(contents),
+    };
+    Ok(links
+        .into_iter()
+        .map(|link| Link {
+            guid: link
+                .guid
+                .or_else(|| Some(Link::deterministic_guid(&format.source(), &link.url))),
+            source: Some(format.source()),
+            kind: link.kind.or(Some(LinkKind::Bookmark)),
+            ..link
+        })
+        .collect())
+}
+
+/// Parses a Netscape bookmarks file. Each <H3> opens a folder whose name is
+/// part of the subtitle of every bookmark until its <DL> list closes.
+fn parse_html(contents: &str) -> Vec<Link> {
+    let re = Regex::new(
+        r#"(?is)<H3[^>]*>(?P<folder>.*?)</H3>|<A\s(?P<attrs>[^>]*)>(?P<title>.*?)</A>|(?P<close></DL>)"#,
+    )
+    .expect("Invalid bookmark HTML regex");
+
+    let mut links = vec![];
+    let mut folders: Vec<String> = vec![];
+    for caps in re.captures_iter(contents) {
+        if let Some(folder) = caps.name("folder") {
+            folders.push(unescape(folder.as_str()));
+        } else if caps.name("close").is_some() {
+            folders.pop();
+        } else if let Some(attrs) = caps.name("attrs") {
+            let attrs = attributes(attrs.as_str());
+            let Some(url) = attribute(&attrs, "href") else {
+                continue;
+            };
+            let title = unescape(caps.name("title").map(|t| t.as_str()).unwrap_or_default());
+            let mut link = Link::new(url, title);
+            if let Some(added) = attribute(&attrs, "add_date").and_then(|d| d.parse().ok()) {
+                link = link.with_timestamp_seconds(added);
+            }
+            if !folders.is_empty() {
+                link = link.with_subtitle(folders.join(" / "));
+            }
+            links.push(link);
+        }
+    }
+    links
+}
+
+/// Parses an OPML outline. Outline elements without a URL are ignored.
+fn parse_opml(contents: &str) -> Vec<Link> {
+    let re = Regex::new(r#"(?is)<outline\s([^>]*)>"#).expect("Invalid OPML regex");
+    re.captures_iter(contents)
+        .filter_map(|caps| {
+            let attrs = attributes(&caps[1]);
+            let url = attribute(&attrs, "htmlurl")
+                .or_else(|| attribute(&attrs, "url"))
+                .or_else(|| attribute(&attrs, "xmlurl"))?;
+            let title = attribute(&attrs, "title")
+                .or_else(|| attribute(&attrs, "text"))
+                .unwrap_or_else(|| url.clone());
+            Some(Link::new(url, title))
+        })
+        .collect()
+}
+
+/// Parses inline [title](url) links from a Markdown document. The nearest
+/// preceding heading becomes the subtitle.
+fn parse_markdown(contents: &str) -> Vec<Link> {
+    let re =
+        Regex::new(r#"\[([^\]]*)\]\((\S+?)(?:\s+"[^"]*")?\)"#).expect("Invalid Markdown regex");
+
+    let mut links = vec![];
+    let mut heading: Option<String> = None;
+    for line in contents.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('#') {
+            heading = Some(trimmed.trim_start_matches('#').trim().to_string());
+        }
+        for caps in re.captures_iter(line) {
+            let mut link = Link::new(caps[2].to_string(), caps[1].to_string());
+            if let Some(heading) = &heading {
+                link = link.with_subtitle(heading.clone());
+            }
+            links.push(link);
+        }
+    }
+    links
+}
+
+/// Returns the name="value" attribute pairs of an HTML/XML tag, with the
+/// names lowercased.
+fn attributes(tag: &str) -> Vec<(String, String)> {
+    let re = Regex::new(r#"([\w:-]+)\s*=\s*"([^"]*)""#).expect("Invalid attribute regex");
+    re.captures_iter(tag)
+        .map(|caps| (caps[1].to_lowercase(), unescape(&caps[2])))
+        .collect()
+}
+
+fn attribute(attrs: &[(String, String)], name: &str) -> Option<String> {
+    attrs
+        .iter()
+        .find(|(key, value)| key == name && !value.is_empty())
+        .map(|(_, value)| value.clone())
+}
+
+/// Decodes the handful of XML entities which show up in exported bookmarks.
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_html() {
+        let html = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<DL><p>
+    <DT><H3 ADD_DATE="1700000000">Dev</H3>
+    <DL><p>
+        <DT><A HREF="https://www.rust-lang.org/" ADD_DATE="1710489600">Rust &amp; Cargo</A>
+    </DL><p>
+    <DT><A HREF="https://example.com/">Example</A>
+</DL><p>"#;
+        let links = parse(Format::Html, html).unwrap();
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].title, "Rust & Cargo");
+        assert_eq!(links[0].subtitle, Some("Dev".to_string()));
+        assert_eq!(links[0].timestamp.timestamp(), 1710489600);
+        assert_eq!(links[0].source, Some("import:html".to_string()));
+        assert_eq!(links[1].subtitle, None);
+    }
+
+    #[test]
+    fn test_import_skips_bookmarklets() -> Result<()> {
+        let html = r#"<DL><p>
+    <DT><A HREF="javascript:void(document.body.style.background='red')">Make It Red</A>
+    <DT><A HREF="https://example.com/">Example</A>
+</DL><p>"#;
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("bookmarks.html");
+        std::fs::write(&path, html)?;
+
+        let mut cache = crate::testutils::create_test_cache();
+        assert_eq!(import_file(&mut cache, Format::Html, &path)?, 1);
+        assert!(cache.search("Make It Red")?.is_empty());
+        assert_eq!(cache.search("Example")?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_opml_and_markdown() {
+        let opml = r#"<opml><body>
+            <outline text="Blogs">
+                <outline text="Rust Blog" htmlUrl="https://blog.rust-lang.org/" />
+            </outline>
+        </body></opml>"#;
+        let links = parse(Format::Opml, opml).unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].title, "Rust Blog");
+
+        let markdown = "# Reading\n- [SQLite FTS5](https://www.sqlite.org/fts5.html)\n";
+        let links = parse(Format::Markdown, markdown).unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].url, "https://www.sqlite.org/fts5.html");
+        assert_eq!(links[0].subtitle, Some("Reading".to_string()));
+    }
+}
@@ -0,0 +1,110 @@
+use rusqlite::Connection;
+
+use crate::{Cache, Result};
+
+/// The problems found by Cache::check_integrity. A healthy cache has none.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// The problems SQLite's integrity_check found in the database file
+    pub database_errors: Vec<String>,
+    /// Why the search index failed its own integrity-check, if it did
+    pub search_index_error: Option<String>,
+    /// The links (as url and collection) which have no search index entry,
+    /// and so can't be found by searches
+    pub unindexed_links: Vec<(String, String)>,
+    /// The search index entries (as url and collection) which have no link,
+    /// and so show up in searches as nothing
+    pub orphaned_index_entries: Vec<(String, String)>,
+}
+
+impl IntegrityReport {
+    /// Returns true when no problems were found.
+    pub fn is_healthy(&self) -> bool {
+        self == &IntegrityReport::default()
+    }
+}
+
+impl Cache {
+    /// Checks the database file, the search index, and that the two agree on
+    /// which links exist, across every collection. Discrepancies between
+    /// links and the index can be repaired with reindex(). Returns an error
+    /// only when the checks themselves couldn't be run.
+    pub fn check_integrity(&self) -> Result<IntegrityReport> {
+        let database_errors = self
+            .conn
+            .prepare("PRAGMA integrity_check")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .filter(|message| !matches!(message.as_deref(), Ok("ok")))
+            .collect::<std::result::Result<_, rusqlite::Error>>()?;
+        let search_index_error = self
+            .conn
+            .execute(
+                "INSERT INTO links_fts (links_fts) VALUES ('integrity-check')",
+                [],
+            )
+            .err()
+            .map(|err| err.to_string());
+        Ok(IntegrityReport {
+            database_errors,
+            search_index_error,
+            unindexed_links: url_difference(&self.conn, "links", "links_fts")?,
+            orphaned_index_entries: url_difference(&self.conn, "links_fts", "links")?,
+        })
+    }
+}
+
+/// Returns the url and collection of every row of the first table which the
+/// second table has no row for.
+fn url_difference(conn: &Connection, table: &str, other: &str) -> Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT url, collection FROM {table}
+         EXCEPT SELECT url, collection FROM {other}
+         ORDER BY collection, url"
+    ))?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<std::result::Result<_, rusqlite::Error>>()?;
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::create_test_cache;
+    use crate::Link;
+
+    #[test]
+    fn test_check_integrity() -> Result<()> {
+        let mut cache = create_test_cache();
+        for (url, title) in [
+            ("https://www.rust-lang.org/", "Rust"),
+            ("https://crates.io/", "crates.io"),
+        ] {
+            cache.add(Link::new(url.to_string(), title.to_string()))?;
+        }
+        let report = cache.check_integrity()?;
+        assert!(report.is_healthy(), "{:?}", report);
+
+        cache.conn.execute_batch(
+            "DELETE FROM links_fts WHERE url = 'https://crates.io/';
+             INSERT INTO links_fts (url, title, collection)
+             VALUES ('https://docs.rs/', 'Docs.rs', 'default');",
+        )?;
+        let report = cache.check_integrity()?;
+        assert!(!report.is_healthy());
+        assert_eq!(report.database_errors, Vec::<String>::new());
+        assert_eq!(report.search_index_error, None);
+        assert_eq!(
+            report.unindexed_links,
+            vec![("https://crates.io/".to_string(), "default".to_string())]
+        );
+        assert_eq!(
+            report.orphaned_index_entries,
+            vec![("https://docs.rs/".to_string(), "default".to_string())]
+        );
+
+        cache.reindex()?;
+        assert!(cache.check_integrity()?.is_healthy());
+        Ok(())
+    }
+}
@@ -1,12 +1,36 @@
+#[cfg(feature = "alfred")]
+mod alfred;
+mod builder;
 mod cache;
 mod ddl;
+mod detect;
+mod dump;
 mod error;
+mod favicon;
+mod integrity;
 mod link;
+mod merge;
+mod meta;
+mod query;
+mod replica;
+mod search;
+mod source;
+#[cfg(test)]
+mod testutils;
 
+pub use builder::{default_data_dir, CacheBuilder, CorruptionPolicy};
 pub use cache::Cache;
+pub use detect::{detected_browsers, detected_browsers_in, DetectedBrowser};
 pub use error::{Error, Result};
-pub use link::Link;
+pub use integrity::IntegrityReport;
+pub use link::{normalize_url, Link, LinkKind, DEFAULT_BLOCKED_SCHEMES, MAX_DISPLAY_URL_LEN};
+pub use search::{
+    Field, RecencyBucket, RecencyField, SearchOrder, SearchResult, DEFAULT_SEARCH_LIMIT,
+};
+pub use source::{cache_sources, BrowserSource};
 
 pub mod arc;
 pub mod chrome;
 pub mod firefox;
+pub mod import;
+pub mod safari;
@@ -1,10 +1,63 @@
 use chrono::{DateTime, Utc};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::path::PathBuf;
+
+use crate::error::{Error, Result};
+
+/// Url schemes which aren't cached unless the Cache is built with other
+/// blocked schemes: javascript: bookmarklets and inline data: urls aren't
+/// pages to navigate to, and running them from a search result is risky.
+pub const DEFAULT_BLOCKED_SCHEMES: &[&str] = &["javascript", "data"];
+
+/// What a link represents in the browser (or file) it was imported from.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    /// A page the user deliberately saved (including pinned Arc tabs)
+    Bookmark,
+    /// A page which merely shows up in the browser's history
+    History,
+}
+
+impl LinkKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            LinkKind::Bookmark => "bookmark",
+            LinkKind::History => "history",
+        }
+    }
+}
+
+impl ToSql for LinkKind {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for LinkKind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "bookmark" => Ok(LinkKind::Bookmark),
+            "history" => Ok(LinkKind::History),
+            other => Err(FromSqlError::Other(
+                format!("Unknown link kind: {}", other).into(),
+            )),
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Link {
     pub url: String,
 
+    /// The identifier the source browser assigned to the link, when it has
+    /// one. Links sharing a guid are the same link even if their url changes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guid: Option<String>,
+
     pub title: String,
 
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -13,11 +66,61 @@ pub struct Link {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub author: Option<String>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub source: Option<String>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<LinkKind>,
+
+    /// The browser profile the link was imported from (the name of its
+    /// directory, e.g. "Profile 1" for Chrome).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+
+    /// The path of an image of the page, for sources which provide one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<PathBuf>,
+
+    /// The path of a copy of the site's favicon, written into the Cache's
+    /// data directory by Cache::extract_icons. It isn't stored with the link.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon_path: Option<PathBuf>,
+
+    #[serde(default = "Utc::now")]
     pub timestamp: DateTime<Utc>,
 
+    /// When the link was last opened via linkcache (see Cache::record_open),
+    /// as opposed to the timestamp, which importers set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_opened_at: Option<DateTime<Utc>>,
+
+    /// When the link stops showing up in searches, for ephemeral links such
+    /// as open tabs. None means it never expires.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+
+    /// How many times the browser recorded a visit to the link, for sources
+    /// which track it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visit_count: Option<u32>,
+
+    /// How many times the url was typed into the browser's address bar, for
+    /// sources which track it. Firefox only records whether it ever was, so
+    /// its links have 0 or 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub typed_count: Option<u32>,
+
+    /// The url the link redirected to (e.g. the page behind a short link),
+    /// for sources which record redirects. Searches match it as well as the
+    /// url. Stored links without a known redirect have their own url here.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub canonical_url: Option<String>,
+
+    /// How well the link matched a search, from 0.0 to 1.0 (the best match
+    /// in the results), or the raw relevance from Cache::search_detailed.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub score: Option<f32>,
 }
@@ -44,8 +147,497 @@ impl Link {
         self
     }
 
+    pub fn with_source(mut self, source: String) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    pub fn with_description(mut self, description: String) -> Self {
+        self.description = Some(description);
+        self
+    }
+
     pub fn with_author(mut self, author: String) -> Self {
         self.author = Some(author);
         self
     }
+
+    pub fn with_guid(mut self, guid: String) -> Self {
+        self.guid = Some(guid);
+        self
+    }
+
+    pub fn with_kind(mut self, kind: LinkKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    pub fn with_profile(mut self, profile: String) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    pub fn with_thumbnail(mut self, thumbnail: PathBuf) -> Self {
+        self.thumbnail = Some(thumbnail);
+        self
+    }
+
+    pub fn with_icon_path(mut self, icon_path: PathBuf) -> Self {
+        self.icon_path = Some(icon_path);
+        self
+    }
+
+    pub fn with_canonical_url(mut self, canonical_url: String) -> Self {
+        self.canonical_url = Some(canonical_url);
+        self
+    }
+
+    pub fn with_expires_at(mut self, expires_at: DateTime<Utc>) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Returns the lowercased scheme of the url (e.g. "https" or
+    /// "javascript"), or None when it doesn't start with one.
+    pub fn scheme(&self) -> Option<String> {
+        let (scheme, _) = self.url.trim_start().split_once(':')?;
+        let valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.');
+        valid.then(|| scheme.to_ascii_lowercase())
+    }
+
+    /// Returns an error when the url uses one of the provided (lowercased)
+    /// schemes, e.g. DEFAULT_BLOCKED_SCHEMES, whose links are bookmarklets
+    /// or inline data rather than pages. The Cache skips links which fail
+    /// this check against its blocked schemes (see
+    /// CacheBuilder::with_blocked_schemes).
+    pub fn validate<S: AsRef<str>>(&self, blocked_schemes: &[S]) -> Result<()> {
+        match self.scheme() {
+            Some(scheme)
+                if blocked_schemes
+                    .iter()
+                    .any(|blocked| blocked.as_ref() == scheme) =>
+            {
+                Err(Error::Parse(format!(
+                    "Links with {}: urls aren't cached",
+                    scheme
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the url tidied up for showing to a person: without its
+    /// scheme, a leading "www." or a lone trailing slash, with percent
+    /// encoding decoded (e.g. "de.wikipedia.org/wiki/München"), and cut
+    /// short with an ellipsis when it's longer than MAX_DISPLAY_URL_LEN
+    /// characters.
+    pub fn display_url(&self) -> String {
+        let url = self.url.trim();
+        let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+        let url = url.strip_prefix("www.").unwrap_or(url);
+        let url = match url.strip_suffix('/') {
+            Some(host) if !host.contains('/') => host,
+            _ => url,
+        };
+        let url = percent_decode(url);
+        if url.chars().count() <= MAX_DISPLAY_URL_LEN {
+            return url;
+        }
+        let mut truncated: String = url.chars().take(MAX_DISPLAY_URL_LEN - 1).collect();
+        truncated.push('…');
+        truncated
+    }
+
+    /// Returns true when both links point at the same page: their urls are
+    /// equal once normalized (see normalize_url), e.g. once fragments
+    /// (#section) and tracking parameters (utm_*, fbclid, etc) are removed.
+    pub fn same_page_as(&self, other: &Link) -> bool {
+        self.normalized_url() == other.normalized_url()
+    }
+
+    /// Returns a guid for a link from a source which doesn't assign its own
+    /// (e.g. Chrome's history or Arc's sidebar): the SHA-256 hex digest of
+    /// "source:url", with the url normalized (see normalize_url) so its
+    /// variants share a guid. The same inputs always give the same guid.
+    pub fn deterministic_guid(source: &str, url: &str) -> String {
+        Sha256::digest(format!("{}:{}", source, normalize_url(url)))
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Returns the url in the form the Cache stores the link under (see
+    /// normalize_url), which every variant of the url shares.
+    pub fn normalized_url(&self) -> String {
+        normalize_url(&self.url)
+    }
+
+    /// Orders links best first, for sorting a Vec<Link> (e.g. gathered from
+    /// several sources) with sort_by(Link::cmp_relevance) so the most
+    /// relevant link is at the front: higher scores come first, links without
+    /// a score come after every scored link, and ties are broken by
+    /// timestamp, newest first, then by url.
+    pub fn cmp_relevance(&self, other: &Self) -> Ordering {
+        let by_score = match (self.score, other.score) {
+            (Some(a), Some(b)) => b.total_cmp(&a),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+        by_score
+            .then_with(|| other.timestamp.cmp(&self.timestamp))
+            .then_with(|| self.url.cmp(&other.url))
+    }
+}
+
+/// The longest url (in characters, including the ellipsis) which
+/// Link::display_url returns.
+pub const MAX_DISPLAY_URL_LEN: usize = 60;
+
+/// Decodes the %XX escapes in the text. Text whose escapes don't decode to
+/// valid UTF-8 is returned as it is.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).unwrap_or_else(|_| text.to_string())
+}
+
+/// Query parameters which identify how a visitor arrived rather than what
+/// they're looking at, in addition to every utm_* parameter.
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "msclkid", "yclid", "igshid", "mc_cid", "mc_eid", "_hsenc",
+    "_hsmi", "mkt_tok",
+];
+
+fn is_tracking_param(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.starts_with("utm_") || TRACKING_PARAMS.contains(&name.as_str())
+}
+
+/// Returns the form of a url which tells whether two urls are the same
+/// page. The Cache stores each link under it, so variants of a url are
+/// stored as one link. The rules are:
+///
+/// - the fragment (#section) is removed
+/// - tracking parameters (utm_*, fbclid, etc) are removed from the query,
+///   along with the "?" when no other parameters are left
+/// - the scheme and host are lowercased
+///
+/// and, for http and https urls:
+///
+/// - http is treated as https
+/// - a leading "www." and the scheme's default port (80 for http, 443 for
+///   https) are removed from the host
+/// - trailing slashes are removed from the path
+///
+/// So "HTTP://www.Example.com:80/docs/?utm_source=x#intro" becomes
+/// "https://example.com/docs". Text which isn't an absolute url only loses
+/// its fragment. Tracking parameters are always removed; there's no option
+/// to keep them, so links which differ only by them are always one link.
+pub fn normalize_url(url: &str) -> String {
+    let url = url.trim();
+    let url = url.split_once('#').map_or(url, |(url, _)| url);
+    let (base, query) = match url.split_once('?') {
+        Some((base, query)) => (base, Some(query)),
+        None => (url, None),
+    };
+
+    let base = match base.split_once("://") {
+        Some((scheme, rest)) => {
+            let (authority, path) = match rest.find('/') {
+                Some(index) => rest.split_at(index),
+                None => (rest, ""),
+            };
+            let scheme = scheme.to_ascii_lowercase();
+            let authority = authority.to_ascii_lowercase();
+            let default_port = match scheme.as_str() {
+                "http" => Some(":80"),
+                "https" => Some(":443"),
+                _ => None,
+            };
+            if let Some(default_port) = default_port {
+                let host = authority.strip_prefix("www.").unwrap_or(&authority);
+                let host = host.strip_suffix(default_port).unwrap_or(host);
+                format!("https://{}{}", host, path.trim_end_matches('/'))
+            } else {
+                format!("{}://{}{}", scheme, authority, path)
+            }
+        }
+        None => base.to_string(),
+    };
+
+    let params: Vec<&str> = query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|param| {
+            let name = param.split_once('=').map_or(*param, |(name, _)| name);
+            !param.is_empty() && !is_tracking_param(name)
+        })
+        .collect();
+    if params.is_empty() {
+        base
+    } else {
+        format!("{}?{}", base, params.join("&"))
+    }
+}
+
+/// Returns true when the text is a single absolute URL (scheme://...), as
+/// opposed to search terms which happen to contain URL-ish fragments.
+pub(crate) fn is_absolute_url(text: &str) -> bool {
+    match text.split_once("://") {
+        Some((scheme, rest)) => {
+            !rest.is_empty()
+                && !text.chars().any(char::is_whitespace)
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+        }
+        None => false,
+    }
+}
+
+/// Returns the lowercased host of an absolute URL, without any userinfo or
+/// port.
+pub(crate) fn host_of(url: &str) -> Option<String> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+    let host = match host.rsplit_once(':') {
+        Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => host,
+    };
+    if host.is_empty() {
+        None
+    } else {
+        Some(host.to_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_rejects_blocked_schemes() {
+        let link = |url: &str| Link::new(url.to_string(), "Link".to_string());
+        let validate = |url: &str| link(url).validate(DEFAULT_BLOCKED_SCHEMES);
+        assert!(validate("https://www.rust-lang.org/").is_ok());
+        assert!(validate("about:blank").is_ok());
+        assert!(validate("javascript:alert(document.title)").is_err());
+        assert!(validate(" JavaScript:void(0)").is_err());
+        assert!(validate("data:text/html,<h1>Hi</h1>").is_err());
+        assert!(link("about:blank").validate(&["about"]).is_err());
+        assert!(link("javascript:void(0)").validate::<&str>(&[]).is_ok());
+        assert_eq!(
+            link("HTTPS://example.com").scheme(),
+            Some("https".to_string())
+        );
+        assert_eq!(link("no scheme here").scheme(), None);
+    }
+
+    #[test]
+    fn test_sort_links_by_score_then_recency() {
+        let link = |title: &str, score: Option<f32>, timestamp_seconds: i64| {
+            let mut link = Link::new(format!("https://example.com/{}", title), title.to_string())
+                .with_timestamp_seconds(timestamp_seconds);
+            link.score = score;
+            link
+        };
+        let mut links = [
+            link("unscored-old", None, 100),
+            link("low", Some(0.2), 300),
+            link("high-old", Some(0.9), 100),
+            link("unscored-new", None, 200),
+            link("high-new", Some(0.9), 200),
+        ];
+        links.sort_by(Link::cmp_relevance);
+
+        let titles: Vec<&str> = links.iter().map(|l| l.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            [
+                "high-new",
+                "high-old",
+                "low",
+                "unscored-new",
+                "unscored-old"
+            ]
+        );
+
+        // Ties on score and timestamp are broken by url
+        let a = link("a", Some(0.5), 100);
+        let b = link("b", Some(0.5), 100);
+        assert_eq!(a.cmp_relevance(&b), Ordering::Less);
+        assert_eq!(a.cmp_relevance(&a.clone()), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_is_absolute_url() {
+        assert!(is_absolute_url("https://www.rust-lang.org/learn"));
+        assert!(!is_absolute_url("rust-lang.org"));
+        assert!(!is_absolute_url("https://"));
+        assert!(!is_absolute_url("see https://www.rust-lang.org"));
+    }
+
+    #[test]
+    fn test_same_page_ignores_fragments() {
+        let a = Link::new("https://example.com/a#intro".to_string(), "A".to_string());
+        let b = Link::new("https://Example.com/a".to_string(), "A".to_string());
+        let c = Link::new("https://example.com/b#intro".to_string(), "B".to_string());
+        assert!(a.same_page_as(&b));
+        assert!(!a.same_page_as(&c));
+    }
+
+    #[test]
+    fn test_same_page_ignores_tracking_params() {
+        let a = Link::new(
+            "https://example.com/a?id=7&utm_source=news&utm_medium=email&fbclid=xyz".to_string(),
+            "A".to_string(),
+        );
+        let b = Link::new("https://example.com/a?id=7".to_string(), "A".to_string());
+        let c = Link::new("https://example.com/a?id=8".to_string(), "A".to_string());
+        let d = Link::new("https://example.com/a?gclid=1".to_string(), "A".to_string());
+        assert!(a.same_page_as(&b));
+        assert!(!a.same_page_as(&c));
+        assert!(d.same_page_as(&Link::new(
+            "https://example.com/a".to_string(),
+            "A".to_string()
+        )));
+    }
+
+    #[test]
+    fn test_deterministic_guid() {
+        let guid = Link::deterministic_guid("chrome", "https://www.rust-lang.org/");
+        assert_eq!(guid.len(), 64);
+        assert!(guid.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(
+            guid,
+            Link::deterministic_guid("chrome", "https://www.rust-lang.org/")
+        );
+        assert_eq!(
+            guid,
+            Link::deterministic_guid("chrome", "http://rust-lang.org")
+        );
+        assert_ne!(
+            guid,
+            Link::deterministic_guid("arc", "https://www.rust-lang.org/")
+        );
+        assert_ne!(
+            guid,
+            Link::deterministic_guid("chrome", "https://www.rust-lang.org/learn")
+        );
+    }
+
+    #[test]
+    fn test_normalize_url() {
+        for (url, normalized) in [
+            ("https://example.com", "https://example.com"),
+            ("https://example.com/", "https://example.com"),
+            ("http://example.com", "https://example.com"),
+            ("HTTPS://Example.COM/Docs/", "https://example.com/Docs"),
+            ("https://www.example.com/docs", "https://example.com/docs"),
+            (
+                "https://www2.example.com/docs",
+                "https://www2.example.com/docs",
+            ),
+            ("http://example.com:80/docs", "https://example.com/docs"),
+            ("https://example.com:443/docs", "https://example.com/docs"),
+            ("https://example.com:80/docs", "https://example.com:80/docs"),
+            (
+                "http://example.com:443/docs",
+                "https://example.com:443/docs",
+            ),
+            (
+                "https://example.com:8443/docs",
+                "https://example.com:8443/docs",
+            ),
+            (
+                "https://example.com/docs/#intro",
+                "https://example.com/docs",
+            ),
+            (
+                "https://example.com/a?id=7&utm_source=x",
+                "https://example.com/a?id=7",
+            ),
+            (
+                "https://example.com/a/?fbclid=1#top",
+                "https://example.com/a",
+            ),
+            ("  https://example.com/a  ", "https://example.com/a"),
+            (
+                "ftp://FTP.example.com:21/pub/",
+                "ftp://ftp.example.com:21/pub/",
+            ),
+            (
+                "file:///Users/me/notes.html#todo",
+                "file:///Users/me/notes.html",
+            ),
+            ("about:blank", "about:blank"),
+        ] {
+            assert_eq!(normalize_url(url), normalized, "normalizing {}", url);
+        }
+    }
+
+    #[test]
+    fn test_display_url() {
+        let display = |url: &str| Link::new(url.to_string(), String::new()).display_url();
+        assert_eq!(display("https://www.rust-lang.org/"), "rust-lang.org");
+        assert_eq!(
+            display("http://docs.rs/rusqlite/latest/rusqlite/"),
+            "docs.rs/rusqlite/latest/rusqlite/"
+        );
+        assert_eq!(
+            display("https://de.wikipedia.org/wiki/M%C3%BCnchen"),
+            "de.wikipedia.org/wiki/München"
+        );
+        assert_eq!(
+            display("https://example.com/100%25%ZZ"),
+            "example.com/100%%ZZ"
+        );
+        // Escapes of bytes which aren't UTF-8 are left alone
+        assert_eq!(display("https://example.com/%FF"), "example.com/%FF");
+    }
+
+    #[test]
+    fn test_display_url_truncates_long_urls() {
+        let link = Link::new(
+            "https://www.google.com/search?q=rust+sqlite+full+text+search&sca_esv=1234&source=hp&ei=abcdef".to_string(),
+            String::new(),
+        );
+        let display = link.display_url();
+        assert_eq!(display.chars().count(), MAX_DISPLAY_URL_LEN);
+        assert!(display.starts_with("google.com/search?q=rust+sqlite+full+text+search"));
+        assert!(display.ends_with('…'));
+    }
+
+    #[test]
+    fn test_host_of() {
+        assert_eq!(
+            host_of("https://user@Docs.RS:443/rusqlite?x=1"),
+            Some("docs.rs".to_string())
+        );
+        assert_eq!(host_of("not a url"), None);
+    }
 }
@@ -0,0 +1,101 @@
+use crate::builder::Options;
+use crate::Link;
+
+/// Separates the per-source entries of a merged subtitle. Folder paths use
+/// " / " between folders, so a different separator keeps them readable.
+pub(crate) const SUBTITLE_SEPARATOR: &str = " | ";
+
+/// The longest merged subtitle which will be stored. Longer subtitles are
+/// truncated with an ellipsis.
+const MAX_MERGED_SUBTITLE_LEN: usize = 200;
+
+/// Combines a link about to be saved with the existing link stored under the
+/// same url, according to the configured options. The incoming link wins for
+/// every field which isn't explicitly merged.
+pub(crate) fn merge(existing: &Link, mut incoming: Link, options: &Options) -> Link {
+    if options.merge_subtitles {
+        incoming.subtitle = merge_subtitles(existing, &incoming);
+    }
+    let precedence = &options.timestamp_precedence;
+    if !precedence.is_empty() && rank(precedence, existing) < rank(precedence, &incoming) {
+        incoming.timestamp = existing.timestamp;
+        incoming.source = existing.source.clone();
+    }
+    incoming
+}
+
+/// Returns the position of the link's source in the timestamp precedence,
+/// where lower is preferred. Unlisted sources rank after every listed one.
+fn rank(precedence: &[String], link: &Link) -> usize {
+    let source = link.source.as_deref().unwrap_or_default().to_lowercase();
+    precedence
+        .iter()
+        .position(|preferred| *preferred == source)
+        .unwrap_or(precedence.len())
+}
+
+/// Merges the subtitles of the same page imported from different sources,
+/// labelling each with its source, e.g. "Firefox: Work | Chrome: Bookmarks
+/// Bar". While only one source has contributed a subtitle it is left
+/// unlabelled.
+fn merge_subtitles(existing: &Link, incoming: &Link) -> Option<String> {
+    let existing_label = source_label(existing.source.as_deref());
+    let incoming_label = source_label(incoming.source.as_deref());
+
+    let mut entries: Vec<(String, String)> = match existing.subtitle.as_deref() {
+        Some(subtitle) if !subtitle.is_empty() => {
+            let parsed: Vec<(String, String)> = subtitle
+                .split(SUBTITLE_SEPARATOR)
+                .filter_map(|entry| {
+                    entry
+                        .split_once(": ")
+                        .map(|(label, path)| (label.to_string(), path.to_string()))
+                })
+                .collect();
+            // An unlabelled subtitle came from the existing link's source alone
+            if parsed.iter().any(|(label, _)| *label == existing_label) {
+                parsed
+            } else {
+                vec![(existing_label, subtitle.to_string())]
+            }
+        }
+        _ => vec![],
+    };
+
+    entries.retain(|(label, _)| *label != incoming_label);
+    if let Some(subtitle) = incoming.subtitle.as_deref().filter(|s| !s.is_empty()) {
+        entries.push((incoming_label, subtitle.to_string()));
+    }
+
+    match entries.len() {
+        0 => None,
+        1 => Some(entries.remove(0).1),
+        _ => {
+            let merged = entries
+                .iter()
+                .map(|(label, path)| format!("{}: {}", label, path))
+                .collect::<Vec<_>>()
+                .join(SUBTITLE_SEPARATOR);
+            Some(truncate(merged, MAX_MERGED_SUBTITLE_LEN))
+        }
+    }
+}
+
+/// Returns the capitalized source name used to label subtitle entries.
+fn source_label(source: Option<&str>) -> String {
+    let source = source.filter(|s| !s.is_empty()).unwrap_or("unknown");
+    let mut chars = source.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn truncate(s: String, max_chars: usize) -> String {
+    if s.chars().count() <= max_chars {
+        return s;
+    }
+    let mut truncated: String = s.chars().take(max_chars - 1).collect();
+    truncated.push('…');
+    truncated
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use crate::cache::UNEXPIRED;
+use crate::{Cache, Result};
+
+impl Cache {
+    /// Attaches a piece of app-specific metadata (e.g. "note" or "color") to
+    /// the link with the provided url, replacing any value already set for
+    /// the key. Metadata isn't searchable, survives the link being re-added
+    /// and is deleted along with the link when it's purged. The url may be a
+    /// variant of the stored link's url (see normalize_url). Returns false if
+    /// no link is stored under the url.
+    pub fn set_meta(&mut self, url: &str, key: &str, value: &str) -> Result<bool> {
+        let set = self.conn.execute(
+            &format!(
+                "INSERT OR REPLACE INTO link_meta (collection, url, key, value)
+                 SELECT links.collection, links.url, ?3, ?4 FROM links
+                 WHERE links.normalized_url = normalize_url(?1) AND links.collection = ?2
+                 AND links.deleted_at IS NULL AND {UNEXPIRED}"
+            ),
+            (url, self.options.collection(), key, value),
+        )?;
+        Ok(set > 0)
+    }
+
+    /// Returns the value of one piece of metadata attached to the link with
+    /// the provided url, or None when it isn't set (or the link has been
+    /// removed).
+    pub fn get_meta(&self, url: &str, key: &str) -> Result<Option<String>> {
+        Ok(self.meta_map(url)?.remove(key))
+    }
+
+    /// Returns all of the metadata attached to the link with the provided
+    /// url, which is empty when there's none (or the link has been removed).
+    pub fn meta_map(&self, url: &str) -> Result<HashMap<String, String>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT link_meta.key, link_meta.value
+             FROM link_meta
+             JOIN links ON links.url = link_meta.url AND links.collection = link_meta.collection
+             WHERE links.normalized_url = normalize_url(?1) AND links.collection = ?2
+             AND links.deleted_at IS NULL AND {UNEXPIRED}",
+        ))?;
+        let entries = stmt.query_map((url, self.options.collection()), |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+        entries
+            .collect::<std::result::Result<HashMap<_, _>, rusqlite::Error>>()
+            .map_err(|e| e.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::create_test_cache;
+    use crate::Link;
+
+    fn rust_book() -> Link {
+        Link::new(
+            "https://doc.rust-lang.org/book/".to_string(),
+            "The Rust Book".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_meta_round_trip() -> Result<()> {
+        let mut cache = create_test_cache();
+        let link = rust_book();
+        assert!(!cache.set_meta(&link.url, "note", "Read chapter 8")?);
+
+        cache.add(link.clone())?;
+        assert!(cache.set_meta(&link.url, "note", "Read chapter 8")?);
+        assert!(cache.set_meta(&link.url, "color", "red")?);
+        assert!(cache.set_meta(&link.url, "color", "blue")?);
+        assert_eq!(
+            cache.get_meta(&link.url, "color")?,
+            Some("blue".to_string())
+        );
+        assert_eq!(cache.get_meta(&link.url, "missing")?, None);
+
+        // Re-importing the link keeps its metadata, which isn't searchable
+        cache.add(link.clone())?;
+        let expected = HashMap::from([
+            ("note".to_string(), "Read chapter 8".to_string()),
+            ("color".to_string(), "blue".to_string()),
+        ]);
+        assert_eq!(cache.meta_map(&link.url)?, expected);
+        assert!(cache.search("chapter")?.is_empty());
+
+        // Variants of the url find the same link's metadata
+        let variant = "http://doc.rust-lang.org/book";
+        assert!(cache.set_meta(variant, "color", "green")?);
+        assert_eq!(cache.get_meta(variant, "color")?, Some("green".to_string()));
+        assert_eq!(cache.meta_map(&link.url)?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_meta_is_deleted_with_its_link() -> Result<()> {
+        let mut cache = create_test_cache();
+        let link = rust_book();
+        cache.add(link.clone())?;
+        cache.set_meta(&link.url, "note", "Read chapter 8")?;
+
+        cache.remove(&link)?;
+        assert!(cache.meta_map(&link.url)?.is_empty());
+        cache.restore(&link.url)?;
+        assert_eq!(cache.meta_map(&link.url)?.len(), 1);
+
+        cache.remove(&link)?;
+        cache.purge_deleted()?;
+        let orphans: i64 = cache
+            .conn
+            .query_row("SELECT COUNT(*) FROM link_meta", [], |row| row.get(0))?;
+        assert_eq!(orphans, 0);
+        Ok(())
+    }
+}
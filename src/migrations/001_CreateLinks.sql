@@ -0,0 +1,47 @@
+CREATE TABLE IF NOT EXISTS links (
+    url TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    subtitle TEXT,
+    source TEXT,
+    author TEXT,
+    timestamp TEXT NOT NULL
+);
+
+
+CREATE VIRTUAL TABLE IF NOT EXISTS links_fts USING fts5 (
+    url, title, subtitle, source, author,
+    tokenize='trigram'
+);
+
+
+-- The url is the unique key of the links table, so the FTS row
+-- must be replaced by url alone. Keying on anything else (e.g.
+-- url AND title) leaves stale index rows behind every time a
+-- re-import changes a title. Existing databases may carry the
+-- older triggers, so they are always recreated.
+DROP TRIGGER IF EXISTS links_upsert;
+CREATE TRIGGER links_upsert AFTER INSERT ON links
+BEGIN
+    DELETE FROM links_fts WHERE url = new.url;
+    INSERT INTO links_fts
+    (url, title, subtitle, source, author)
+    VALUES
+    (new.url, new.title, new.subtitle, new.source, new.author);
+END;
+
+
+DROP TRIGGER IF EXISTS links_update;
+CREATE TRIGGER links_update AFTER UPDATE ON links
+BEGIN
+    DELETE FROM links_fts WHERE url = old.url;
+    INSERT INTO links_fts
+    (url, title, subtitle, source, author)
+    VALUES
+    (new.url, new.title, new.subtitle, new.source, new.author);
+END;
+
+
+CREATE TRIGGER IF NOT EXISTS links_delete BEFORE DELETE ON links
+BEGIN
+    DELETE FROM links_fts WHERE url = old.url;
+END;
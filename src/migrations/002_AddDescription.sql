@@ -0,0 +1,52 @@
+-- Adds an optional page description (e.g. <meta name="description">) which
+-- is indexed for search. FTS5 tables can't be altered, so the index is
+-- rebuilt with the new column and repopulated from links.
+ALTER TABLE links ADD COLUMN description TEXT;
+
+
+DROP TRIGGER IF EXISTS links_upsert;
+DROP TRIGGER IF EXISTS links_update;
+DROP TRIGGER IF EXISTS links_delete;
+DROP TABLE IF EXISTS links_fts;
+
+
+CREATE VIRTUAL TABLE links_fts USING fts5 (
+    url, title, subtitle, source, author, description,
+    tokenize='trigram'
+);
+
+
+-- Column weights for bm25 ranking, in the column order above. Title matches
+-- count most, then the folder path, then the description.
+INSERT INTO links_fts (links_fts, rank) VALUES ('rank', 'bm25(1.0, 10.0, 5.0, 1.0, 1.0, 2.0)');
+
+
+CREATE TRIGGER links_upsert AFTER INSERT ON links
+BEGIN
+    DELETE FROM links_fts WHERE url = new.url;
+    INSERT INTO links_fts
+    (url, title, subtitle, source, author, description)
+    VALUES
+    (new.url, new.title, new.subtitle, new.source, new.author, new.description);
+END;
+
+
+CREATE TRIGGER links_update AFTER UPDATE ON links
+BEGIN
+    DELETE FROM links_fts WHERE url = old.url;
+    INSERT INTO links_fts
+    (url, title, subtitle, source, author, description)
+    VALUES
+    (new.url, new.title, new.subtitle, new.source, new.author, new.description);
+END;
+
+
+CREATE TRIGGER links_delete BEFORE DELETE ON links
+BEGIN
+    DELETE FROM links_fts WHERE url = old.url;
+END;
+
+
+INSERT INTO links_fts
+(url, title, subtitle, source, author, description)
+SELECT url, title, subtitle, source, author, description FROM links;
@@ -0,0 +1,3 @@
+-- Records when a link was last opened from linkcache, separately from the
+-- timestamp set by whichever import discovered it. Not indexed for search.
+ALTER TABLE links ADD COLUMN last_opened_at TEXT;
@@ -0,0 +1,74 @@
+-- Adds the collection (e.g. "personal", "work") which each link belongs to,
+-- so one database can hold several independent sets of links. A url is now
+-- unique within its collection rather than across the whole table, which
+-- means rebuilding both the links table (to change its primary key) and the
+-- FTS index (which can't be altered).
+DROP TRIGGER IF EXISTS links_upsert;
+DROP TRIGGER IF EXISTS links_update;
+DROP TRIGGER IF EXISTS links_delete;
+DROP TABLE IF EXISTS links_fts;
+
+
+CREATE TABLE links_new (
+    collection TEXT NOT NULL DEFAULT 'default',
+    url TEXT NOT NULL,
+    title TEXT NOT NULL,
+    subtitle TEXT,
+    source TEXT,
+    author TEXT,
+    timestamp TEXT NOT NULL,
+    description TEXT,
+    last_opened_at TEXT,
+    PRIMARY KEY (collection, url)
+);
+
+INSERT INTO links_new
+(url, title, subtitle, source, author, timestamp, description, last_opened_at)
+SELECT url, title, subtitle, source, author, timestamp, description, last_opened_at FROM links;
+
+DROP TABLE links;
+ALTER TABLE links_new RENAME TO links;
+
+
+-- The collection is stored (but not indexed) so matches can be joined back
+-- to the right link. It comes last so the bm25 weights below still line up
+-- with the indexed columns.
+CREATE VIRTUAL TABLE links_fts USING fts5 (
+    url, title, subtitle, source, author, description,
+    collection UNINDEXED,
+    tokenize='trigram'
+);
+
+
+INSERT INTO links_fts (links_fts, rank) VALUES ('rank', 'bm25(1.0, 10.0, 5.0, 1.0, 1.0, 2.0)');
+
+
+CREATE TRIGGER links_upsert AFTER INSERT ON links
+BEGIN
+    DELETE FROM links_fts WHERE url = new.url AND collection = new.collection;
+    INSERT INTO links_fts
+    (url, title, subtitle, source, author, description, collection)
+    VALUES
+    (new.url, new.title, new.subtitle, new.source, new.author, new.description, new.collection);
+END;
+
+
+CREATE TRIGGER links_update AFTER UPDATE ON links
+BEGIN
+    DELETE FROM links_fts WHERE url = old.url AND collection = old.collection;
+    INSERT INTO links_fts
+    (url, title, subtitle, source, author, description, collection)
+    VALUES
+    (new.url, new.title, new.subtitle, new.source, new.author, new.description, new.collection);
+END;
+
+
+CREATE TRIGGER links_delete BEFORE DELETE ON links
+BEGIN
+    DELETE FROM links_fts WHERE url = old.url AND collection = old.collection;
+END;
+
+
+INSERT INTO links_fts
+(url, title, subtitle, source, author, description, collection)
+SELECT url, title, subtitle, source, author, description, collection FROM links;
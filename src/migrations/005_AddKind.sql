@@ -0,0 +1,4 @@
+-- Records whether a link is a bookmark or a history entry, so ranking can
+-- treat them differently. Links imported before this was tracked have no
+-- kind. Not indexed for search.
+ALTER TABLE links ADD COLUMN kind TEXT;
@@ -0,0 +1,6 @@
+-- Records the identifier the source browser assigned to each link, so a
+-- link whose url changes between imports replaces its old row rather than
+-- leaving a duplicate behind. Not indexed for search.
+ALTER TABLE links ADD COLUMN guid TEXT;
+
+CREATE INDEX IF NOT EXISTS links_guid ON links (collection, guid);
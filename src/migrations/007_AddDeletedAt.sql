@@ -0,0 +1,4 @@
+-- Records when a link was removed. Removed links stay in the table (and the
+-- search index) so they can be restored, but are excluded from every query
+-- until they're restored or purged.
+ALTER TABLE links ADD COLUMN deleted_at TEXT;
@@ -0,0 +1,20 @@
+-- Rebuilds the search index with diacritics folded, so "Munchen" matches
+-- "München" and vice versa. FTS5 applies the same folding to queries. The
+-- triggers on links refer to links_fts by name, so they carry on working
+-- once the table is recreated.
+DROP TABLE IF EXISTS links_fts;
+
+
+CREATE VIRTUAL TABLE links_fts USING fts5 (
+    url, title, subtitle, source, author, description,
+    collection UNINDEXED,
+    tokenize='trigram remove_diacritics 1'
+);
+
+
+INSERT INTO links_fts (links_fts, rank) VALUES ('rank', 'bm25(1.0, 10.0, 5.0, 1.0, 1.0, 2.0)');
+
+
+INSERT INTO links_fts
+(url, title, subtitle, source, author, description, collection)
+SELECT url, title, subtitle, source, author, description, collection FROM links;
@@ -0,0 +1,4 @@
+-- Records when an ephemeral link (e.g. an open tab) stops being relevant.
+-- Expired links are excluded from every query until purge_expired() deletes
+-- them. NULL means the link never expires.
+ALTER TABLE links ADD COLUMN expires_at TEXT;
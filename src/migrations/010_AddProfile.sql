@@ -0,0 +1,4 @@
+-- Records which browser profile (e.g. Chrome's "Profile 1") a link was
+-- imported from, so links from several profiles of one browser can be told
+-- apart.
+ALTER TABLE links ADD COLUMN profile TEXT;
@@ -0,0 +1,17 @@
+-- Stores app-specific metadata (e.g. a note or a color tag) which
+-- integrators attach to links, as key-value pairs. It isn't indexed for
+-- search. Re-adding a link keeps its metadata; deleting the link (e.g. by
+-- purging it) deletes the metadata too.
+CREATE TABLE IF NOT EXISTS link_meta (
+    collection TEXT NOT NULL,
+    url TEXT NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (collection, url, key)
+);
+
+
+CREATE TRIGGER IF NOT EXISTS links_delete_meta AFTER DELETE ON links
+BEGIN
+    DELETE FROM link_meta WHERE url = old.url AND collection = old.collection;
+END;
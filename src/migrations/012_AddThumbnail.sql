@@ -0,0 +1,3 @@
+-- Records the path of an image of the page (e.g. a browser's thumbnail of
+-- it), for clients which show previews. Not indexed for search.
+ALTER TABLE links ADD COLUMN thumbnail TEXT;
@@ -0,0 +1,9 @@
+-- Caches the bytes of each host's favicon, so launchers can show icons
+-- without going online. Icons are shared by every collection, since they
+-- belong to the site rather than to any one link.
+CREATE TABLE IF NOT EXISTS favicons (
+    host TEXT PRIMARY KEY NOT NULL,
+    bytes BLOB NOT NULL,
+    mime TEXT NOT NULL,
+    fetched_at DATETIME NOT NULL
+);
@@ -0,0 +1,5 @@
+-- Records how often the browser visited each link, and how often its url
+-- was typed into the address bar, for ranking frequently-used links higher.
+-- NULL when the source doesn't track them.
+ALTER TABLE links ADD COLUMN visit_count INTEGER;
+ALTER TABLE links ADD COLUMN typed_count INTEGER;
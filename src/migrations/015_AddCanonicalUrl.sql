@@ -0,0 +1,53 @@
+-- Records the url a link redirected to (e.g. the page behind a short link),
+-- so a search for either url finds the link. It equals the url for links
+-- whose source has no redirect data. The search index gains a column for
+-- it, which means rebuilding the index and the triggers which fill it.
+DROP TRIGGER IF EXISTS links_upsert;
+DROP TRIGGER IF EXISTS links_update;
+DROP TABLE IF EXISTS links_fts;
+
+
+ALTER TABLE links ADD COLUMN canonical_url TEXT;
+UPDATE links SET canonical_url = url;
+
+
+-- The canonical url comes after the collection so the columns (and bm25
+-- weights) before it keep their positions. It's only indexed when it
+-- differs from the url, so a link's url isn't counted twice.
+CREATE VIRTUAL TABLE links_fts USING fts5 (
+    url, title, subtitle, source, author, description,
+    collection UNINDEXED,
+    canonical_url,
+    tokenize='trigram remove_diacritics 1'
+);
+
+
+INSERT INTO links_fts (links_fts, rank) VALUES ('rank', 'bm25(1.0, 10.0, 5.0, 1.0, 1.0, 2.0, 0.0, 1.0)');
+
+
+CREATE TRIGGER links_upsert AFTER INSERT ON links
+BEGIN
+    DELETE FROM links_fts WHERE url = new.url AND collection = new.collection;
+    INSERT INTO links_fts
+    (url, title, subtitle, source, author, description, collection, canonical_url)
+    VALUES
+    (new.url, new.title, new.subtitle, new.source, new.author, new.description, new.collection,
+     NULLIF(new.canonical_url, new.url));
+END;
+
+
+CREATE TRIGGER links_update AFTER UPDATE ON links
+BEGIN
+    DELETE FROM links_fts WHERE url = old.url AND collection = old.collection;
+    INSERT INTO links_fts
+    (url, title, subtitle, source, author, description, collection, canonical_url)
+    VALUES
+    (new.url, new.title, new.subtitle, new.source, new.author, new.description, new.collection,
+     NULLIF(new.canonical_url, new.url));
+END;
+
+
+INSERT INTO links_fts
+(url, title, subtitle, source, author, description, collection, canonical_url)
+SELECT url, title, subtitle, source, author, description, collection, NULLIF(canonical_url, url)
+FROM links;
@@ -0,0 +1,17 @@
+-- Stores each link's url in its normalized form (see normalize_url, which
+-- the crate registers as a SQL function), so variants of a url such as
+-- "http://example.com" and "https://example.com/" are stored as one link.
+-- Of the links already stored under variants of one url, the most recent
+-- is kept.
+ALTER TABLE links ADD COLUMN normalized_url TEXT;
+UPDATE links SET normalized_url = normalize_url(url);
+
+
+DELETE FROM links WHERE rowid NOT IN (
+    SELECT id FROM (
+        SELECT rowid AS id, MAX(timestamp) FROM links GROUP BY collection, normalized_url
+    )
+);
+
+
+CREATE UNIQUE INDEX links_normalized_url ON links (collection, normalized_url);
@@ -0,0 +1,147 @@
+use chrono::{DateTime, NaiveDate, Utc};
+
+/// The filters of a search query written with operators, e.g.
+/// "site:github.com title:issues after:2024-01-01 rusqlite".
+///
+/// - site:<host> matches links whose host contains the hint
+/// - after:<YYYY-MM-DD> and before:<YYYY-MM-DD> match links whose timestamp
+///   is on or after, or strictly before, the start of the day (in UTC)
+/// - source:<source> matches links from the source
+/// - title:<term> matches links whose title contains the term
+///
+/// Everything else is the text matched against the search index as usual.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct QueryFilters<'a> {
+    pub site: Option<&'a str>,
+    pub after: Option<DateTime<Utc>>,
+    pub before: Option<DateTime<Utc>>,
+    pub source: Option<&'a str>,
+    pub titles: Vec<&'a str>,
+    pub text: String,
+}
+
+impl<'a> QueryFilters<'a> {
+    /// Parses the operators out of the query. Returns None when it has none,
+    /// so it can be searched as plain text. An operator which can't be used
+    /// (e.g. "after:yesterday", or a second "site:") is searched for
+    /// literally, as text.
+    pub fn parse(query: &'a str) -> Option<Self> {
+        let mut filters = QueryFilters::default();
+        let mut text = vec![];
+        let mut has_operators = false;
+        for term in query.split_whitespace() {
+            let Some((operator, value)) = term.split_once(':') else {
+                text.push(term.to_string());
+                continue;
+            };
+            let applied = match operator {
+                "site" => set_once(&mut filters.site, Some(value).filter(|v| !v.is_empty())),
+                "after" => set_once(&mut filters.after, start_of_day(value)),
+                "before" => set_once(&mut filters.before, start_of_day(value)),
+                "source" => set_once(&mut filters.source, Some(value).filter(|v| !v.is_empty())),
+                "title" if !value.is_empty() => {
+                    filters.titles.push(value);
+                    true
+                }
+                "title" => false,
+                // Not an operator, e.g. part of a url or "c++:"
+                _ => {
+                    text.push(term.to_string());
+                    continue;
+                }
+            };
+            has_operators = true;
+            if !applied {
+                text.push(quoted(term));
+            }
+        }
+        filters.text = text.join(" ");
+        has_operators.then_some(filters)
+    }
+
+    /// Returns the text to MATCH against the search index: the query's text
+    /// along with a column filter for each title: term.
+    pub fn match_text(&self, text: &str) -> String {
+        std::iter::once(text.to_string())
+            .filter(|text| !text.is_empty())
+            .chain(
+                self.titles
+                    .iter()
+                    .map(|title| format!("title : {}", quoted(title))),
+            )
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Sets the filter when it's valid and not already set. Returns false
+/// otherwise.
+fn set_once<T>(filter: &mut Option<T>, value: Option<T>) -> bool {
+    match value {
+        Some(value) if filter.is_none() => {
+            *filter = Some(value);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Parses a YYYY-MM-DD date into the start of the day in UTC.
+fn start_of_day(date: &str) -> Option<DateTime<Utc>> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+/// Quotes the term as an FTS5 string, so it's matched literally rather than
+/// parsed as query syntax.
+fn quoted(term: &str) -> String {
+    format!("\"{}\"", term.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_operators() {
+        let filters = QueryFilters::parse(
+            "site:github.com title:issues after:2024-01-01 before:2024-02-01 source:firefox rusqlite",
+        )
+        .unwrap();
+        assert_eq!(filters.site, Some("github.com"));
+        assert_eq!(filters.titles, vec!["issues"]);
+        assert_eq!(
+            filters.after.unwrap().to_rfc3339(),
+            "2024-01-01T00:00:00+00:00"
+        );
+        assert_eq!(
+            filters.before.unwrap().to_rfc3339(),
+            "2024-02-01T00:00:00+00:00"
+        );
+        assert_eq!(filters.source, Some("firefox"));
+        assert_eq!(filters.text, "rusqlite");
+        assert_eq!(
+            filters.match_text(&filters.text),
+            "rusqlite title : \"issues\""
+        );
+    }
+
+    #[test]
+    fn test_plain_queries_have_no_filters() {
+        assert_eq!(QueryFilters::parse("rust book"), None);
+        assert_eq!(QueryFilters::parse("c++: primer"), None);
+    }
+
+    #[test]
+    fn test_invalid_operators_are_text() {
+        let filters =
+            QueryFilters::parse("site:github.com after:yesterday site:gitlab.com").unwrap();
+        assert_eq!(filters.site, Some("github.com"));
+        assert_eq!(filters.after, None);
+        assert_eq!(filters.text, "\"after:yesterday\" \"site:gitlab.com\"");
+
+        let filters = QueryFilters::parse("site: rust").unwrap();
+        assert_eq!(filters.site, None);
+        assert_eq!(filters.text, "\"site:\" rust");
+    }
+}
@@ -0,0 +1,51 @@
+use filetime::FileTime;
+use log::debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+
+/// Copies a SQLite database which a browser holds locked to a replica
+/// linkcache can read. While the browser is running, its latest writes are
+/// in the write-ahead log, so the log and its index are copied alongside it
+/// when they exist. The copy is skipped when the replica is already newer
+/// than the database and its log, since the files may be large and are
+/// often unchanged between refreshes. Returns true if it copied.
+pub(crate) fn replicate(database: &Path, replica: &Path) -> Result<bool> {
+    let mut modified = fs::metadata(database)?.modified()?;
+    if let Ok(wal) = fs::metadata(sidecar(database, WAL_SUFFIX)) {
+        modified = modified.max(wal.modified()?);
+    }
+    if let Ok(copied) = fs::metadata(replica) {
+        if modified <= copied.modified()? {
+            debug!("Skipping copy of unchanged {}", database.display());
+            return Ok(false);
+        }
+    }
+
+    fs::copy(database, replica)?;
+    for suffix in [WAL_SUFFIX, SHM_SUFFIX] {
+        match fs::copy(sidecar(database, suffix), sidecar(replica, suffix)) {
+            Ok(_) => {}
+            // A log left over from an earlier copy doesn't belong to this one
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                let _ = fs::remove_file(sidecar(replica, suffix));
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+    filetime::set_file_times(replica, FileTime::now(), FileTime::now())?;
+    Ok(true)
+}
+
+/// The suffixes SQLite appends to a database's path to name its write-ahead
+/// log and the log's shared-memory index.
+pub(crate) const WAL_SUFFIX: &str = "-wal";
+pub(crate) const SHM_SUFFIX: &str = "-shm";
+
+/// Returns the path of one of the database's sidecar files (e.g. its -wal).
+pub(crate) fn sidecar(database: &Path, suffix: &str) -> PathBuf {
+    let mut path = database.as_os_str().to_os_string();
+    path.push(suffix);
+    PathBuf::from(path)
+}
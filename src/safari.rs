@@ -0,0 +1,347 @@
+use chrono::{DateTime, Utc};
+use plist::Value;
+use rusqlite::Connection;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::replica::replicate;
+use crate::{BrowserSource, Cache, Link, LinkKind};
+
+/// The source recorded on every Link imported from Safari
+pub const SOURCE: &str = "safari";
+
+/// Seconds between the Unix epoch and the Cocoa epoch (2001-01-01), which
+/// Safari counts its history timestamps from.
+const COCOA_EPOCH_OFFSET_SECS: f64 = 978_307_200.0;
+
+/// The folder Safari keeps its Reading List in, alongside the bookmarks
+const READING_LIST_FOLDER: &str = "com.apple.ReadingList";
+
+/// Converts a Safari timestamp (seconds since the Cocoa epoch) into a
+/// DateTime. Values out of range become the default (Unix epoch) timestamp.
+fn from_cocoa_time(seconds: f64) -> DateTime<Utc> {
+    let millis = ((seconds + COCOA_EPOCH_OFFSET_SECS) * 1000.0) as i64;
+    DateTime::from_timestamp_millis(millis).unwrap_or_default()
+}
+
+/// Safari's bookmarks and history. macOS only lets apps with Full Disk
+/// Access read the Safari directory.
+pub struct Browser {
+    profile_dir: PathBuf,
+}
+
+impl Browser {
+    /// Default constructor which creates a new Safari Browser reading the
+    /// current user's Safari directory.
+    pub fn new() -> Self {
+        Browser {
+            profile_dir: Self::default_profile_dir(),
+        }
+    }
+
+    /// Overrides the directory Bookmarks.plist and History.db are read
+    /// from.
+    pub fn with_profile_dir(mut self, dir: PathBuf) -> Self {
+        self.profile_dir = dir;
+        self
+    }
+
+    /// Adds every bookmark from this browser to the provided Cache,
+    /// returning the number of links written.
+    pub fn cache_bookmarks(&self, cache: &mut Cache) -> Result<usize> {
+        cache.add_batch(self.bookmark_links()?)
+    }
+
+    /// Adds every visited page in this browser's history to the provided
+    /// Cache, returning the number of links written.
+    pub fn cache_history(&self, cache: &mut Cache) -> Result<usize> {
+        replicate(&self.history_path(), &self.history_replica_path())?;
+        cache.add_batch(self.history_links()?)
+    }
+
+    /// Parses Bookmarks.plist and returns a Link for each bookmark, with the
+    /// folders it's in as its subtitle (e.g. "/Favorites/Rust"). Each Link
+    /// carries Safari's own UUID for the bookmark as its guid. The Reading
+    /// List, which Safari stores as a bookmark folder, is skipped.
+    pub fn bookmark_links(&self) -> Result<Vec<Link>> {
+        fn traverse(node: &Value, links: &mut Vec<Link>, subtitle: &str) {
+            let Some(node) = node.as_dictionary() else {
+                return;
+            };
+            let field = |key: &str| node.get(key).and_then(Value::as_string);
+            match field("WebBookmarkType") {
+                Some("WebBookmarkTypeLeaf") => {
+                    let Some(url) = field("URLString") else {
+                        return;
+                    };
+                    let title = node
+                        .get("URIDictionary")
+                        .and_then(Value::as_dictionary)
+                        .and_then(|uri| uri.get("title"))
+                        .and_then(Value::as_string)
+                        .filter(|title| !title.is_empty())
+                        .unwrap_or(url);
+                    let mut link = Link::new(url.to_string(), title.to_string())
+                        .with_source(SOURCE.to_string())
+                        .with_kind(LinkKind::Bookmark)
+                        .with_subtitle(subtitle.to_string());
+                    link.guid = field("WebBookmarkUUID").map(String::from);
+                    links.push(link);
+                }
+                Some("WebBookmarkTypeList") => {
+                    let title = match field("Title").unwrap_or_default() {
+                        READING_LIST_FOLDER => return,
+                        // Safari shows its bookmarks bar as Favorites
+                        "BookmarksBar" => "Favorites",
+                        "BookmarksMenu" => "Bookmarks Menu",
+                        title => title,
+                    };
+                    let subtitle = if title.is_empty() {
+                        subtitle.to_string()
+                    } else {
+                        format!("{}/{}", subtitle, title)
+                    };
+                    let children = node.get("Children").and_then(Value::as_array);
+                    for child in children.into_iter().flatten() {
+                        traverse(child, links, &subtitle);
+                    }
+                }
+                // Proxies (e.g. the History entry) don't point at a page
+                _ => {}
+            }
+        }
+
+        let root = Value::from_file(self.bookmarks_path())
+            .map_err(|err| Error::Parse(format!("Bookmarks.plist: {}", err)))?;
+        let mut links = vec![];
+        traverse(&root, &mut links, "");
+        Ok(links)
+    }
+
+    /// Scans the copy of History.db (this function assumes it already
+    /// exists) and returns a Link for each page in the history, oldest visit
+    /// first, titled as it was on the latest visit.
+    pub fn history_links(&self) -> Result<Vec<Link>> {
+        let conn = Connection::open(self.history_replica_path())?;
+        let mut stmt = conn.prepare(
+            r#"
+                SELECT history_items.url,
+                COALESCE(NULLIF(history_visits.title, ''), history_items.url),
+                MAX(history_visits.visit_time),
+                history_items.visit_count
+                FROM history_items
+                JOIN history_visits ON history_visits.history_item = history_items.id
+                GROUP BY history_items.id
+                ORDER BY 3 ASC
+            "#,
+        )?;
+        let links = stmt
+            .query_map([], |row| {
+                Ok(Link {
+                    url: row.get(0)?,
+                    title: row.get(1)?,
+                    source: Some(SOURCE.to_string()),
+                    kind: Some(LinkKind::History),
+                    timestamp: from_cocoa_time(row.get(2)?),
+                    visit_count: row.get(3)?,
+                    ..Default::default()
+                })
+            })?
+            // Remove erroneous rows
+            .filter_map(|link| link.ok())
+            .collect();
+        Ok(links)
+    }
+
+    fn bookmarks_path(&self) -> PathBuf {
+        self.profile_dir.join("Bookmarks.plist")
+    }
+
+    fn history_path(&self) -> PathBuf {
+        self.profile_dir.join("History.db")
+    }
+
+    fn history_replica_path(&self) -> PathBuf {
+        self.history_path().with_file_name("History.linkcache.db")
+    }
+
+    /// Returns the current user's Safari directory.
+    pub fn default_profile_dir() -> PathBuf {
+        let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/tmp"));
+        Self::profile_dir_in(&home_dir)
+    }
+
+    /// Returns the Safari directory under the provided home directory.
+    /// Safari only runs on macOS.
+    pub fn profile_dir_in(home_dir: &Path) -> PathBuf {
+        home_dir.join("Library/Safari")
+    }
+}
+
+impl BrowserSource for Browser {
+    /// The bookmarks, followed by the history when there is one.
+    fn links(&self) -> Result<Vec<Link>> {
+        let mut links = self.bookmark_links()?;
+        if self.history_path().exists() {
+            replicate(&self.history_path(), &self.history_replica_path())?;
+            links.extend(self.history_links()?);
+        }
+        Ok(links)
+    }
+
+    fn source_name(&self) -> &'static str {
+        SOURCE
+    }
+}
+
+impl Default for Browser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::create_test_cache;
+
+    const BOOKMARKS_PLIST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Title</key><string></string>
+    <key>WebBookmarkType</key><string>WebBookmarkTypeList</string>
+    <key>Children</key>
+    <array>
+        <dict>
+            <key>Title</key><string>History</string>
+            <key>WebBookmarkType</key><string>WebBookmarkTypeProxy</string>
+        </dict>
+        <dict>
+            <key>Title</key><string>BookmarksBar</string>
+            <key>WebBookmarkType</key><string>WebBookmarkTypeList</string>
+            <key>Children</key>
+            <array>
+                <dict>
+                    <key>Title</key><string>Rust</string>
+                    <key>WebBookmarkType</key><string>WebBookmarkTypeList</string>
+                    <key>Children</key>
+                    <array>
+                        <dict>
+                            <key>URLString</key><string>https://doc.rust-lang.org/book/</string>
+                            <key>URIDictionary</key>
+                            <dict><key>title</key><string>The Rust Book</string></dict>
+                            <key>WebBookmarkType</key><string>WebBookmarkTypeLeaf</string>
+                            <key>WebBookmarkUUID</key><string>6C2E3F4A-0000-4000-8000-000000000001</string>
+                        </dict>
+                    </array>
+                </dict>
+            </array>
+        </dict>
+        <dict>
+            <key>Title</key><string>BookmarksMenu</string>
+            <key>WebBookmarkType</key><string>WebBookmarkTypeList</string>
+            <key>Children</key>
+            <array>
+                <dict>
+                    <key>URLString</key><string>https://www.sqlite.org/</string>
+                    <key>URIDictionary</key><dict></dict>
+                    <key>WebBookmarkType</key><string>WebBookmarkTypeLeaf</string>
+                </dict>
+            </array>
+        </dict>
+        <dict>
+            <key>Title</key><string>com.apple.ReadingList</string>
+            <key>WebBookmarkType</key><string>WebBookmarkTypeList</string>
+            <key>Children</key>
+            <array>
+                <dict>
+                    <key>URLString</key><string>https://example.com/read-later</string>
+                    <key>URIDictionary</key>
+                    <dict><key>title</key><string>Read Later</string></dict>
+                    <key>WebBookmarkType</key><string>WebBookmarkTypeLeaf</string>
+                </dict>
+            </array>
+        </dict>
+    </array>
+</dict>
+</plist>
+"#;
+
+    fn build_history_fixture(path: &Path) -> Result<()> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE history_items (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                url TEXT NOT NULL UNIQUE,
+                visit_count INTEGER NOT NULL
+            );
+            CREATE TABLE history_visits (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                history_item INTEGER NOT NULL,
+                visit_time REAL NOT NULL,
+                title TEXT
+            );
+            INSERT INTO history_items (id, url, visit_count) VALUES
+                (1, 'https://news.ycombinator.com/', 2),
+                (2, 'https://example.com/untitled', 1);
+            -- 732_240_000 seconds after the Cocoa epoch is 2024-03-15
+            INSERT INTO history_visits (history_item, visit_time, title) VALUES
+                (1, 732200000.0, 'Old Title'),
+                (1, 732240000.0, 'Hacker News'),
+                (2, 732100000.5, NULL);",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_bookmark_links() -> Result<()> {
+        let profile_dir = tempfile::tempdir()?;
+        std::fs::write(profile_dir.path().join("Bookmarks.plist"), BOOKMARKS_PLIST)?;
+        let browser = Browser::new().with_profile_dir(profile_dir.path().to_path_buf());
+
+        let links = browser.bookmark_links()?;
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].title, "The Rust Book");
+        assert_eq!(links[0].subtitle, Some("/Favorites/Rust".to_string()));
+        assert_eq!(
+            links[0].guid.as_deref(),
+            Some("6C2E3F4A-0000-4000-8000-000000000001")
+        );
+        assert_eq!(links[1].title, "https://www.sqlite.org/");
+        assert_eq!(links[1].subtitle, Some("/Bookmarks Menu".to_string()));
+        assert!(links
+            .iter()
+            .all(|l| l.source == Some(SOURCE.to_string()) && l.kind == Some(LinkKind::Bookmark)));
+
+        let mut cache = create_test_cache();
+        assert_eq!(browser.cache_bookmarks(&mut cache)?, 2);
+        assert_eq!(cache.search("Rust Book")?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_history_links() -> Result<()> {
+        let profile_dir = tempfile::tempdir()?;
+        build_history_fixture(&profile_dir.path().join("History.db"))?;
+        let browser = Browser::new().with_profile_dir(profile_dir.path().to_path_buf());
+
+        let mut cache = create_test_cache();
+        assert_eq!(browser.cache_history(&mut cache)?, 2);
+        let links = browser.history_links()?;
+        assert_eq!(links[0].title, "https://example.com/untitled");
+        let hn = &links[1];
+        assert_eq!(hn.title, "Hacker News");
+        assert_eq!(hn.kind, Some(LinkKind::History));
+        assert_eq!(hn.visit_count, Some(2));
+        assert_eq!(hn.timestamp.timestamp(), 1_710_547_200);
+        assert_eq!(cache.search("Hacker News")?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_cocoa_time() {
+        assert_eq!(from_cocoa_time(0.0).timestamp(), 978_307_200);
+        assert_eq!(from_cocoa_time(1.5).timestamp_millis(), 978_307_201_500);
+    }
+}
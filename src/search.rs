@@ -0,0 +1,2590 @@
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use log::{info, warn};
+use rusqlite::{params_from_iter, Connection, ErrorCode};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::cache::{link_from_row, LINK_COLUMNS, LINK_COLUMN_COUNT, UNEXPIRED};
+use crate::link::{host_of, is_absolute_url, normalize_url};
+use crate::query::QueryFilters;
+use crate::{Cache, Error, Link, LinkKind, Result};
+
+/// How search results are ordered. FTS5's rank column requires a reasonably
+/// modern SQLite, so the best available strategy is probed once when the
+/// Cache is built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RankStrategy {
+    /// ORDER BY the FTS5 rank column, which uses the bm25 weights configured
+    /// on the links_fts table.
+    Rank,
+    /// ORDER BY an explicit bm25() call with the same column weights
+    Bm25,
+    /// No relevance ranking is available, so matches are ordered newest first
+    Recency,
+}
+
+impl RankStrategy {
+    /// Returns the best strategy the connection's SQLite supports, logging a
+    /// warning when falling back from the FTS5 rank column.
+    pub(crate) fn probe(conn: &Connection) -> Self {
+        let supports = |expr: &str| {
+            conn.prepare(&format!(
+                "SELECT {expr} FROM links_fts WHERE links_fts MATCH 'probe' LIMIT 0"
+            ))
+            .and_then(|mut stmt| stmt.query([]).map(|_| ()))
+            .is_ok()
+        };
+
+        if supports("rank") {
+            RankStrategy::Rank
+        } else if supports(BM25) {
+            warn!("FTS5 rank is unavailable; falling back to ordering by bm25()");
+            RankStrategy::Bm25
+        } else {
+            warn!("FTS5 ranking is unavailable; search results will be ordered by recency");
+            RankStrategy::Recency
+        }
+    }
+
+    /// The SQL expression selected as each result's rank, where more negative
+    /// is a better match.
+    fn score_expr(&self) -> &'static str {
+        match self {
+            RankStrategy::Rank => "rank",
+            RankStrategy::Bm25 => BM25,
+            RankStrategy::Recency => "0.0",
+        }
+    }
+
+    /// The ORDER BY clause for results which select the boosted rank as
+    /// weighted_rank. Ties are broken by url, so equally-ranked links come
+    /// back in the same order every time.
+    fn order_by(&self) -> &'static str {
+        match self {
+            RankStrategy::Rank | RankStrategy::Bm25 => "weighted_rank, links.url",
+            RankStrategy::Recency => "links.timestamp DESC, links.url",
+        }
+    }
+}
+
+/// The order of search results.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SearchOrder {
+    /// Best match first (the order used by Cache::search)
+    #[default]
+    Relevance,
+    /// Newest first, by the provided timestamp
+    Recency(RecencyField),
+}
+
+/// The timestamp used to order results by recency.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RecencyField {
+    /// When the link was imported (or added)
+    #[default]
+    Timestamp,
+    /// When the link was last opened via Cache::record_open. Links which have
+    /// never been opened come last, newest import first.
+    LastOpenedAt,
+}
+
+impl RecencyField {
+    fn order_by(&self) -> &'static str {
+        match self {
+            RecencyField::Timestamp => "links.timestamp DESC, links.url",
+            RecencyField::LastOpenedAt => {
+                "links.last_opened_at IS NULL, links.last_opened_at DESC, links.timestamp DESC, \
+                 links.url"
+            }
+        }
+    }
+}
+
+/// The indexed columns of a link which a search can match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    Url,
+    Title,
+    /// The folder path (or other context) the link was found under
+    Subtitle,
+    Source,
+    Author,
+    Description,
+}
+
+impl Field {
+    /// Every field, in the order of their columns in links_fts.
+    const ALL: [Field; 6] = [
+        Field::Url,
+        Field::Title,
+        Field::Subtitle,
+        Field::Source,
+        Field::Author,
+        Field::Description,
+    ];
+
+    /// The column of the links table holding this field.
+    fn column(&self) -> &'static str {
+        match self {
+            Field::Url => "links.url",
+            Field::Title => "links.title",
+            Field::Subtitle => "links.subtitle",
+            Field::Source => "links.source",
+            Field::Author => "links.author",
+            Field::Description => "links.description",
+        }
+    }
+
+    /// Sets this field of the link from the value of its column.
+    fn set(&self, link: &mut Link, value: Option<String>) {
+        match self {
+            Field::Url => link.url = value.unwrap_or_default(),
+            Field::Title => link.title = value.unwrap_or_default(),
+            Field::Subtitle => link.subtitle = value,
+            Field::Source => link.source = value,
+            Field::Author => link.author = value,
+            Field::Description => link.description = value,
+        }
+    }
+}
+
+/// How long ago a link's timestamp was, in the local timezone, as used to
+/// group the results of Cache::search_bucketed. Ordered most recent first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RecencyBucket {
+    Today,
+    Yesterday,
+    /// Earlier than yesterday, but within the last seven days
+    ThisWeek,
+    Older,
+}
+
+impl RecencyBucket {
+    /// Returns the bucket of a timestamp, relative to the provided local date.
+    fn of(timestamp: DateTime<Utc>, today: NaiveDate) -> RecencyBucket {
+        let days_ago = (today - timestamp.with_timezone(&Local).date_naive()).num_days();
+        match days_ago {
+            ..=0 => RecencyBucket::Today,
+            1 => RecencyBucket::Yesterday,
+            2..=6 => RecencyBucket::ThisWeek,
+            _ => RecencyBucket::Older,
+        }
+    }
+
+    /// Returns a heading for the bucket, e.g. "This Week".
+    pub fn label(&self) -> &'static str {
+        match self {
+            RecencyBucket::Today => "Today",
+            RecencyBucket::Yesterday => "Yesterday",
+            RecencyBucket::ThisWeek => "This Week",
+            RecencyBucket::Older => "Older",
+        }
+    }
+}
+
+/// A search result along with the fields which matched the query, so a
+/// client can distinguish e.g. a folder match from a title match.
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub link: Link,
+    pub matched_fields: Vec<Field>,
+}
+
+/// Drops the links beyond the limit, if there is one.
+fn truncated(mut links: Vec<Link>, limit: Option<u32>) -> Vec<Link> {
+    if let Some(limit) = limit {
+        links.truncate(limit as usize);
+    }
+    links
+}
+
+/// Describes how long before now the timestamp was, in the largest whole
+/// unit, e.g. "3 days ago". Timestamps within the last minute (or in the
+/// future) are "just now".
+fn relative_time(timestamp: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let elapsed = now - timestamp;
+    let (count, unit) = if elapsed.num_days() >= 365 {
+        (elapsed.num_days() / 365, "year")
+    } else if elapsed.num_days() >= 30 {
+        (elapsed.num_days() / 30, "month")
+    } else if elapsed.num_days() >= 1 {
+        (elapsed.num_days(), "day")
+    } else if elapsed.num_hours() >= 1 {
+        (elapsed.num_hours(), "hour")
+    } else if elapsed.num_minutes() >= 1 {
+        (elapsed.num_minutes(), "minute")
+    } else {
+        return "just now".to_string();
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{} {}{} ago", count, unit, plural)
+}
+
+/// The number of days after which search_by_frecency halves a page's
+/// weight.
+const FRECENCY_HALF_LIFE_DAYS: f64 = 30.0;
+
+/// Combines a link's search relevance with how often and how recently it
+/// was visited (see Cache::search_by_frecency).
+fn frecency(relevance: f32, link: &Link, now: DateTime<Utc>) -> f32 {
+    let visits = f64::from(link.visit_count.unwrap_or(0));
+    let age_days = ((now - link.timestamp).num_seconds() as f64 / 86_400.0).max(0.0);
+    let weight = (1.0 + visits.ln_1p()) / (1.0 + age_days / FRECENCY_HALF_LIFE_DAYS);
+    (f64::from(relevance) * weight) as f32
+}
+
+/// A SQL expression for the authority (host, plus any port) of links.url:
+/// everything between "://" and the next "/".
+const HOST_EXPR: &str = "substr(substr(links.url, instr(links.url, '://') + 3), 1, \
+     instr(substr(links.url, instr(links.url, '://') + 3) || '/', '/') - 1)";
+
+/// How many SQLite virtual machine instructions run between checks of
+/// search_with_timeout's deadline.
+const TIMEOUT_CHECK_OPS: i32 = 1000;
+
+/// How much a title which starts with one of the query's words boosts a
+/// result's rank. A word found later in the title gets this bonus divided by
+/// its (1-based) position, so matches near the start still count for more.
+const TITLE_POSITION_BONUS: f64 = 0.5;
+
+/// The most query words whose position in titles is weighed, which bounds
+/// the work done per result for long queries.
+const MAX_TITLE_WORDS: usize = 8;
+
+/// How many links search() returns at most.
+pub const DEFAULT_SEARCH_LIMIT: u32 = 50;
+
+/// The explicit equivalent of the rank configured on links_fts. The weights
+/// must be kept in step with the latest migration which sets them.
+const BM25: &str = "bm25(links_fts, 1.0, 10.0, 5.0, 1.0, 1.0, 2.0, 0.0, 1.0)";
+
+impl Cache {
+    /// Searches the index for linkx matching the query. A query which is a
+    /// full URL (e.g. pasted from the clipboard) is looked up literally: the
+    /// exact link comes first, followed by other links from the same host.
+    /// The query may narrow the results with site:, after:, before:,
+    /// source: and title: operators (e.g. "site:github.com title:issues
+    /// after:2024-01-01 rusqlite"). Ranked results' scores are normalized so
+    /// the best match scores 1.0 and the rest are relative to it (see
+    /// search_detailed for the raw scores). At most DEFAULT_SEARCH_LIMIT
+    /// links are returned (see search_limited for other limits).
+    pub fn search(&self, query: &str) -> Result<Vec<Link>> {
+        self.search_up_to(query, Some(DEFAULT_SEARCH_LIMIT))
+    }
+
+    /// Searches like search(), but returns at most limit links, e.g. the
+    /// number of items a launcher has room to show. Ranked queries only
+    /// fetch the rows they return. The limit may be given per query as a
+    /// number or an Option, where None returns every ranked match (and the
+    /// 50 latest links for an empty query).
+    pub fn search_limited(&self, query: &str, limit: impl Into<Option<u32>>) -> Result<Vec<Link>> {
+        self.search_up_to(query, limit.into())
+    }
+
+    /// Searches for up to limit links (when there is one), applying the
+    /// presentation options to them.
+    fn search_up_to(&self, query: &str, limit: Option<u32>) -> Result<Vec<Link>> {
+        let mut links = self.search_links(query, limit, None)?;
+        self.present(&mut links);
+        Ok(links)
+    }
+
+    /// Applies the presentation options (e.g. relative_time_subtitles) to
+    /// links about to be returned by a search.
+    fn present(&self, links: &mut [Link]) {
+        if self.options.relative_time_subtitles {
+            let now = Utc::now();
+            for link in links
+                .iter_mut()
+                .filter(|link| link.kind == Some(LinkKind::History))
+            {
+                link.subtitle = Some(format!(
+                    "last visited {}",
+                    relative_time(link.timestamp, now)
+                ));
+            }
+        }
+    }
+
+    /// Finds up to limit of the links search() returns (only from the
+    /// provided source, when there is one), before any presentation options
+    /// are applied to them. Without a limit, the latest links are capped at
+    /// DEFAULT_SEARCH_LIMIT and ranked matches aren't capped.
+    fn search_links(
+        &self,
+        query: &str,
+        limit: Option<u32>,
+        source: Option<&str>,
+    ) -> Result<Vec<Link>> {
+        let latest = limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
+        if query.is_empty() {
+            return self.latest_links(latest, source);
+        }
+
+        if is_absolute_url(query) {
+            let links = self.search_url(query, source)?;
+            if !links.is_empty() {
+                return Ok(truncated(links, limit));
+            }
+        }
+
+        if let Some(filters) = QueryFilters::parse(query) {
+            return Ok(truncated(self.search_filtered(&filters, source)?, limit));
+        }
+
+        let query = self.prepare_query(query);
+        if query.is_empty() {
+            return self.latest_links(latest, source);
+        }
+        let mut links = match self.search_page_in(&query, limit, 0, source) {
+            Err(err) if is_corrupt_index(&err) => {
+                truncated(self.search_after_corruption(&query, source, err)?, limit)
+            }
+            result => result?,
+        };
+        normalize_scores(&mut links);
+        Ok(links)
+    }
+
+    /// Recovers a search which failed because links_fts is corrupt (e.g.
+    /// after an interrupted write) by rebuilding the index and retrying once.
+    /// When the index still can't be searched, the links table is scanned
+    /// instead, so searches keep working (unranked) until it's repaired.
+    fn search_after_corruption(
+        &self,
+        query: &str,
+        source: Option<&str>,
+        err: Error,
+    ) -> Result<Vec<Link>> {
+        warn!("The search index is corrupt ({}); rebuilding it", err);
+        match self
+            .reindex()
+            .and_then(|_| self.search_page_in(query, None, 0, source))
+        {
+            Ok(links) => {
+                info!("Rebuilt the corrupt search index");
+                Ok(links)
+            }
+            Err(err) => {
+                warn!(
+                    "The search index is still unusable ({}); falling back to scanning links",
+                    err
+                );
+                self.search_links_table(query, source)
+            }
+        }
+    }
+
+    /// Searches the links table directly, without the FTS index, for links
+    /// whose url, title, subtitle or description contains every term of the
+    /// query (and which come from the provided source, when there is one).
+    /// Matches are ordered newest first and have no score.
+    fn search_links_table(&self, query: &str, source: Option<&str>) -> Result<Vec<Link>> {
+        let terms: Vec<String> = query
+            .split_whitespace()
+            .map(|term| {
+                term.trim_matches('"')
+                    .replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_")
+            })
+            .filter(|term| !term.is_empty())
+            .collect();
+        let conditions: String = (0..terms.len())
+            .map(|i| {
+                format!(
+                    " AND (links.url || ' ' || links.title || ' ' || ifnull(links.subtitle, '') \
+                     || ' ' || ifnull(links.description, '')) LIKE '%' || ?{} || '%' ESCAPE '\\'",
+                    i + 2
+                )
+            })
+            .collect();
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {LINK_COLUMNS}
+             FROM links
+             WHERE links.collection = ?1 AND links.deleted_at IS NULL AND {UNEXPIRED}
+             AND (?{source_index} IS NULL OR links.source = ?{source_index}){conditions}
+             ORDER BY links.timestamp DESC, links.url
+             LIMIT {DEFAULT_SEARCH_LIMIT}",
+            source_index = terms.len() + 2,
+        ))?;
+        let params = std::iter::once(Some(self.options.collection().to_string()))
+            .chain(terms.into_iter().map(Some))
+            .chain(std::iter::once(source.map(str::to_string)));
+        let links_iter = stmt.query_map(params_from_iter(params), link_from_row)?;
+        links_iter
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()
+            .map_err(|e| e.into())
+    }
+
+    /// Searches like search(), but gives up with Error::Timeout once the
+    /// search has run for longer than the timeout, so a pathological query
+    /// on a large cache can't hang the caller.
+    pub fn search_with_timeout(&self, query: &str, timeout: Duration) -> Result<Vec<Link>> {
+        let deadline = Instant::now() + timeout;
+        self.conn
+            .progress_handler(TIMEOUT_CHECK_OPS, Some(move || Instant::now() >= deadline))?;
+        let result = self.search(query);
+        self.conn.progress_handler(0, None::<fn() -> bool>)?;
+
+        match result {
+            Err(Error::Rusqlite(rusqlite::Error::SqliteFailure(e, _)))
+                if e.code == ErrorCode::OperationInterrupted =>
+            {
+                Err(Error::Timeout)
+            }
+            result => result,
+        }
+    }
+
+    /// Searches for links whose host contains the host hint (e.g. "github"
+    /// matches github.com and gist.github.com) and which match the text,
+    /// ranked like search(). With empty text, the most recent links from
+    /// matching hosts are returned. search() accepts the same thing as
+    /// "site:github.com issues".
+    pub fn search_host_and_text(&self, host_hint: &str, text: &str) -> Result<Vec<Link>> {
+        self.search_filtered(
+            &QueryFilters {
+                site: Some(host_hint),
+                text: text.to_string(),
+                ..Default::default()
+            },
+            None,
+        )
+    }
+
+    /// Searches for links which pass the filters of a query written with
+    /// operators (see QueryFilters) and match its text, ranked like search().
+    /// Without text (or title: terms), the most recent links which pass the
+    /// filters are returned.
+    fn search_filtered(&self, filters: &QueryFilters, source: Option<&str>) -> Result<Vec<Link>> {
+        let host_hint = filters.site.map(|host_hint| {
+            host_hint
+                .to_lowercase()
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        });
+        let conditions = format!(
+            "AND (?3 IS NULL OR lower({HOST_EXPR}) LIKE '%' || ?3 || '%' ESCAPE '\\')
+             AND (?4 IS NULL OR links.timestamp >= ?4)
+             AND (?5 IS NULL OR links.timestamp < ?5)
+             AND (?6 IS NULL OR lower(links.source) = lower(?6))
+             AND (?7 IS NULL OR links.source = ?7)"
+        );
+        let text = filters.match_text(&self.prepare_query(&filters.text));
+        if text.is_empty() {
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT {LINK_COLUMNS}
+                 FROM links
+                 WHERE links.collection = ?2
+                 AND links.deleted_at IS NULL AND {UNEXPIRED}
+                 {conditions}
+                 ORDER BY links.timestamp DESC, links.url
+                 LIMIT ?1",
+            ))?;
+            let params = (
+                DEFAULT_SEARCH_LIMIT,
+                self.options.collection(),
+                &host_hint,
+                filters.after,
+                filters.before,
+                filters.source,
+                source,
+            );
+            let links_iter = stmt.query_map(params, link_from_row)?;
+            return links_iter
+                .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()
+                .map_err(|e| e.into());
+        }
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {LINK_COLUMNS}, {weighted_rank} AS weighted_rank
+             FROM links_fts
+             JOIN links ON links_fts.url = links.url
+                 AND links_fts.collection = links.collection
+             WHERE links_fts MATCH ?1 AND links_fts.collection = ?2
+             AND links.deleted_at IS NULL AND {UNEXPIRED}
+             {conditions}
+             ORDER BY {order_by}",
+            weighted_rank = self.weighted_rank_expr(),
+            order_by = self.rank_strategy.order_by(),
+        ))?;
+        let params = (
+            &text,
+            self.options.collection(),
+            &host_hint,
+            filters.after,
+            filters.before,
+            filters.source,
+            source,
+        );
+        let links_iter = stmt.query_map(params, |row| {
+            let mut link = link_from_row(row)?;
+            let rank: f64 = row.get(LINK_COLUMN_COUNT)?;
+            link.score = Some(-rank as f32);
+            Ok(link)
+        })?;
+        let mut links = links_iter.collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+        normalize_scores(&mut links);
+        Ok(links)
+    }
+
+    /// Searches the index for links matching the query, in the provided
+    /// order. An empty query returns the 50 most recent links by the order's
+    /// timestamp.
+    pub fn search_ordered(&self, query: &str, order: SearchOrder) -> Result<Vec<Link>> {
+        let field = match order {
+            SearchOrder::Relevance => return self.search(query),
+            SearchOrder::Recency(field) => field,
+        };
+
+        let order_by = field.order_by();
+        let query = self.prepare_query(query);
+        let links = if query.is_empty() {
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT {LINK_COLUMNS}
+                 FROM links
+                 WHERE links.collection = ?1 AND links.deleted_at IS NULL AND {UNEXPIRED}
+                 ORDER BY {order_by}
+                 LIMIT 50",
+            ))?;
+            let links_iter = stmt.query_map([self.options.collection()], link_from_row)?;
+            links_iter.collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?
+        } else {
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT {LINK_COLUMNS}
+                 FROM links_fts
+                 JOIN links ON links_fts.url = links.url
+                     AND links_fts.collection = links.collection
+                 WHERE links_fts MATCH ?1 AND links_fts.collection = ?2
+                 AND links.deleted_at IS NULL AND {UNEXPIRED}
+                 ORDER BY {order_by}",
+            ))?;
+            let links_iter =
+                stmt.query_map([query.as_str(), self.options.collection()], link_from_row)?;
+            links_iter.collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?
+        };
+        Ok(links)
+    }
+
+    /// Yields the results search_limited(query, None) returns, so unlike
+    /// search() the iterator isn't capped at DEFAULT_SEARCH_LIMIT: it runs
+    /// through every ranked match (and the 50 latest links for an empty
+    /// query). Ranked results are fetched from the database a page at a
+    /// time as the iterator is consumed, so clients which stream results (or
+    /// stop after the first few) avoid materializing every match. The
+    /// presentation options apply as they do to search().
+    pub fn search_iter(&self, query: &str) -> Result<impl Iterator<Item = Result<Link>> + '_> {
+        let Some(fts_query) = self.paged_query(query) else {
+            // URLs, operators and the latest links aren't paged
+            let links = self.search_limited(query, None)?;
+            return Ok(SearchIter {
+                cache: self,
+                query: None,
+                offset: 0,
+                max_score: None,
+                buffer: links.into(),
+            });
+        };
+        Ok(SearchIter {
+            cache: self,
+            query: Some(fts_query),
+            offset: 0,
+            max_score: None,
+            buffer: VecDeque::new(),
+        })
+    }
+
+    /// Returns a page of up to limit ranked results for the query, starting
+    /// after the result with the provided rank and url (the score and url of
+    /// the last result of the previous page), or from the best match when no
+    /// rank is provided. Unlike an offset, the cursor stays put when links
+    /// are added between page fetches, so pages neither skip nor repeat
+    /// results. Scores are raw (as in search_detailed) so they can be passed
+    /// back as the cursor. Ties are broken by url rather than guid, since
+    /// every link has a url but not every link has a guid. An empty (or
+    /// stopword-only) query has no ranked results.
+    pub fn search_after(
+        &self,
+        query: &str,
+        after_rank: Option<f32>,
+        after_url: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<Link>> {
+        let query = self.prepare_query(query);
+        if query.is_empty() {
+            return Ok(vec![]);
+        }
+
+        // Ranks are rounded to f32 so the cursor compares equal to the rank
+        // of the result it was taken from. Without a url, a NULL makes every
+        // link tied with the cursor's rank compare as not after it.
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {LINK_COLUMNS}, to_f32({weighted_rank}) AS weighted_rank
+             FROM links_fts
+             JOIN links ON links_fts.url = links.url
+                 AND links_fts.collection = links.collection
+             WHERE links_fts MATCH ?1 AND links_fts.collection = ?3
+             AND links.deleted_at IS NULL AND {UNEXPIRED}
+             AND (?4 IS NULL OR (weighted_rank, links.url) > (?4, ?5))
+             ORDER BY weighted_rank, links.url
+             LIMIT ?2",
+            weighted_rank = self.weighted_rank_expr(),
+        ))?;
+        // FTS5's bm25 rank is the negated score
+        let after_rank = after_rank.map(|score| -f64::from(score));
+        let params = (
+            &query,
+            limit,
+            self.options.collection(),
+            after_rank,
+            after_url,
+        );
+        let links_iter = stmt.query_map(params, |row| {
+            let mut link = link_from_row(row)?;
+            let rank: f64 = row.get(LINK_COLUMN_COUNT)?;
+            link.score = Some(-rank as f32);
+            Ok(link)
+        })?;
+
+        links_iter
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()
+            .map_err(|e| e.into())
+    }
+
+    /// Returns only the highest-ranked result of search(), or None when
+    /// nothing matches. Ranked queries fetch just that one row.
+    pub fn best_match(&self, query: &str) -> Result<Option<Link>> {
+        let links = match self.paged_query(query) {
+            Some(fts_query) => {
+                let mut links = self.search_page(&fts_query, Some(1), 0)?;
+                normalize_scores(&mut links);
+                links
+            }
+            None => self.search(query)?,
+        };
+        Ok(links.into_iter().next())
+    }
+
+    /// Searches like search(), but only returns links from the provided
+    /// source (e.g. "firefox"), so a client can offer a search scoped to one
+    /// browser. An empty (or stopword-only) query returns the source's latest
+    /// links.
+    pub fn search_in_source(&self, query: &str, source: &str) -> Result<Vec<Link>> {
+        let mut links = self.search_links(query, Some(DEFAULT_SEARCH_LIMIT), Some(source))?;
+        self.present(&mut links);
+        Ok(links)
+    }
+
+    /// Searches like search(), but leaves out links whose url is one of the
+    /// excluded urls (e.g. tabs which are already open). Urls are compared
+    /// after normalization, so fragments, tracking parameters and the case
+    /// of the scheme and host don't prevent a match.
+    pub fn search_excluding_urls(&self, query: &str, excluded: &[&str]) -> Result<Vec<Link>> {
+        let excluded: HashSet<String> = excluded.iter().map(|url| normalize_url(url)).collect();
+        let mut links = self.search(query)?;
+        links.retain(|link| !excluded.contains(&normalize_url(&link.url)));
+        Ok(links)
+    }
+
+    /// Searches like search(), but only reads the requested fields of each
+    /// link (e.g. the title, url and subtitle a result list shows), leaving
+    /// the rest defaulted. The score is still set on ranked results. An
+    /// empty (or stopword-only) query returns the latest links.
+    pub fn search_projected(&self, query: &str, fields: &[Field]) -> Result<Vec<Link>> {
+        let columns: String = fields
+            .iter()
+            .map(|field| format!("{}, ", field.column()))
+            .collect();
+        let project = |row: &rusqlite::Row| {
+            let mut link = Link::default();
+            for (index, field) in fields.iter().enumerate() {
+                field.set(&mut link, row.get(index)?);
+            }
+            Ok(link)
+        };
+
+        let query = self.prepare_query(query);
+        if query.is_empty() {
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT {columns}links.timestamp
+                 FROM links
+                 WHERE links.collection = ?1 AND links.deleted_at IS NULL AND {UNEXPIRED}
+                 ORDER BY links.timestamp DESC, links.url
+                 LIMIT 50",
+            ))?;
+            let links_iter = stmt.query_map([self.options.collection()], project)?;
+            return links_iter
+                .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()
+                .map_err(|e| e.into());
+        }
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {columns}{weighted_rank} AS weighted_rank
+             FROM links_fts
+             JOIN links ON links_fts.url = links.url
+                 AND links_fts.collection = links.collection
+             WHERE links_fts MATCH ?1 AND links_fts.collection = ?2
+             AND links.deleted_at IS NULL AND {UNEXPIRED}
+             ORDER BY {order_by}",
+            weighted_rank = self.weighted_rank_expr(),
+            order_by = self.rank_strategy.order_by(),
+        ))?;
+        let links_iter = stmt.query_map([query.as_str(), self.options.collection()], |row| {
+            let mut link = project(row)?;
+            let rank: f64 = row.get(fields.len())?;
+            link.score = Some(-rank as f32);
+            Ok(link)
+        })?;
+        let mut links = links_iter.collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+        normalize_scores(&mut links);
+        Ok(links)
+    }
+
+    /// Counts the links matching the query by source (e.g. "firefox" => 12,
+    /// "chrome" => 3), as search() would match them, without fetching the
+    /// links themselves. Links without a source are counted under "". An
+    /// empty (or stopword-only) query counts every link.
+    pub fn search_facets(&self, query: &str) -> Result<HashMap<String, u64>> {
+        let facet = |row: &rusqlite::Row| Ok((row.get(0)?, row.get::<_, i64>(1)? as u64));
+        let query = self.prepare_query(query);
+        let counts = if query.is_empty() {
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT COALESCE(links.source, ''), COUNT(*)
+                 FROM links
+                 WHERE links.collection = ?1 AND links.deleted_at IS NULL AND {UNEXPIRED}
+                 GROUP BY 1",
+            ))?;
+            let counts = stmt.query_map([self.options.collection()], facet)?;
+            counts.collect::<std::result::Result<HashMap<_, _>, rusqlite::Error>>()?
+        } else {
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT COALESCE(links.source, ''), COUNT(*)
+                 FROM links_fts
+                 JOIN links ON links_fts.url = links.url
+                     AND links_fts.collection = links.collection
+                 WHERE links_fts MATCH ?1 AND links_fts.collection = ?2
+                 AND links.deleted_at IS NULL AND {UNEXPIRED}
+                 GROUP BY 1",
+            ))?;
+            let counts = stmt.query_map([query.as_str(), self.options.collection()], facet)?;
+            counts.collect::<std::result::Result<HashMap<_, _>, rusqlite::Error>>()?
+        };
+        Ok(counts)
+    }
+
+    /// Searches for links containing every term, ranking those where the
+    /// terms appear close together (e.g. "release notes" rather than
+    /// "release schedule ... meeting notes") above those where they're far
+    /// apart. The index is made of character trigrams, so distance is
+    /// roughly the number of characters allowed between two terms. Terms
+    /// are matched as phrases, and an empty list returns the latest links.
+    pub fn search_near(&self, terms: &[&str], distance: u32) -> Result<Vec<Link>> {
+        let phrases: Vec<String> = terms
+            .iter()
+            .map(|term| fold_query(term.trim()))
+            .filter(|term| !term.is_empty())
+            .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+            .collect();
+        if phrases.is_empty() {
+            return self.get_latest_n(50);
+        }
+
+        let near_query = format!("NEAR({}, {})", phrases.join(" "), distance);
+        let mut links = self.search_page(&near_query, None, 0)?;
+        normalize_scores(&mut links);
+
+        // Matches whose terms are far apart follow every near match, scaled
+        // to at most half the lowest near match's score
+        let lowest_near = links
+            .iter()
+            .filter_map(|link| link.score)
+            .fold(1.0, f32::min);
+        let near_urls: HashSet<String> = links.iter().map(|link| link.url.clone()).collect();
+        let mut far = self.search_page(&phrases.join(" AND "), None, 0)?;
+        far.retain(|link| !near_urls.contains(&link.url));
+        normalize_scores(&mut far);
+        for mut link in far {
+            link.score = link.score.map(|score| score * lowest_near * 0.5);
+            links.push(link);
+        }
+        Ok(links)
+    }
+
+    /// Returns the FTS query search() would run through search_page() for
+    /// the provided query, or None when search() handles the query another
+    /// way (empty, a URL, operators or only stopwords).
+    fn paged_query(&self, query: &str) -> Option<String> {
+        if query.is_empty() || is_absolute_url(query) || QueryFilters::parse(query).is_some() {
+            return None;
+        }
+        Some(self.prepare_query(query)).filter(|query| !query.is_empty())
+    }
+
+    /// Searches like search(), but ranks the matches by frecency, as browser
+    /// address bars do, so pages which are visited often and recently come
+    /// first. Each match's score is
+    ///
+    /// relevance * (1 + ln(1 + visit_count)) / (1 + age_days / 30)
+    ///
+    /// where relevance is its (boosted) bm25 score, visit_count is 0 when
+    /// unknown, and age_days is the fractional number of days since its
+    /// timestamp (0 for timestamps in the future). A page loses half its
+    /// weight after 30 days, so a page visited many times can still outrank
+    /// a newer one which was rarely visited. Ties are ordered by url, and
+    /// scores are normalized like search()'s. An empty (or stopword-only)
+    /// query returns the latest links.
+    pub fn search_by_frecency(&self, query: &str) -> Result<Vec<Link>> {
+        self.search_by_frecency_at(query, Utc::now())
+    }
+
+    /// Runs search_by_frecency() with ages measured from the provided time.
+    fn search_by_frecency_at(&self, query: &str, now: DateTime<Utc>) -> Result<Vec<Link>> {
+        let query = self.prepare_query(query);
+        if query.is_empty() {
+            return self.get_latest_n(50);
+        }
+        let mut links = self.search_page(&query, None, 0)?;
+        for link in &mut links {
+            link.score = link.score.map(|relevance| frecency(relevance, link, now));
+        }
+        links.sort_by(|a, b| {
+            let score = |link: &Link| link.score.unwrap_or_default();
+            score(b)
+                .total_cmp(&score(a))
+                .then_with(|| a.url.cmp(&b.url))
+        });
+        normalize_scores(&mut links);
+        Ok(links)
+    }
+
+    /// Searches like search(), grouping the results by how recent their
+    /// timestamp is (see RecencyBucket). Buckets are returned most recent
+    /// first, each keeping the order of search(), and empty buckets are
+    /// left out.
+    pub fn search_bucketed(&self, query: &str) -> Result<Vec<(RecencyBucket, Vec<Link>)>> {
+        let today = Local::now().date_naive();
+        let mut buckets: Vec<(RecencyBucket, Vec<Link>)> = vec![];
+        for link in self.search(query)? {
+            let bucket = RecencyBucket::of(link.timestamp, today);
+            match buckets.iter_mut().find(|(b, _)| *b == bucket) {
+                Some((_, links)) => links.push(link),
+                None => buckets.push((bucket, vec![link])),
+            }
+        }
+        buckets.sort_by_key(|(bucket, _)| *bucket);
+        Ok(buckets)
+    }
+
+    /// Searches like search(), additionally reporting which fields of each
+    /// link matched the query. An empty (or stopword-only) query returns the
+    /// latest links, with no matched fields.
+    pub fn search_detailed(&self, query: &str) -> Result<Vec<SearchResult>> {
+        let query = self.prepare_query(query);
+        if query.is_empty() {
+            return Ok(self
+                .get_latest_n(50)?
+                .into_iter()
+                .map(|link| SearchResult {
+                    link,
+                    matched_fields: vec![],
+                })
+                .collect());
+        }
+
+        // highlight() marks the matched text of a column, so a column matched
+        // when its highlighted text contains the opening marker.
+        let matched: String = (0..Field::ALL.len())
+            .map(|column| {
+                format!(
+                    ", COALESCE(instr(highlight(links_fts, {column}, char(1), ''), char(1)) > 0, 0)"
+                )
+            })
+            .collect();
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {LINK_COLUMNS}, {weighted_rank} AS weighted_rank{matched}
+             FROM links_fts
+             JOIN links ON links_fts.url = links.url
+                 AND links_fts.collection = links.collection
+             WHERE links_fts MATCH ?1 AND links_fts.collection = ?2
+             AND links.deleted_at IS NULL AND {UNEXPIRED}
+             ORDER BY {order_by}",
+            weighted_rank = self.weighted_rank_expr(),
+            order_by = self.rank_strategy.order_by(),
+        ))?;
+
+        let results_iter = stmt.query_map((&query, self.options.collection()), |row| {
+            let mut link = link_from_row(row)?;
+            let rank: f64 = row.get(LINK_COLUMN_COUNT)?;
+            link.score = Some(-rank as f32);
+
+            let mut matched_fields = vec![];
+            for (index, field) in Field::ALL.iter().enumerate() {
+                if row.get::<_, bool>(LINK_COLUMN_COUNT + 1 + index)? {
+                    matched_fields.push(*field);
+                }
+            }
+            Ok(SearchResult {
+                link,
+                matched_fields,
+            })
+        })?;
+
+        results_iter
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()
+            .map_err(|e| e.into())
+    }
+
+    /// Searches like search(), but also matches links which have been
+    /// removed (and not yet purged), pairing each link with when it was
+    /// removed, or None if it wasn't. Meant for diagnosing why a link no
+    /// longer turns up, rather than for showing to users. An empty (or
+    /// stopword-only) query returns the latest links.
+    pub fn search_including_deleted(
+        &self,
+        query: &str,
+    ) -> Result<Vec<(Link, Option<DateTime<Utc>>)>> {
+        let query = self.prepare_query(query);
+        let read_row = |row: &rusqlite::Row| {
+            let mut link = link_from_row(row)?;
+            let rank: f64 = row.get(LINK_COLUMN_COUNT)?;
+            link.score = Some(-rank as f32);
+            Ok((link, row.get(LINK_COLUMN_COUNT + 1)?))
+        };
+
+        let results = if query.is_empty() {
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT {LINK_COLUMNS}, 0.0, links.deleted_at
+                 FROM links
+                 WHERE links.collection = ?1 AND {UNEXPIRED}
+                 ORDER BY links.timestamp DESC, links.url
+                 LIMIT 50"
+            ))?;
+            let results_iter = stmt.query_map([self.options.collection()], read_row)?;
+            results_iter.collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?
+        } else {
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT {LINK_COLUMNS}, {weighted_rank} AS weighted_rank, links.deleted_at
+                 FROM links_fts
+                 JOIN links ON links_fts.url = links.url
+                     AND links_fts.collection = links.collection
+                 WHERE links_fts MATCH ?1 AND links_fts.collection = ?2
+                 AND {UNEXPIRED}
+                 ORDER BY {order_by}",
+                weighted_rank = self.weighted_rank_expr(),
+                order_by = self.rank_strategy.order_by(),
+            ))?;
+            let results_iter = stmt.query_map((&query, self.options.collection()), read_row)?;
+            results_iter.collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?
+        };
+        Ok(results)
+    }
+
+    /// Runs the ranked FTS query, returning up to limit results (or all of
+    /// them when None) after skipping offset results. Like the query itself,
+    /// the limit and offset are always bound as parameters rather than
+    /// formatted into the SQL.
+    fn search_page(&self, query: &str, limit: Option<u32>, offset: u32) -> Result<Vec<Link>> {
+        self.search_page_in(query, limit, offset, None)
+    }
+
+    /// Runs search_page(), only matching links from the provided source when
+    /// there is one.
+    fn search_page_in(
+        &self,
+        query: &str,
+        limit: Option<u32>,
+        offset: u32,
+        source: Option<&str>,
+    ) -> Result<Vec<Link>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {LINK_COLUMNS}, {weighted_rank} AS weighted_rank
+             FROM links_fts
+             JOIN links ON links_fts.url = links.url
+                 AND links_fts.collection = links.collection
+             WHERE links_fts MATCH ?1 AND links_fts.collection = ?4
+             AND links.deleted_at IS NULL AND {UNEXPIRED}
+             AND (?5 IS NULL OR links.source = ?5)
+             ORDER BY {order_by}
+             LIMIT ?2 OFFSET ?3",
+            weighted_rank = self.weighted_rank_expr(),
+            order_by = self.rank_strategy.order_by(),
+        ))?;
+
+        // A negative LIMIT means no limit in SQLite
+        let limit = limit.map(i64::from).unwrap_or(-1);
+        let params = (query, limit, offset, self.options.collection(), source);
+        let links_iter = stmt.query_map(params, |row| {
+            let mut link = link_from_row(row)?;
+            // FTS5's bm25 rank is more negative for better matches
+            let rank: f64 = row.get(LINK_COLUMN_COUNT)?;
+            link.score = Some(-rank as f32);
+            Ok(link)
+        })?;
+
+        links_iter
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()
+            .map_err(|e| e.into())
+    }
+
+    /// Returns the query as it's matched against the index: folded, and with
+    /// any configured stopwords removed. The result is empty when the query
+    /// was made up only of stopwords.
+    fn prepare_query(&self, query: &str) -> String {
+        fold_query(&self.without_stopwords(query))
+    }
+
+    /// Returns the query with any configured stopwords removed.
+    fn without_stopwords(&self, query: &str) -> String {
+        if self.options.stopwords.is_empty() {
+            return query.to_string();
+        }
+        query
+            .split_whitespace()
+            .filter(|term| {
+                let word = term.trim_matches(|c: char| !c.is_alphanumeric());
+                !self.options.stopwords.contains(&word.to_lowercase())
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Returns the link stored under this URL (or a variant of it which
+    /// normalizes the same, see normalize_url), if any.
+    pub fn get_by_url(&self, url: &str) -> Result<Option<Link>> {
+        Self::find_by_url(&self.conn, self.options.collection(), url, None)
+    }
+
+    /// Literal URL lookup: the exact link (if stored) followed by the most
+    /// recent links from the same host, only from the provided source when
+    /// there is one.
+    fn search_url(&self, url: &str, source: Option<&str>) -> Result<Vec<Link>> {
+        let mut links: Vec<Link> =
+            Self::find_by_url(&self.conn, self.options.collection(), url, source)?
+                .into_iter()
+                .collect();
+
+        if let Some(host) = host_of(url) {
+            let host = host
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT {LINK_COLUMNS}
+                 FROM links
+                 WHERE (links.url LIKE '%://' || ?1 ESCAPE '\\'
+                        OR links.url LIKE '%://' || ?1 || '/%' ESCAPE '\\'
+                        OR links.url LIKE '%://' || ?1 || '?%' ESCAPE '\\')
+                 AND links.normalized_url != normalize_url(?2)
+                 AND links.collection = ?4
+                 AND (?5 IS NULL OR links.source = ?5)
+                 AND links.deleted_at IS NULL AND {UNEXPIRED}
+                 ORDER BY links.timestamp DESC, links.url
+                 LIMIT ?3",
+            ))?;
+            let params = (
+                &host,
+                url,
+                DEFAULT_SEARCH_LIMIT,
+                self.options.collection(),
+                source,
+            );
+            let related = stmt.query_map(params, link_from_row)?;
+            for link in related {
+                links.push(link?);
+            }
+        }
+        Ok(links)
+    }
+
+    /// Returns the SQL expression for each result's rank after the source
+    /// boosts, any history age penalty and the title position bonus have
+    /// been applied. The bonus is computed by the title_position() SQL
+    /// function (see title_position), so the statement must bind the
+    /// (prepared) query it MATCHes as ?1.
+    fn weighted_rank_expr(&self) -> String {
+        format!(
+            "({}) * ({}) * ({}) * title_position(links.title, ?1)",
+            self.rank_strategy.score_expr(),
+            self.source_boost_expr(),
+            self.age_penalty_expr(),
+        )
+    }
+
+    /// Returns a SQL expression evaluating to the configured penalty for
+    /// history links older than the configured age, and 1.0 for every other
+    /// link. The cutoff is formatted the way rusqlite stores timestamps so
+    /// the two compare as text.
+    fn age_penalty_expr(&self) -> String {
+        let Some((max_age, penalty)) = self.options.history_age_penalty else {
+            return "1.0".to_string();
+        };
+        let cutoff = chrono::Duration::from_std(max_age)
+            .ok()
+            .and_then(|max_age| Utc::now().checked_sub_signed(max_age))
+            .unwrap_or(DateTime::<Utc>::MIN_UTC);
+        format!(
+            "CASE WHEN links.kind = 'history' AND links.timestamp < '{}' THEN {:?} ELSE 1.0 END",
+            cutoff.format("%F %T%.f%:z"),
+            penalty
+        )
+    }
+
+    /// Returns a SQL expression evaluating to the configured boost for each
+    /// result's source, so the boosts apply in the database's ORDER BY and
+    /// every page of results agrees with the full ranking. Boosts are set
+    /// by the application rather than the user, but sources are quoted as
+    /// SQL string literals regardless.
+    fn source_boost_expr(&self) -> String {
+        if self.options.source_boosts.is_empty() {
+            return "1.0".to_string();
+        }
+        let cases: String = self
+            .options
+            .source_boosts
+            .iter()
+            .map(|(source, boost)| {
+                format!(" WHEN '{}' THEN {:?}", source.replace('\'', "''"), boost)
+            })
+            .collect();
+        format!("CASE links.source{} ELSE 1.0 END", cases)
+    }
+}
+
+/// Returns the bonus for where the query's words first appear in the title:
+/// 1.0 + TITLE_POSITION_BONUS when the title starts with one, shrinking the
+/// further into the title the earliest one is, and 1.0 when the title doesn't
+/// contain any. The title is folded like the (prepared) query, so accented
+/// titles match too. Registered as the title_position() SQL function.
+pub(crate) fn title_position(title: &str, words: &[String]) -> f64 {
+    let title = fold_query(title).to_lowercase();
+    words
+        .iter()
+        .filter_map(|word| title.find(word.as_str()))
+        .min()
+        .map_or(1.0, |index| {
+            let position = title[..index].chars().count() + 1;
+            1.0 + TITLE_POSITION_BONUS / position as f64
+        })
+}
+
+/// Returns the distinct lowercased words of a prepared query, leaving out
+/// FTS5 operators and column filters (e.g. the "title" of "title : rust").
+pub(crate) fn title_words(query: &str) -> Vec<String> {
+    let terms: Vec<&str> = query.split_whitespace().collect();
+    let mut words: Vec<String> = vec![];
+    for (index, term) in terms.iter().enumerate() {
+        let is_column = term.ends_with(':')
+            || terms
+                .get(index + 1)
+                .is_some_and(|next| next.starts_with(':'));
+        if is_column {
+            continue;
+        }
+        for word in term.split(|c: char| !c.is_alphanumeric()) {
+            if word.is_empty() || ["AND", "OR", "NOT", "NEAR"].contains(&word) {
+                continue;
+            }
+            let word = word.to_lowercase();
+            if !words.contains(&word) {
+                words.push(word);
+            }
+        }
+    }
+    words.truncate(MAX_TITLE_WORDS);
+    words
+}
+
+/// Returns true when the error is SQLite reporting a corrupt database, which
+/// for searches means links_fts' shadow tables are damaged.
+fn is_corrupt_index(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::Rusqlite(rusqlite::Error::SqliteFailure(e, _)) if e.code == ErrorCode::DatabaseCorrupt
+    )
+}
+
+/// Accented Latin letters, and the letters they fold to, in the same order
+const DIACRITICS: &str = "ÀÁÂÃÄÅàáâãäåÇçÈÉÊËèéêëÌÍÎÏìíîïÑñÒÓÔÕÖòóôõöÙÚÛÜùúûüÝýÿ";
+const FOLDED: &str = "AAAAAAaaaaaaCcEEEEeeeeIIIIiiiiNnOOOOOoooooUUUUuuuuYyy";
+
+/// Folds accented Latin letters in a query to their unaccented forms (e.g.
+/// "München" to "Munchen"), matching the folding the search index applies
+/// to the links it stores.
+fn fold_query(query: &str) -> String {
+    query
+        .chars()
+        .map(|c| match DIACRITICS.chars().position(|d| d == c) {
+            Some(index) => FOLDED.chars().nth(index).unwrap_or(c),
+            None => c,
+        })
+        .collect()
+}
+
+/// Scales the scores of ranked results so the best scores 1.0 and the rest
+/// fall between 0.0 and 1.0 in proportion to it. When nothing has a positive
+/// score (e.g. results ordered by recency) every result scores 1.0.
+fn normalize_scores(links: &mut [Link]) {
+    let max_score = max_score(links);
+    for link in links {
+        link.score = link.score.map(|score| normalize_score(score, max_score));
+    }
+}
+
+fn max_score(links: &[Link]) -> f32 {
+    links
+        .iter()
+        .filter_map(|link| link.score)
+        .fold(0.0_f32, f32::max)
+}
+
+fn normalize_score(score: f32, max_score: f32) -> f32 {
+    if max_score > 0.0 {
+        (score / max_score).clamp(0.0, 1.0)
+    } else {
+        1.0
+    }
+}
+
+/// The number of results SearchIter fetches from the database at a time
+const SEARCH_PAGE_SIZE: u32 = 50;
+
+/// Iterator returned by Cache::search_iter()
+struct SearchIter<'a> {
+    cache: &'a Cache,
+    /// The FTS query still being paged through, or None once exhausted
+    query: Option<String>,
+    offset: u32,
+    /// The best raw score, taken from the first page, which every score is
+    /// normalized against
+    max_score: Option<f32>,
+    buffer: VecDeque<Link>,
+}
+
+impl Iterator for SearchIter<'_> {
+    type Item = Result<Link>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buffer.is_empty() {
+            let query = self.query.as_deref()?;
+            match self
+                .cache
+                .search_page(query, Some(SEARCH_PAGE_SIZE), self.offset)
+            {
+                Ok(mut links) => {
+                    if (links.len() as u32) < SEARCH_PAGE_SIZE {
+                        self.query = None;
+                    }
+                    self.offset += links.len() as u32;
+                    let max_score = *self.max_score.get_or_insert_with(|| max_score(&links));
+                    for link in &mut links {
+                        link.score = link.score.map(|score| normalize_score(score, max_score));
+                    }
+                    self.cache.present(&mut links);
+                    self.buffer = links.into();
+                }
+                Err(err) => {
+                    self.query = None;
+                    return Some(Err(err));
+                }
+            }
+        }
+        self.buffer.pop_front().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::create_test_cache;
+
+    #[test]
+    fn test_probe_prefers_rank() {
+        let cache = create_test_cache();
+        assert_eq!(RankStrategy::probe(&cache.conn), RankStrategy::Rank);
+    }
+
+    #[test]
+    fn test_probe_falls_back_without_fts5() -> Result<()> {
+        // A plain table named links_fts supports neither MATCH nor bm25()
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch("CREATE TABLE links_fts (url TEXT)")?;
+        assert_eq!(RankStrategy::probe(&conn), RankStrategy::Recency);
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_for_pasted_url() -> Result<()> {
+        let mut cache = create_test_cache();
+        cache.add(Link::new(
+            "https://docs.rs/rusqlite/latest/rusqlite/".to_string(),
+            "rusqlite - Rust".to_string(),
+        ))?;
+        cache.add(Link::new(
+            "https://docs.rs/rusqlite/latest/rusqlite/struct.Connection.html".to_string(),
+            "Connection in rusqlite - Rust".to_string(),
+        ))?;
+        cache.add(Link::new(
+            "https://github.com/rusqlite/rusqlite".to_string(),
+            "rusqlite on GitHub".to_string(),
+        ))?;
+
+        let url = "https://docs.rs/rusqlite/latest/rusqlite/struct.Connection.html";
+        let results = cache.search(url)?;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].url, url);
+        assert_eq!(results[1].url, "https://docs.rs/rusqlite/latest/rusqlite/");
+
+        // A variant of a stored url finds that link once, ahead of the rest
+        for variant in [
+            "https://docs.rs/rusqlite/latest/rusqlite",
+            "http://docs.rs/rusqlite/latest/rusqlite/",
+        ] {
+            let results: Vec<String> = cache.search(variant)?.into_iter().map(|l| l.url).collect();
+            assert_eq!(
+                results,
+                [
+                    "https://docs.rs/rusqlite/latest/rusqlite/",
+                    "https://docs.rs/rusqlite/latest/rusqlite/struct.Connection.html"
+                ],
+                "searching {}",
+                variant
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_iter_matches_search() -> Result<()> {
+        let mut cache = create_test_cache();
+        // More than a page of results, with varying relevance
+        for i in 0..(SEARCH_PAGE_SIZE + 20) {
+            let title = format!("Rust {} {}", "notes ".repeat(i as usize % 7), i);
+            cache.add(Link::new(format!("https://example.com/{}", i), title))?;
+        }
+
+        let expected: Vec<String> = cache
+            .search_limited("Rust", None)?
+            .into_iter()
+            .map(|l| l.url)
+            .collect();
+        let streamed = cache
+            .search_iter("Rust")?
+            .map(|link| link.map(|l| l.url))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(expected.len(), (SEARCH_PAGE_SIZE + 20) as usize);
+        assert_eq!(streamed, expected);
+
+        let latest = cache.search_iter("")?.collect::<Result<Vec<_>>>()?;
+        assert_eq!(latest.len(), cache.search("")?.len());
+
+        // Operator queries aren't capped either
+        let filtered = cache
+            .search_iter("site:example.com Rust")?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(filtered.len(), expected.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_limits_and_offsets_are_bound() -> Result<()> {
+        let mut cache = create_test_cache();
+        cache.add(Link::new(
+            "https://www.sqlite.org/lang_select.html".to_string(),
+            "SQLite SELECT".to_string(),
+        ))?;
+
+        // Values which would be out of range (or invalid SQL) if they were
+        // interpolated into the statement as text
+        assert_eq!(cache.get_latest_n(u32::MAX)?.len(), 1);
+        assert_eq!(cache.search_page("SELECT", Some(u32::MAX), 0)?.len(), 1);
+        assert!(cache
+            .search_page("SELECT", Some(u32::MAX), u32::MAX)?
+            .is_empty());
+        assert!(cache.search_page("SELECT", Some(0), 0)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_open() -> Result<()> {
+        let mut cache = create_test_cache();
+        cache.add(
+            Link::new("https://example.com/a".to_string(), "Rust A".to_string())
+                .with_timestamp_seconds(1_700_000_000),
+        )?;
+        cache.add(
+            Link::new("https://example.com/b".to_string(), "Rust B".to_string())
+                .with_timestamp_seconds(1_600_000_000),
+        )?;
+
+        assert!(cache.record_open("https://example.com/b")?);
+        assert!(!cache.record_open("https://example.com/missing")?);
+
+        let opened = cache.get_by_url("https://example.com/b")?.unwrap();
+        assert!(opened.last_opened_at.is_some());
+        assert_eq!(opened.timestamp.timestamp(), 1_600_000_000);
+
+        // Re-importing doesn't forget that the link was opened
+        cache.add(
+            Link::new("https://example.com/b".to_string(), "Rust B".to_string())
+                .with_timestamp_seconds(1_600_000_000),
+        )?;
+        let reimported = cache.get_by_url("https://example.com/b")?.unwrap();
+        assert_eq!(reimported.last_opened_at, opened.last_opened_at);
+
+        let by_import =
+            cache.search_ordered("Rust", SearchOrder::Recency(RecencyField::Timestamp))?;
+        assert_eq!(by_import[0].url, "https://example.com/a");
+        let by_open =
+            cache.search_ordered("Rust", SearchOrder::Recency(RecencyField::LastOpenedAt))?;
+        assert_eq!(by_open[0].url, "https://example.com/b");
+        let latest = cache.search_ordered("", SearchOrder::Recency(RecencyField::LastOpenedAt))?;
+        assert_eq!(latest[0].url, "https://example.com/b");
+        Ok(())
+    }
+
+    #[test]
+    fn test_stopword_only_query_returns_latest() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut cache = crate::CacheBuilder::new()
+            .with_path(temp_dir.path().join("cache.sqlite"))
+            .with_stopwords(["the", "Home", "login"])
+            .build()?;
+        for i in 0..60 {
+            cache.add(
+                Link::new(
+                    format!("https://example.com/{}", i),
+                    format!("The Home Page {}", i),
+                )
+                .with_timestamp_seconds(1_600_000_000 + i),
+            )?;
+        }
+        cache.add(
+            Link::new(
+                "https://example.com/rust".to_string(),
+                "The Rust Home".to_string(),
+            )
+            .with_timestamp_seconds(1_500_000_000),
+        )?;
+
+        let results = cache.search("the HOME")?;
+        assert_eq!(results.len(), 50);
+        assert_eq!(results[0].url, "https://example.com/59");
+        assert_eq!(cache.search_iter("login the")?.count(), 50);
+
+        // Stopwords are dropped from queries which have other terms
+        let results = cache.search("the rust home")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].url, "https://example.com/rust");
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_detailed_reports_matched_fields() -> Result<()> {
+        let mut cache = create_test_cache();
+        cache.add(
+            Link::new(
+                "https://example.com/standup".to_string(),
+                "Standup Notes".to_string(),
+            )
+            .with_subtitle("Work / Areas".to_string()),
+        )?;
+        cache.add(Link::new(
+            "https://example.com/areas".to_string(),
+            "Areas of Responsibility".to_string(),
+        ))?;
+
+        let results = cache.search_detailed("Areas")?;
+        assert_eq!(results.len(), 2);
+
+        let folder_match = results
+            .iter()
+            .find(|r| r.link.url == "https://example.com/standup")
+            .unwrap();
+        assert!(folder_match.matched_fields.contains(&Field::Subtitle));
+        assert!(!folder_match.matched_fields.contains(&Field::Title));
+
+        let title_match = results
+            .iter()
+            .find(|r| r.link.url == "https://example.com/areas")
+            .unwrap();
+        assert!(title_match.matched_fields.contains(&Field::Title));
+        assert!(title_match.matched_fields.contains(&Field::Url));
+        assert!(!title_match.matched_fields.contains(&Field::Subtitle));
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_including_deleted() -> Result<()> {
+        let mut cache = create_test_cache();
+        let removed = Link::new(
+            "https://example.com/removed".to_string(),
+            "Removed Runbook".to_string(),
+        );
+        cache.add(removed.clone())?;
+        cache.add(Link::new(
+            "https://example.com/kept".to_string(),
+            "Kept Runbook".to_string(),
+        ))?;
+        cache.remove(&removed)?;
+
+        let urls: Vec<String> = cache
+            .search("runbook")?
+            .into_iter()
+            .map(|l| l.url)
+            .collect();
+        assert_eq!(urls, vec!["https://example.com/kept"]);
+
+        let results = cache.search_including_deleted("runbook")?;
+        assert_eq!(results.len(), 2);
+        for (link, deleted_at) in &results {
+            assert_eq!(
+                deleted_at.is_some(),
+                link.url == removed.url,
+                "{}",
+                link.url
+            );
+        }
+        assert_eq!(cache.search_including_deleted("")?.len(), 2);
+
+        cache.purge_deleted()?;
+        assert_eq!(cache.search_including_deleted("runbook")?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_history_age_penalty() -> Result<()> {
+        let three_years_ago = (Utc::now() - chrono::Duration::days(3 * 365)).timestamp();
+        let last_week = (Utc::now() - chrono::Duration::days(7)).timestamp();
+        let old_history = Link::new(
+            "https://example.com/old".to_string(),
+            "Rust Async Book".to_string(),
+        )
+        .with_kind(LinkKind::History)
+        .with_timestamp_seconds(three_years_ago);
+        let recent_bookmark = Link::new(
+            "https://example.com/new".to_string(),
+            "Rust Async Book".to_string(),
+        )
+        .with_kind(LinkKind::Bookmark)
+        .with_timestamp_seconds(last_week);
+
+        let temp_dir = tempfile::tempdir()?;
+        let mut cache = crate::CacheBuilder::new()
+            .with_path(temp_dir.path().join("cache.sqlite"))
+            .with_history_age_penalty(std::time::Duration::from_secs(365 * 24 * 60 * 60), 0.5)
+            .build()?;
+        // A tie would put the bookmark (whose url sorts first) ahead too, so
+        // the scores are compared as well
+        cache.add(old_history)?;
+        cache.add(recent_bookmark)?;
+
+        let results = cache.search("Rust Async")?;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].url, "https://example.com/new");
+        assert_eq!(results[0].kind, Some(LinkKind::Bookmark));
+        assert!(results[0].score > results[1].score);
+
+        // A recent history link isn't penalized
+        cache.add(
+            Link::new(
+                "https://example.com/recent-history".to_string(),
+                "Rust Async Book".to_string(),
+            )
+            .with_kind(LinkKind::History)
+            .with_timestamp_seconds(last_week),
+        )?;
+        let results = cache.search("Rust Async")?;
+        assert_eq!(results[2].url, "https://example.com/old");
+        Ok(())
+    }
+
+    #[test]
+    fn test_non_finite_history_age_penalty_is_ignored() -> Result<()> {
+        let mut cache = crate::CacheBuilder::new()
+            .in_memory()
+            .with_history_age_penalty(std::time::Duration::from_secs(60), f32::NAN)
+            .build()?;
+        cache.add(
+            Link::new(
+                "https://example.com/old".to_string(),
+                "Rust Async Book".to_string(),
+            )
+            .with_kind(LinkKind::History)
+            .with_timestamp_seconds(0),
+        )?;
+
+        let results = cache.search("Rust Async")?;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].score.is_some_and(f32::is_finite));
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_site_syntax() -> Result<()> {
+        let mut cache = create_test_cache();
+        cache.add(Link::new(
+            "https://github.com/rusqlite/rusqlite/issues".to_string(),
+            "Issues · rusqlite/rusqlite".to_string(),
+        ))?;
+        cache.add(Link::new(
+            "https://github.com/rusqlite/rusqlite".to_string(),
+            "rusqlite on GitHub".to_string(),
+        ))?;
+        cache.add(Link::new(
+            "https://gitlab.com/example/project/-/issues".to_string(),
+            "Issues · Project".to_string(),
+        ))?;
+        cache.add(Link::new(
+            "https://example.com/?ref=github.com".to_string(),
+            "Issues mentioning github.com".to_string(),
+        ))?;
+
+        assert_eq!(cache.search("issues")?.len(), 3);
+
+        let results = cache.search("site:github.com issues")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].url,
+            "https://github.com/rusqlite/rusqlite/issues"
+        );
+        assert_eq!(cache.search("issues site:GitHub")?.len(), 1);
+        assert_eq!(cache.search_host_and_text("github", "issues")?.len(), 1);
+        assert_eq!(cache.search_iter("site:github.com issues")?.count(), 1);
+
+        // Without text, every link from the host is returned
+        assert_eq!(cache.search("site:github.com")?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_operators() -> Result<()> {
+        let mut cache = create_test_cache();
+        let link = |url: &str, title: &str, source: &str, date: &str| {
+            let timestamp = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+                .and_utc();
+            Link::new(url.to_string(), title.to_string())
+                .with_source(source.to_string())
+                .with_timestamp_seconds(timestamp.timestamp())
+        };
+        cache.add(link(
+            "https://github.com/rusqlite/rusqlite/issues",
+            "Issues · rusqlite/rusqlite",
+            "firefox",
+            "2024-03-01",
+        ))?;
+        cache.add(link(
+            "https://github.com/rusqlite/rusqlite/pulls",
+            "Pull requests · rusqlite/rusqlite",
+            "chrome",
+            "2024-02-01",
+        ))?;
+        cache.add(link(
+            "https://github.com/rusqlite/rusqlite/wiki/issues",
+            "Wiki · rusqlite/rusqlite",
+            "firefox",
+            "2023-06-01",
+        ))?;
+        cache.add(link(
+            "https://gitlab.com/example/project/-/issues",
+            "Issues · Project",
+            "chrome",
+            "2024-04-01",
+        ))?;
+
+        let urls = |query: &str| -> Result<Vec<String>> {
+            Ok(cache.search(query)?.into_iter().map(|l| l.url).collect())
+        };
+        assert_eq!(urls("site:github.com rusqlite")?.len(), 3);
+        assert_eq!(urls("source:firefox")?.len(), 2);
+        assert_eq!(urls("source:Chrome requests")?.len(), 1);
+        assert_eq!(
+            urls("after:2024-02-01 before:2024-04-01")?,
+            vec![
+                "https://github.com/rusqlite/rusqlite/issues",
+                "https://github.com/rusqlite/rusqlite/pulls"
+            ]
+        );
+        // The wiki page only has "issues" in its url
+        assert_eq!(urls("issues")?.len(), 3);
+        assert_eq!(urls("title:issues")?.len(), 2);
+
+        assert_eq!(
+            urls("site:github.com title:issues after:2024-01-01")?,
+            vec!["https://github.com/rusqlite/rusqlite/issues"]
+        );
+        assert_eq!(
+            cache
+                .search_iter("site:github.com title:issues after:2024-01-01")?
+                .count(),
+            1
+        );
+
+        // An operator which can't be applied is matched as text
+        assert!(urls("after:yesterday")?.is_empty());
+        assert_eq!(urls("source:firefox before:someday")?.len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_by_frecency() -> Result<()> {
+        let mut cache = create_test_cache();
+        let now = DateTime::parse_from_rfc3339("2024-06-01T12:00:00Z")
+            .unwrap()
+            .to_utc();
+        let page = |url: &str, days_ago: i64, visits: u32| Link {
+            visit_count: Some(visits),
+            ..Link::new(url.to_string(), "Release Notes".to_string())
+                .with_timestamp_seconds((now - chrono::Duration::days(days_ago)).timestamp())
+        };
+        cache.add(page("https://example.com/older", 30, 50))?;
+        cache.add(page("https://example.com/newer", 1, 1))?;
+        cache.add(page("https://example.com/stale", 365, 50))?;
+
+        // The pages match equally well, so visits and age decide
+        let urls = |links: Vec<Link>| -> Vec<String> { links.into_iter().map(|l| l.url).collect() };
+        let ranked = cache.search_by_frecency_at("release notes", now)?;
+        assert_eq!(ranked[0].score, Some(1.0));
+        assert_eq!(
+            urls(ranked),
+            vec![
+                "https://example.com/older",
+                "https://example.com/newer",
+                "https://example.com/stale"
+            ]
+        );
+        let again = cache.search_by_frecency_at("release notes", now)?;
+        assert_eq!(urls(again)[0], "https://example.com/older");
+        Ok(())
+    }
+
+    #[test]
+    fn test_frecency() {
+        let now = Utc::now();
+        let link = |days_ago: i64, visits: Option<u32>| Link {
+            timestamp: now - chrono::Duration::days(days_ago),
+            visit_count: visits,
+            ..Default::default()
+        };
+        assert_eq!(frecency(2.0, &link(0, None), now), 2.0);
+        assert_eq!(frecency(2.0, &link(30, None), now), 1.0);
+        assert_eq!(frecency(2.0, &link(-5, Some(0)), now), 2.0);
+        let visited = frecency(2.0, &link(0, Some(9)), now);
+        assert!((visited - 2.0 * (1.0 + 10f32.ln())).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_best_match() -> Result<()> {
+        let mut cache = create_test_cache();
+        cache.add(Link::new(
+            "https://doc.rust-lang.org/book/".to_string(),
+            "The Rust Programming Language".to_string(),
+        ))?;
+        cache.add(Link::new(
+            "https://www.rust-lang.org/".to_string(),
+            "Rust".to_string(),
+        ))?;
+        cache.add(
+            Link::new("https://example.com/".to_string(), "Example".to_string())
+                .with_subtitle("Rust / Misc".to_string()),
+        )?;
+
+        let best = cache.best_match("Rust")?.expect("Rust should match");
+        assert_eq!(best.url, cache.search("Rust")?[0].url);
+        assert_eq!(best.score, cache.search("Rust")?[0].score);
+        assert!(cache.best_match("Haskell")?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ties_are_ordered_by_url() -> Result<()> {
+        let mut cache = create_test_cache();
+        let timestamp = Utc::now().timestamp();
+        // Added in reverse, so SQLite's row order would put b first
+        for url in ["https://example.com/b", "https://example.com/a"] {
+            cache.add(
+                Link::new(url.to_string(), "Identical Title".to_string())
+                    .with_timestamp_seconds(timestamp),
+            )?;
+        }
+
+        let expected = vec!["https://example.com/a", "https://example.com/b"];
+        for _ in 0..3 {
+            let urls = |links: Vec<Link>| links.into_iter().map(|l| l.url).collect::<Vec<_>>();
+            assert_eq!(urls(cache.search("identical")?), expected);
+            assert_eq!(urls(cache.search("")?), expected);
+            let by_recency =
+                cache.search_ordered("identical", SearchOrder::Recency(RecencyField::Timestamp))?;
+            assert_eq!(urls(by_recency), expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_limited() -> Result<()> {
+        let mut cache = create_test_cache();
+        for i in 0..(SEARCH_PAGE_SIZE + 5) {
+            let title = format!("Rust {} {}", "notes ".repeat(i as usize % 4), i);
+            cache.add(Link::new(format!("https://example.com/{}", i), title))?;
+        }
+
+        let urls = |links: Vec<Link>| links.into_iter().map(|l| l.url).collect::<Vec<_>>();
+        let all = cache.search_limited("Rust", None)?;
+        assert_eq!(all.len(), (SEARCH_PAGE_SIZE + 5) as usize);
+        assert_eq!(cache.search("Rust")?.len(), DEFAULT_SEARCH_LIMIT as usize);
+        let limited = cache.search_limited("Rust", 5)?;
+        assert_eq!(limited[0].score, Some(1.0));
+        assert_eq!(urls(limited), urls(all.into_iter().take(5).collect()));
+
+        assert_eq!(cache.search_limited("", 3)?.len(), 3);
+        assert_eq!(
+            cache.search_limited("", 80)?.len(),
+            (SEARCH_PAGE_SIZE + 5) as usize
+        );
+        assert_eq!(cache.search("")?.len(), 50);
+        assert_eq!(cache.search_limited("https://example.com/1", 2)?.len(), 2);
+
+        // The limit can be overridden per query
+        assert_eq!(cache.search_limited("notes", Some(2))?.len(), 2);
+        let notes = cache.search("notes")?;
+        assert!(notes.len() > 2);
+        assert_eq!(urls(cache.search_limited("notes", None)?), urls(notes));
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_tabs_can_be_boosted() -> Result<()> {
+        let mut cache = crate::CacheBuilder::new()
+            .in_memory()
+            .with_source_boosts(HashMap::from([(
+                crate::firefox::TAB_SOURCE.to_string(),
+                2.0,
+            )]))
+            .build()?;
+        // Its url sorts first, so it would win a tie
+        cache.add(
+            Link::new(
+                "https://example.com/bookmark".to_string(),
+                "Tokio Tutorial".to_string(),
+            )
+            .with_source(crate::firefox::SOURCE.to_string())
+            .with_kind(LinkKind::Bookmark),
+        )?;
+        cache.add(
+            Link::new(
+                "https://example.com/tab".to_string(),
+                "Tokio Tutorial".to_string(),
+            )
+            .with_source(crate::firefox::TAB_SOURCE.to_string()),
+        )?;
+
+        let results = cache.search("tokio")?;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].url, "https://example.com/tab");
+        assert!(results[0].score > results[1].score);
+        Ok(())
+    }
+
+    #[test]
+    fn test_non_finite_source_boosts_are_ignored() -> Result<()> {
+        let mut cache = crate::CacheBuilder::new()
+            .in_memory()
+            .with_source_boosts(HashMap::from([
+                ("arc".to_string(), f32::NAN),
+                ("chrome".to_string(), f32::INFINITY),
+                ("firefox".to_string(), 2.0),
+            ]))
+            .build()?;
+        for source in ["arc", "chrome", "firefox"] {
+            cache.add(
+                Link::new(
+                    format!("https://example.com/{}", source),
+                    "Tokio Tutorial".to_string(),
+                )
+                .with_source(source.to_string()),
+            )?;
+        }
+
+        let results = cache.search("tokio")?;
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].url, "https://example.com/firefox");
+        assert!(results.iter().all(|l| l.score.is_some_and(f32::is_finite)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_title_matches_at_the_start_rank_first() -> Result<()> {
+        let mut cache = create_test_cache();
+        // The same words in a different order, so bm25 scores them equally
+        // and the url of the mid-title match would otherwise sort first
+        cache.add(Link::new(
+            "https://example.com/a".to_string(),
+            "Learning with Rust".to_string(),
+        ))?;
+        cache.add(Link::new(
+            "https://example.com/b".to_string(),
+            "Rust with Learning".to_string(),
+        ))?;
+
+        let results = cache.search("rust")?;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].title, "Rust with Learning");
+        assert!(results[0].score > results[1].score);
+
+        // Accented titles are folded like the query
+        cache.add(Link::new(
+            "https://example.com/c".to_string(),
+            "Recipe for Crème Brûlée".to_string(),
+        ))?;
+        cache.add(Link::new(
+            "https://example.com/d".to_string(),
+            "Crème Brûlée for Recipe".to_string(),
+        ))?;
+        let results = cache.search("creme")?;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].title, "Crème Brûlée for Recipe");
+        assert!(results[0].score > results[1].score);
+        Ok(())
+    }
+
+    #[test]
+    fn test_title_position() {
+        let words = title_words("rust book");
+        assert_eq!(title_position("Rust Book", &words), 1.5);
+        assert_eq!(title_position("The Book", &words), 1.0 + 0.5 / 5.0);
+        assert_eq!(title_position("Élan", &title_words("elan")), 1.5);
+        assert_eq!(title_position("über rust", &words), 1.0 + 0.5 / 6.0);
+        assert_eq!(title_position("Go", &words), 1.0);
+        assert_eq!(title_position("Rust", &[]), 1.0);
+    }
+
+    #[test]
+    fn test_title_words() {
+        assert_eq!(title_words("Rust rust BOOK"), vec!["rust", "book"]);
+        assert_eq!(
+            title_words("rusqlite title : \"issues\""),
+            vec!["rusqlite", "issues"]
+        );
+        assert_eq!(title_words("NEAR(rust web, 3)"), vec!["rust", "web", "3"]);
+        assert!(title_words("").is_empty());
+    }
+
+    #[test]
+    fn test_search_sets_scores_from_bm25() -> Result<()> {
+        let mut cache = create_test_cache();
+        cache.add(Link::new(
+            "https://example.com/passing".to_string(),
+            "Release notes mention tokio once".to_string(),
+        ))?;
+        cache.add(Link::new(
+            "https://tokio.rs/".to_string(),
+            "Tokio".to_string(),
+        ))?;
+
+        let results = cache.search("tokio")?;
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|l| l.score.is_some()));
+        assert_eq!(results[0].url, "https://tokio.rs/");
+
+        // The raw scores are the negated bm25 rank, which is more negative
+        // for better matches
+        let detailed = cache.search_detailed("tokio")?;
+        let bm25 = |url: &str| {
+            let link = &detailed.iter().find(|r| r.link.url == url).unwrap().link;
+            -link.score.unwrap()
+        };
+        assert!(bm25("https://tokio.rs/") < bm25("https://example.com/passing"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_scores_are_normalized() -> Result<()> {
+        let mut cache = create_test_cache();
+        for i in 0..(SEARCH_PAGE_SIZE + 5) {
+            let title = format!("Rust {} {}", "notes ".repeat(i as usize % 4), i);
+            cache.add(Link::new(format!("https://example.com/{}", i), title))?;
+        }
+
+        let results = cache.search("Rust")?;
+        assert_eq!(results[0].score, Some(1.0));
+        assert!(results
+            .iter()
+            .all(|l| l.score.is_some_and(|s| (0.0..=1.0).contains(&s))));
+        assert!(results.last().unwrap().score < Some(1.0));
+
+        let streamed = cache.search_iter("Rust")?.collect::<Result<Vec<_>>>()?;
+        let scores = |links: &[Link]| links.iter().map(|l| l.score).collect::<Vec<_>>();
+        assert_eq!(scores(&streamed[..results.len()]), scores(&results));
+        assert_eq!(cache.best_match("Rust")?.unwrap().score, Some(1.0));
+
+        // The raw bm25 scores are still available
+        let detailed = cache.search_detailed("Rust")?;
+        assert!(detailed[0].link.score.is_some_and(|s| s != 1.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_fold_query() {
+        assert_eq!(DIACRITICS.chars().count(), FOLDED.chars().count());
+        assert_eq!(fold_query("München Café"), "Munchen Cafe");
+        assert_eq!(fold_query("plain ascii"), "plain ascii");
+    }
+
+    /// Damages links_fts by removing its leaf pages, as an interrupted write
+    /// might, so MATCH queries fail with SQLITE_CORRUPT.
+    fn corrupt_index(cache: &Cache) {
+        cache
+            .conn
+            .execute("DELETE FROM links_fts_data WHERE id > 10", [])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_search_recovers_from_corrupt_index() -> Result<()> {
+        let mut cache = create_test_cache();
+        cache.add(Link::new(
+            "https://doc.rust-lang.org/book/".to_string(),
+            "The Rust Programming Language".to_string(),
+        ))?;
+        cache.add(Link::new(
+            "https://go.dev/doc/".to_string(),
+            "Go Documentation".to_string(),
+        ))?;
+        corrupt_index(&cache);
+        let err = cache.search_page("rust", None, 0).unwrap_err();
+        assert!(is_corrupt_index(&err));
+
+        let results = cache.search("rust")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].url, "https://doc.rust-lang.org/book/");
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_links_table_fallback() -> Result<()> {
+        let mut cache = create_test_cache();
+        cache.add(Link::new(
+            "https://doc.rust-lang.org/book/".to_string(),
+            "The Rust Programming Language".to_string(),
+        ))?;
+        cache.add(Link::new(
+            "https://doc.rust-lang.org/std/".to_string(),
+            "Rust Standard Library".to_string(),
+        ))?;
+        cache.add(Link::new(
+            "https://example.com/100%_rust".to_string(),
+            "Percentages".to_string(),
+        ))?;
+        corrupt_index(&cache);
+
+        let results = cache.search_links_table("rust programming", None)?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].title, "The Rust Programming Language");
+        assert_eq!(results[0].score, None);
+
+        assert_eq!(cache.search_links_table("RUST", None)?.len(), 3);
+        assert_eq!(cache.search_links_table("%", None)?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_near_ranks_adjacent_terms_first() -> Result<()> {
+        let mut cache = create_test_cache();
+        cache.add(Link::new(
+            "https://example.com/schedule".to_string(),
+            "Release schedule, with meeting notes".to_string(),
+        ))?;
+        cache.add(Link::new(
+            "https://example.com/changelog/2024/version-1-0".to_string(),
+            "Version 1.0 Release Notes and Upgrade Guide".to_string(),
+        ))?;
+        cache.add(Link::new(
+            "https://example.com/notes".to_string(),
+            "Notes".to_string(),
+        ))?;
+
+        let results = cache.search_near(&["release", "notes"], 5)?;
+        assert_eq!(results.len(), 2);
+        assert_eq!(
+            results[0].url,
+            "https://example.com/changelog/2024/version-1-0"
+        );
+        assert_eq!(results[1].url, "https://example.com/schedule");
+        assert!(results[0].score > results[1].score);
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_with_timeout() -> Result<()> {
+        let mut cache = create_test_cache();
+        for i in 0..200 {
+            cache.add(Link::new(
+                format!("https://example.com/rust/{}", i),
+                format!("Rust Article {}", i),
+            ))?;
+        }
+
+        let result = cache.search_with_timeout("rust article", Duration::ZERO);
+        assert!(matches!(result, Err(Error::Timeout)));
+
+        let results = cache.search_with_timeout("rust article", Duration::from_secs(60))?;
+        assert_eq!(results.len(), DEFAULT_SEARCH_LIMIT as usize);
+        // The deadline doesn't outlive the search
+        assert_eq!(cache.search_limited("rust article", None)?.len(), 200);
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_projected() -> Result<()> {
+        let mut cache = create_test_cache();
+        cache.add(
+            Link::new(
+                "https://doc.rust-lang.org/book/".to_string(),
+                "The Rust Programming Language".to_string(),
+            )
+            .with_subtitle("Bookmarks Bar / Rust".to_string())
+            .with_source("chrome".to_string())
+            .with_description("The official book".to_string())
+            .with_author("Steve Klabnik".to_string()),
+        )?;
+
+        let fields = [Field::Title, Field::Url, Field::Subtitle];
+        let results = cache.search_projected("rust book", &fields)?;
+        assert_eq!(results.len(), 1);
+        let link = &results[0];
+        assert_eq!(link.title, "The Rust Programming Language");
+        assert_eq!(link.url, "https://doc.rust-lang.org/book/");
+        assert_eq!(link.subtitle, Some("Bookmarks Bar / Rust".to_string()));
+        assert_eq!(link.score, Some(1.0));
+        assert_eq!(link.source, None);
+        assert_eq!(link.description, None);
+        assert_eq!(link.author, None);
+        assert_eq!(link.timestamp, DateTime::<Utc>::default());
+
+        let latest = cache.search_projected("", &[Field::Url])?;
+        assert_eq!(latest[0].url, "https://doc.rust-lang.org/book/");
+        assert_eq!(latest[0].title, "");
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_facets() -> Result<()> {
+        let mut cache = create_test_cache();
+        for (i, source) in ["firefox", "firefox", "chrome", "arc"].iter().enumerate() {
+            cache.add(
+                Link::new(
+                    format!("https://example.com/rust/{}", i),
+                    format!("Rust Article {}", i),
+                )
+                .with_source(source.to_string()),
+            )?;
+        }
+        cache.add(Link::new(
+            "https://example.com/rust/unsourced".to_string(),
+            "Rust Notes".to_string(),
+        ))?;
+        cache.add(
+            Link::new("https://go.dev/".to_string(), "Go".to_string())
+                .with_source("chrome".to_string()),
+        )?;
+
+        let mut expected: HashMap<String, u64> = HashMap::new();
+        for link in cache.search("rust")? {
+            *expected.entry(link.source.unwrap_or_default()).or_default() += 1;
+        }
+        let facets = cache.search_facets("rust")?;
+        assert_eq!(facets, expected);
+        assert_eq!(facets["firefox"], 2);
+        assert_eq!(facets["chrome"], 1);
+        assert_eq!(facets[""], 1);
+
+        assert_eq!(cache.search_facets("")?["chrome"], 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_relative_time_subtitle() -> Result<()> {
+        let three_days_ago = (Utc::now() - chrono::Duration::days(3)).timestamp();
+        let history = Link::new(
+            "https://example.com/history".to_string(),
+            "Rust Async Book".to_string(),
+        )
+        .with_subtitle("Recently Closed".to_string())
+        .with_kind(LinkKind::History)
+        .with_timestamp_seconds(three_days_ago);
+        let bookmark = Link::new(
+            "https://example.com/bookmark".to_string(),
+            "Rust Async Notes".to_string(),
+        )
+        .with_subtitle("Bookmarks Bar / Rust".to_string())
+        .with_kind(LinkKind::Bookmark)
+        .with_timestamp_seconds(three_days_ago);
+
+        let subtitle_of = |links: &[Link], url: &str| {
+            links
+                .iter()
+                .find(|link| link.url == url)
+                .and_then(|link| link.subtitle.clone())
+        };
+
+        let mut cache = create_test_cache();
+        cache.add(history.clone())?;
+        let results = cache.search("Rust Async")?;
+        assert_eq!(
+            subtitle_of(&results, "https://example.com/history"),
+            Some("Recently Closed".to_string())
+        );
+
+        let mut cache = crate::CacheBuilder::new()
+            .in_memory()
+            .with_relative_time_subtitle(true)
+            .build()?;
+        cache.add(history)?;
+        cache.add(bookmark)?;
+        let results = cache.search("Rust Async")?;
+        assert_eq!(
+            subtitle_of(&results, "https://example.com/history"),
+            Some("last visited 3 days ago".to_string())
+        );
+        assert_eq!(
+            subtitle_of(&results, "https://example.com/bookmark"),
+            Some("Bookmarks Bar / Rust".to_string())
+        );
+
+        // Streamed results are presented the same way
+        let streamed = cache
+            .search_iter("Rust Async")?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            subtitle_of(&streamed, "https://example.com/history"),
+            Some("last visited 3 days ago".to_string())
+        );
+
+        // The stored subtitle is unchanged
+        let stored = cache.get_by_url("https://example.com/history")?.unwrap();
+        assert_eq!(stored.subtitle, Some("Recently Closed".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_relative_time() {
+        let now = Utc::now();
+        let ago = |duration: chrono::Duration| relative_time(now - duration, now);
+        assert_eq!(ago(chrono::Duration::seconds(20)), "just now");
+        assert_eq!(ago(chrono::Duration::minutes(1)), "1 minute ago");
+        assert_eq!(ago(chrono::Duration::hours(5)), "5 hours ago");
+        assert_eq!(ago(chrono::Duration::days(1)), "1 day ago");
+        assert_eq!(ago(chrono::Duration::days(65)), "2 months ago");
+        assert_eq!(ago(chrono::Duration::days(800)), "2 years ago");
+        assert_eq!(
+            relative_time(now + chrono::Duration::hours(1), now),
+            "just now"
+        );
+    }
+
+    #[test]
+    fn test_search_bucketed() -> Result<()> {
+        let mut cache = create_test_cache();
+        let now = Utc::now();
+        let ages = [
+            ("today", 0),
+            ("last-month", 30),
+            ("yesterday", 1),
+            ("this-week", 3),
+            ("last-year", 365),
+        ];
+        for (name, days) in ages {
+            cache.add(
+                Link::new(
+                    format!("https://example.com/{}", name),
+                    "Rust Notes".to_string(),
+                )
+                .with_timestamp_seconds((now - chrono::Duration::days(days)).timestamp()),
+            )?;
+        }
+
+        let buckets = cache.search_bucketed("rust")?;
+        let grouped: Vec<(RecencyBucket, Vec<&str>)> = buckets
+            .iter()
+            .map(|(bucket, links)| {
+                let mut urls: Vec<&str> = links.iter().map(|l| l.url.as_str()).collect();
+                urls.sort();
+                (*bucket, urls)
+            })
+            .collect();
+        assert_eq!(
+            grouped,
+            vec![
+                (RecencyBucket::Today, vec!["https://example.com/today"]),
+                (
+                    RecencyBucket::Yesterday,
+                    vec!["https://example.com/yesterday"]
+                ),
+                (
+                    RecencyBucket::ThisWeek,
+                    vec!["https://example.com/this-week"]
+                ),
+                (
+                    RecencyBucket::Older,
+                    vec![
+                        "https://example.com/last-month",
+                        "https://example.com/last-year"
+                    ]
+                ),
+            ]
+        );
+
+        // Empty buckets are left out
+        let buckets = cache.search_bucketed("")?;
+        assert_eq!(buckets.len(), 4);
+        assert!(cache.search_bucketed("python")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_recency_bucket_boundaries() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 15).unwrap();
+        let at = |day: u32, hour: u32| {
+            NaiveDate::from_ymd_opt(2024, 3, day)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+                .and_local_timezone(Local)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        assert_eq!(RecencyBucket::of(at(15, 0), today), RecencyBucket::Today);
+        assert_eq!(
+            RecencyBucket::of(at(14, 23), today),
+            RecencyBucket::Yesterday
+        );
+        assert_eq!(
+            RecencyBucket::of(at(14, 0), today),
+            RecencyBucket::Yesterday
+        );
+        assert_eq!(RecencyBucket::of(at(9, 12), today), RecencyBucket::ThisWeek);
+        assert_eq!(RecencyBucket::of(at(8, 12), today), RecencyBucket::Older);
+    }
+
+    #[test]
+    fn test_search_after_is_stable_across_inserts() -> Result<()> {
+        let mut cache = create_test_cache();
+        for title in [
+            "Rust",
+            "Rust Book",
+            "Rust by Example Exercises",
+            "Rust Reference Manual for the Language",
+            "Rust Nomicon: The Dark Arts of Unsafe Rust Programs",
+        ] {
+            cache.add(Link::new(
+                format!("https://example.com/{}", title.replace(' ', "-")),
+                title.to_string(),
+            ))?;
+        }
+
+        let first = cache.search_after("rust", None, None, 2)?;
+        assert_eq!(first.len(), 2);
+        let cursor = first.last().unwrap();
+        let second = cache.search_after("rust", cursor.score, Some(&cursor.url), 2)?;
+        let second_urls: Vec<String> = second.iter().map(|l| l.url.clone()).collect();
+        assert_eq!(second_urls.len(), 2);
+        assert!(first.iter().all(|l| !second_urls.contains(&l.url)));
+
+        cache.add(Link::new(
+            "https://example.com/rust-trivia".to_string(),
+            "An Assorted Collection of Miscellaneous Trivia, Including Some Rust".to_string(),
+        ))?;
+        let again = cache.search_after("rust", cursor.score, Some(&cursor.url), 2)?;
+        let again_urls: Vec<String> = again.iter().map(|l| l.url.clone()).collect();
+        assert_eq!(again_urls, second_urls);
+
+        // Paging through every match, including equally-ranked ones, visits
+        // each exactly once
+        cache.add(Link::new(
+            "https://example.org/Rust-Book".to_string(),
+            "Rust Book".to_string(),
+        ))?;
+        let mut paged: Vec<String> = vec![];
+        let mut cursor: Option<Link> = None;
+        loop {
+            let page = cache.search_after(
+                "rust",
+                cursor.as_ref().and_then(|l| l.score),
+                cursor.as_ref().map(|l| l.url.as_str()),
+                2,
+            )?;
+            if page.is_empty() {
+                break;
+            }
+            paged.extend(page.iter().map(|l| l.url.clone()));
+            cursor = page.last().cloned();
+        }
+        let mut all: Vec<String> = cache
+            .search_limited("rust", None)?
+            .into_iter()
+            .map(|l| l.url)
+            .collect();
+        assert_eq!(paged.len(), all.len());
+        paged.sort();
+        all.sort();
+        assert_eq!(paged, all);
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_in_source() -> Result<()> {
+        let mut cache = create_test_cache();
+        for source in ["firefox", "chrome"] {
+            for page in ["book", "reference"] {
+                cache.add(
+                    Link::new(
+                        format!("https://example.com/{}/{}", source, page),
+                        format!("Rust {}", page),
+                    )
+                    .with_source(source.to_string()),
+                )?;
+            }
+        }
+
+        let results = cache.search_in_source("rust", "firefox")?;
+        assert_eq!(results.len(), 2);
+        assert!(results
+            .iter()
+            .all(|l| l.source.as_deref() == Some("firefox")));
+        assert_eq!(results[0].score, Some(1.0));
+
+        let results = cache.search_in_source("reference", "chrome")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].url, "https://example.com/chrome/reference");
+
+        let latest = cache.search_in_source("", "chrome")?;
+        assert_eq!(latest.len(), 2);
+        assert!(latest.iter().all(|l| l.source.as_deref() == Some("chrome")));
+        assert!(cache.search_in_source("rust", "arc")?.is_empty());
+
+        // More than a search's worth of better-ranked (and newer) links from
+        // another source don't crowd out the source's own matches
+        for i in 0..(DEFAULT_SEARCH_LIMIT + 10) {
+            cache.add(
+                Link::new(
+                    format!("https://example.com/chrome/{}", i),
+                    "Rust".to_string(),
+                )
+                .with_source("chrome".to_string())
+                .with_timestamp_seconds(2_000_000_000 + i64::from(i)),
+            )?;
+        }
+        for query in ["site:example.com rust", "https://example.com/firefox/book"] {
+            let results = cache.search_in_source(query, "firefox")?;
+            assert_eq!(results.len(), 2, "searching {}", query);
+            assert!(results
+                .iter()
+                .all(|l| l.source.as_deref() == Some("firefox")));
+        }
+        assert_eq!(
+            cache.search_in_source("rust", "chrome")?.len(),
+            DEFAULT_SEARCH_LIMIT as usize
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_excluding_urls() -> Result<()> {
+        let mut cache = create_test_cache();
+        for (url, title) in [
+            ("https://example.com/rust/book", "Rust Book"),
+            ("https://example.com/rust/reference", "Rust Reference"),
+            ("https://example.com/rust/nomicon", "Rust Nomicon"),
+        ] {
+            cache.add(Link::new(url.to_string(), title.to_string()))?;
+        }
+
+        let results = cache.search_excluding_urls(
+            "rust",
+            &[
+                "https://EXAMPLE.com/rust/book#chapter-1",
+                "https://example.com/rust/nomicon?utm_source=tabs",
+            ],
+        )?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].url, "https://example.com/rust/reference");
+
+        assert_eq!(cache.search_excluding_urls("rust", &[])?.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_folds_diacritics() -> Result<()> {
+        let mut cache = create_test_cache();
+        cache.add(Link::new(
+            "https://example.com/muenchen".to_string(),
+            "München Travel Guide".to_string(),
+        ))?;
+        cache.add(Link::new(
+            "https://example.com/munchen-cafes".to_string(),
+            "Munchen Cafes".to_string(),
+        ))?;
+
+        let results = cache.search("Munchen Travel")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].title, "München Travel Guide");
+
+        let results = cache.search("München Cafes")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].title, "Munchen Cafes");
+
+        assert_eq!(cache.search("münchen")?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_matches_inside_identifiers() -> Result<()> {
+        let mut cache = create_test_cache();
+        cache.add(Link::new(
+            "https://react.dev/reference/react/useEffect".to_string(),
+            "useEffectHook".to_string(),
+        ))?;
+        cache.add(Link::new(
+            "https://docs.rs/async-trait".to_string(),
+            "async_trait".to_string(),
+        ))?;
+
+        let results = cache.search("effect")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].title, "useEffectHook");
+
+        let results = cache.search("trait")?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].title, "async_trait");
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_with_fallback_strategies() -> Result<()> {
+        let mut cache = create_test_cache();
+        cache.add(
+            Link::new(
+                "https://example.com/old".to_string(),
+                "Rust Notes".to_string(),
+            )
+            .with_timestamp_seconds(1_600_000_000),
+        )?;
+        cache.add(
+            Link::new("https://example.com/new".to_string(), "Rust".to_string())
+                .with_timestamp_seconds(1_700_000_000),
+        )?;
+
+        cache.rank_strategy = RankStrategy::Bm25;
+        assert_eq!(cache.search("Rust")?.len(), 2);
+
+        cache.rank_strategy = RankStrategy::Recency;
+        let results = cache.search("Rust")?;
+        assert_eq!(results[0].url, "https://example.com/new");
+        assert_eq!(results[1].url, "https://example.com/old");
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{Cache, Link, Result};
+
+/// A browser (or other application) whose links can be imported into a
+/// Cache, so code which refreshes the cache (like the linkcache binary) can
+/// treat every browser alike, e.g. as a Vec<Box<dyn BrowserSource>>. Sources
+/// must be shareable between threads, so they can be read in parallel (see
+/// cache_sources).
+pub trait BrowserSource: Send + Sync {
+    /// Reads every link this browser offers for caching.
+    fn links(&self) -> Result<Vec<Link>>;
+
+    /// The source recorded on the links, e.g. "firefox".
+    fn source_name(&self) -> &'static str;
+
+    /// Adds every link from this browser to the provided Cache in a single
+    /// batch. Returns the number of links added.
+    fn cache_links(&self, cache: &mut Cache) -> Result<usize> {
+        cache.add_batch(self.links()?)
+    }
+}
+
+/// Adds the links of every source to the provided Cache, one batch per
+/// source, in the order given. Returns the total number of links added, or
+/// the first error reading or writing a source's links, after which later
+/// sources aren't written.
+///
+/// With the parallel feature, every source's links are read on a thread of
+/// their own while the calling thread writes them, still in the order given
+/// so the result is the same as reading them one at a time.
+pub fn cache_sources(cache: &mut Cache, sources: &[Box<dyn BrowserSource>]) -> Result<usize> {
+    #[cfg(feature = "parallel")]
+    {
+        cache_sources_in_parallel(cache, sources)
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    {
+        let mut added = 0;
+        for source in sources {
+            added += source.cache_links(cache)?;
+        }
+        Ok(added)
+    }
+}
+
+/// Reads each source's links on a thread of its own and sends them to the
+/// calling thread, which writes them since the Cache's connection can't be
+/// shared. Links which arrive before those of an earlier source wait for
+/// them.
+#[cfg(feature = "parallel")]
+fn cache_sources_in_parallel(
+    cache: &mut Cache,
+    sources: &[Box<dyn BrowserSource>],
+) -> Result<usize> {
+    use std::collections::BTreeMap;
+    use std::sync::mpsc;
+
+    std::thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for (index, source) in sources.iter().enumerate() {
+            let sender = sender.clone();
+            scope.spawn(move || {
+                // The receiver is only gone once writing has failed
+                let _ = sender.send((index, source.links()));
+            });
+        }
+        drop(sender);
+
+        let mut read = BTreeMap::new();
+        let mut next = 0;
+        let mut added = 0;
+        for (index, links) in receiver {
+            read.insert(index, links);
+            while let Some(links) = read.remove(&next) {
+                added += cache.add_batch(links?)?;
+                next += 1;
+            }
+        }
+        Ok(added)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::{build_firefox_fixture, create_test_cache};
+    use crate::{arc, firefox};
+    use std::path::PathBuf;
+
+    type Contents = Vec<(String, String, Option<String>, Option<String>)>;
+
+    /// The stored links, as the fields the sources provide, sorted by url.
+    fn contents(cache: &Cache) -> Result<Contents> {
+        let mut links: Vec<_> = cache
+            .get_latest_n(1000)?
+            .into_iter()
+            .map(|link| (link.url, link.title, link.subtitle, link.source))
+            .collect();
+        links.sort();
+        Ok(links)
+    }
+
+    #[test]
+    fn test_cache_sources_matches_sequential_import() -> Result<()> {
+        let profile_dir = tempfile::tempdir()?;
+        build_firefox_fixture(&profile_dir.path().join("places.sqlite"))?;
+        let sources: Vec<Box<dyn BrowserSource>> = vec![
+            Box::new(arc::Browser::new().with_profile_dir(PathBuf::from("./test_data"))),
+            Box::new(firefox::Browser::from_profile_dir(
+                profile_dir.path().to_path_buf(),
+            )),
+            Box::new(
+                arc::Browser::new()
+                    .with_profile_dir(PathBuf::from("./test_data"))
+                    .with_include_archived(true),
+            ),
+        ];
+
+        let mut sequential = create_test_cache();
+        let mut expected = 0;
+        for source in &sources {
+            expected += source.cache_links(&mut sequential)?;
+        }
+        let mut cache = create_test_cache();
+        assert_eq!(cache_sources(&mut cache, &sources)?, expected);
+        assert!(expected > 0);
+        assert_eq!(contents(&cache)?, contents(&sequential)?);
+        Ok(())
+    }
+}
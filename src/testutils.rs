@@ -0,0 +1,118 @@
+use rusqlite::Connection;
+use std::path::Path;
+
+use crate::{Cache, CacheBuilder, Result};
+
+/// Creates a Cache backed by an in-memory database, so each test starts
+/// with an empty cache of its own.
+pub(crate) fn create_test_cache() -> Cache {
+    CacheBuilder::new()
+        .in_memory()
+        .build()
+        .expect("Failed to create test cache")
+}
+
+/// Creates a minimal Firefox places.sqlite at the provided path, with the
+/// subset of the moz_places/moz_bookmarks schema the firefox module reads,
+/// seeded with a known set of rows:
+///
+/// - "The Rust Book" in Bookmarks Toolbar / Dev, also tagged "rust" and
+///   given the keyword "rb"
+/// - "SQLite FTS5 Extension" in the Bookmarks Menu, next to a separator,
+///   with a description annotation
+/// - An untitled bookmark (whose page has no title either) in Other
+///   Bookmarks
+/// - "Recipes" in Mobile Bookmarks
+/// - Hacker News, visited but never bookmarked
+/// - A prefetched page which was never actually visited
+///
+/// New scenarios should be added here rather than committed as binary
+/// fixtures.
+pub(crate) fn build_firefox_fixture(path: &Path) -> Result<()> {
+    let conn = Connection::open(path)?;
+    conn.execute_batch(
+        "CREATE TABLE moz_places (
+            id INTEGER PRIMARY KEY,
+            url LONGVARCHAR,
+            title LONGVARCHAR,
+            rev_host LONGVARCHAR,
+            visit_count INTEGER DEFAULT 0,
+            hidden INTEGER DEFAULT 0 NOT NULL,
+            typed INTEGER DEFAULT 0 NOT NULL,
+            frecency INTEGER DEFAULT -1 NOT NULL,
+            last_visit_date INTEGER,
+            guid TEXT,
+            description TEXT
+        );
+        CREATE TABLE moz_bookmarks (
+            id INTEGER PRIMARY KEY,
+            type INTEGER,
+            fk INTEGER DEFAULT NULL,
+            parent INTEGER,
+            position INTEGER,
+            title LONGVARCHAR,
+            keyword_id INTEGER,
+            folder_type TEXT,
+            dateAdded INTEGER,
+            lastModified INTEGER,
+            guid TEXT
+        );
+        CREATE TABLE moz_anno_attributes (
+            id INTEGER PRIMARY KEY,
+            name VARCHAR(32) UNIQUE NOT NULL
+        );
+        CREATE TABLE moz_items_annos (
+            id INTEGER PRIMARY KEY,
+            item_id INTEGER NOT NULL,
+            anno_attribute_id INTEGER,
+            content LONGVARCHAR,
+            flags INTEGER DEFAULT 0,
+            expiration INTEGER DEFAULT 0,
+            type INTEGER DEFAULT 0,
+            dateAdded INTEGER DEFAULT 0,
+            lastModified INTEGER DEFAULT 0
+        );
+        CREATE TABLE moz_keywords (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            keyword TEXT UNIQUE,
+            place_id INTEGER,
+            post_data TEXT
+        );
+
+        INSERT INTO moz_places (id, url, title, visit_count, typed, last_visit_date, guid) VALUES
+            (1, 'https://doc.rust-lang.org/book/', 'The Rust Programming Language', 12, 1, 1710489600000000, 'place-rust-book'),
+            (2, 'https://www.sqlite.org/fts5.html', 'SQLite FTS5 Extension', 3, 0, 1710403200000000, 'place-fts5'),
+            (3, 'https://example.com/untitled', NULL, 1, 0, 1710316800000000, 'place-untitled'),
+            (4, 'https://news.ycombinator.com/', 'Hacker News', 40, 1, 1710576000000000, 'place-hn'),
+            (5, 'https://example.com/prefetched', 'Prefetched', 0, 0, NULL, 'place-prefetched'),
+            (6, 'https://m.example.com/recipes', 'Recipes', 2, 0, 1710230400000000, 'place-recipes');
+
+        -- type 1 is a bookmark, 2 a folder and 3 a separator
+        INSERT INTO moz_bookmarks (id, type, fk, parent, position, title, dateAdded, lastModified, guid) VALUES
+            (1, 2, NULL, 0, 0, '', 1700000000000000, 1700000000000000, 'root________'),
+            (2, 2, NULL, 1, 0, 'menu', 1700000000000000, 1700000000000000, 'menu________'),
+            (3, 2, NULL, 1, 1, 'toolbar', 1700000000000000, 1700000000000000, 'toolbar_____'),
+            (4, 2, NULL, 1, 2, 'tags', 1700000000000000, 1700000000000000, 'tags________'),
+            (5, 2, NULL, 1, 3, 'unfiled', 1700000000000000, 1700000000000000, 'unfiled_____'),
+            (6, 2, NULL, 1, 4, 'mobile', 1700000000000000, 1700000000000000, 'mobile______'),
+            (7, 2, NULL, 3, 0, 'Dev', 1700000000000000, 1700000000000000, 'folder-dev'),
+            (8, 1, 1, 7, 0, 'The Rust Book', 1700000100000000, 1700000100000000, 'bookmark-rust'),
+            (9, 1, 2, 2, 0, 'SQLite FTS5 Extension', 1700000200000000, 1700000200000000, 'bookmark-fts5'),
+            (10, 3, NULL, 2, 1, NULL, 1700000200000000, 1700000200000000, 'separator-1'),
+            (11, 1, 3, 5, 0, '', 1700000300000000, 1700000300000000, 'bookmark-untitled'),
+            (12, 2, NULL, 4, 0, 'rust', 1700000400000000, 1700000400000000, 'tag-rust'),
+            (13, 1, 1, 12, 0, NULL, 1700000400000000, 1700000400000000, 'tag-entry-rust'),
+            (14, 1, 6, 6, 0, 'Recipes', 1700000500000000, 1700000500000000, 'bookmark-recipes');
+
+        INSERT INTO moz_anno_attributes (id, name) VALUES
+            (1, 'bookmarkProperties/description'),
+            (2, 'sync/parent');
+        INSERT INTO moz_items_annos (item_id, anno_attribute_id, content) VALUES
+            (9, 1, 'Virtual table module for full-text search'),
+            (9, 2, 'menu________');
+
+        INSERT INTO moz_keywords (id, keyword, place_id) VALUES (1, 'rb', 1);
+        UPDATE moz_bookmarks SET keyword_id = 1 WHERE id = 8;",
+    )?;
+    Ok(())
+}
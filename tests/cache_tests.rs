@@ -1,4 +1,4 @@
-use std::{fs::Permissions, os::unix::fs::PermissionsExt};
+use std::{fs::Permissions, os::unix::fs::PermissionsExt, path::PathBuf};
 
 use linkcache::*;
 use tempfile::*;
@@ -42,3 +42,56 @@ fn test_indexing_chrome_bookmarks() -> Result<()> {
         .expect("Failed to cache bookmarks");
     Ok(())
 }
+
+#[test]
+fn test_caching_firefox_bookmarks_is_idempotent() -> Result<()> {
+    let (mut cache, _temp_dir) = test_cache_instance();
+    let browser = firefox::Browser::from_profile_dir(PathBuf::from(
+        "test_data/FirefoxProfileDir/5abcyz0s.default-release",
+    ));
+
+    browser.cache_bookmarks(&mut cache)?;
+    let first_run = cache.search("")?;
+    assert_eq!(first_run.len(), 3);
+
+    // The background refresh re-imports the same bookmarks repeatedly
+    browser.cache_bookmarks(&mut cache)?;
+    let second_run = cache.search("")?;
+    assert_eq!(first_run.len(), second_run.len());
+
+    let results = cache.search("FTS5")?;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].url, "https://www.sqlite.org/fts5.html");
+    Ok(())
+}
+
+#[test]
+fn test_importing_json_file() -> Result<()> {
+    let (mut cache, temp_dir) = test_cache_instance();
+    let input = temp_dir.path().join("links.json");
+    std::fs::write(
+        &input,
+        r#"[
+            {"url": "https://docs.rs/rusqlite", "title": "rusqlite documentation"},
+            {"url": "https://docs.rs/chrono", "title": "chrono documentation",
+             "subtitle": "Crates", "timestamp": "2024-03-15T08:00:00Z"}
+        ]"#,
+    )?;
+
+    let count = import::import_file(&mut cache, import::Format::Json, &input)?;
+    assert_eq!(count, 2);
+
+    let results = cache.search("rusqlite")?;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].url, "https://docs.rs/rusqlite");
+    assert_eq!(results[0].source, Some("import:json".to_string()));
+    Ok(())
+}
+
+#[test]
+fn test_importing_malformed_json_file_fails() {
+    let (mut cache, temp_dir) = test_cache_instance();
+    let input = temp_dir.path().join("links.json");
+    std::fs::write(&input, "[{\"url\": ").expect("Failed to write input");
+    assert!(import::import_file(&mut cache, import::Format::Json, &input).is_err());
+}